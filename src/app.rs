@@ -3,13 +3,21 @@
 //! This module contains TUI-specific state and acts as an adapter between
 //! the core business logic (ManagerState) and the ratatui UI.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use color_eyre::Result;
 use ratatui::widgets::{ListState, TableState};
 
+use synh8::changelog::{Changelog, ChangelogEntry, ChangelogLoader, ChangelogRequest, entries_between, parse_changelog};
+use synh8::config::Config;
 use synh8::core::{ManagerState, check_apt_lock};
+use synh8::files::{self, FileLookup, PackageFile};
+use synh8::filter::Filter;
+use synh8::keymap::{Action, Keymap};
 use synh8::progress::{ProgressState, StdioRedirect, TuiAcquireProgress, TuiInstallProgress};
+use synh8::root;
+use synh8::simulate::Simulation;
+use synh8::theme::{Theme, ThemeName};
 use synh8::types::*;
 
 /// UI widget state for the main views
@@ -22,13 +30,39 @@ pub struct UiState {
     pub visual_mode: bool,
 }
 
+/// A row of the filter pane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterRow {
+    Category(FilterCategory),
+    /// A named filter from config.toml
+    Custom(String),
+    /// Group title, skipped by the cursor
+    Heading(&'static str),
+    /// Clears the repository filter
+    AnyRepo,
+    Repo(RepoFilter),
+    /// Clears the section filter
+    AnySection,
+    Section(String),
+}
+
 /// Details pane state and cached data
 pub struct DetailsState {
     pub scroll: u16,
     pub tab: DetailsTab,
     pub cached_deps: Vec<(String, String)>,
     pub cached_rdeps: Vec<(String, String)>,
+    pub cached_details: PackageDetails,
     pub cached_pkg_name: String,
+    /// Files of `cached_files_pkg`, loaded only while the Files tab is shown
+    pub cached_files: Vec<PackageFile>,
+    pub cached_files_pkg: Option<String>,
+    /// True when `cached_files` came from the Contents indexes
+    pub files_from_contents: bool,
+    /// Scans the Contents indexes for packages that aren't installed
+    pub contents_lookup: FileLookup<Vec<PackageFile>>,
+    /// Substring the Files tab is filtered by
+    pub file_filter: String,
 }
 
 impl Default for DetailsState {
@@ -38,7 +72,64 @@ impl Default for DetailsState {
             tab: DetailsTab::Info,
             cached_deps: Vec::new(),
             cached_rdeps: Vec::new(),
+            cached_details: PackageDetails::default(),
             cached_pkg_name: String::new(),
+            cached_files: Vec::new(),
+            cached_files_pkg: None,
+            files_from_contents: false,
+            contents_lookup: FileLookup::new(files::contents_files),
+            file_filter: String::new(),
+        }
+    }
+}
+
+/// A package shown in the changelog view
+pub struct ChangelogTarget {
+    pub name: String, // Display name
+    pub installed: Option<String>,
+    pub version: String, // Version whose changelog is loaded (candidate, or installed)
+    pub entries: Vec<ChangelogEntry>,
+    pub raw: Vec<String>, // Shown when no entries could be parsed (unusual formats)
+    pub note: Option<String>, // Load error, or why the installed version's changelog is shown
+    pub fallback: bool,       // Entries are the installed version's, not `version`'s
+    pub loaded: bool,
+}
+
+impl ChangelogTarget {
+    fn new(name: String, installed: Option<String>, version: String) -> Self {
+        Self {
+            name, installed, version,
+            entries: Vec::new(), raw: Vec::new(), note: None, fallback: false, loaded: false,
+        }
+    }
+
+    fn fill(&mut self, result: Result<Changelog, String>) {
+        match result {
+            Ok(Changelog { lines, fallback }) => {
+                self.entries = parse_changelog(&lines);
+                if self.entries.is_empty() {
+                    self.raw = lines;
+                }
+                self.fallback = fallback.is_some();
+                self.note = fallback;
+            }
+            Err(e) => self.note = Some(e),
+        }
+        self.loaded = true;
+    }
+
+    /// Whether the changelog covers an upgrade, so a delta can be shown
+    pub fn is_upgrade(&self) -> bool {
+        self.installed.as_ref().is_some_and(|inst| *inst != self.version)
+    }
+
+    /// Entries to show: the installed → candidate delta for upgrades unless
+    /// `full`. A fallback has no candidate entries, so it shows everything.
+    pub fn visible_entries(&self, full: bool) -> Vec<&ChangelogEntry> {
+        if full || self.fallback || !self.is_upgrade() {
+            self.entries.iter().collect()
+        } else {
+            entries_between(&self.entries, self.installed.as_deref(), &self.version).collect()
         }
     }
 }
@@ -48,8 +139,18 @@ impl Default for DetailsState {
 pub struct ModalState {
     pub mark_confirm_scroll: u16,
     pub changes_scroll: u16,
+    pub changes_tab: ChangesTab,
+    pub simulation: Option<Result<Simulation, String>>, // Dry run of the plan, run when its tab opens
     pub changelog_scroll: u16,
-    pub changelog_content: Vec<String>,
+    pub changelog: ChangelogLoader,
+    pub changelog_targets: Vec<ChangelogTarget>,
+    pub changelog_full: bool,         // Show full history instead of installed → candidate delta
+    pub changelog_from_changes: bool, // Opened from the changes modal (Esc returns there)
+    pub versions: Vec<AvailableVersion>,
+    pub version_selection: usize,
+    pub version_pkg: Option<PackageId>,
+    pub help_scroll: u16,
+    pub help_return: Option<AppState>, // State the help overlay was opened from
 }
 
 /// TUI Application - wraps ManagerState with UI state
@@ -63,10 +164,14 @@ pub struct App {
     pub modals: ModalState,
     pub state: AppState,
     pub settings: Settings,
+    pub theme: Theme,
+    pub keymap: Keymap,
     pub settings_selection: usize,
     pub col_widths: ColumnWidths,
     pub status_message: String,
     pub output_lines: Vec<String>,
+    /// Finds the owners of a path query in the background
+    pub owner_lookup: FileLookup<HashMap<String, f64>>,
 
     /// Mark preview state (shown before confirming package mark)
     pub mark_preview: Option<MarkPreview>,
@@ -76,11 +181,19 @@ pub struct App {
 
 impl App {
     pub fn new() -> Result<Self> {
-        let core = ManagerState::new()?;
+        let mut core = ManagerState::new()?;
         let mut filter_state = ListState::default();
         filter_state.select(Some(0));
 
-        let settings = Settings::default();
+        let (config, mut config_warnings) = Config::load();
+        let settings = config.to_settings();
+        let (theme, theme_warnings) = Theme::from_config(&config.theme);
+        config_warnings.extend(theme_warnings);
+        let (keymap, keymap_warnings) = Keymap::from_config(&config.keys);
+        config_warnings.extend(keymap_warnings);
+        let (custom_filters, filter_warnings) = Filter::from_config(&config.filters);
+        config_warnings.extend(filter_warnings);
+        core.set_custom_filters(custom_filters);
         let mut app = Self {
             core,
             ui: UiState {
@@ -95,10 +208,13 @@ impl App {
             modals: ModalState::default(),
             state: AppState::Listing,
             settings,
+            theme,
+            keymap,
             settings_selection: 0,
             col_widths: ColumnWidths::new(),
             status_message: String::from("Loading..."),
             output_lines: Vec::new(),
+            owner_lookup: FileLookup::new(files::find_owners),
             mark_preview: None,
             mark_preview_scroll: 0,
             output_scroll: 0,
@@ -106,8 +222,20 @@ impl App {
 
         // Sync sort settings from UI settings to core
         app.core.set_sort(app.settings.sort_by, app.settings.sort_ascending);
+        let filter_idx = FilterCategory::all().iter()
+            .position(|&f| f == app.settings.default_filter)
+            .unwrap_or(0);
+        app.ui.filter_state.select(Some(filter_idx));
+        app.core.apply_filter(app.settings.default_filter);
+        app.details.tab = app.settings.details_tab;
+        // Load the stored search index now so the first `/` is instant. A
+        // failure is reported when search is opened and it's retried.
+        app.core.ensure_search_index().ok();
         app.refresh_ui_state();
         app.update_status_message();
+        if !config_warnings.is_empty() {
+            app.status_message = config_warnings.join(" | ");
+        }
         Ok(app)
     }
 
@@ -169,22 +297,111 @@ impl App {
             .map(|p| p.name.clone())
             .unwrap_or_default();
 
-        if pkg_name == self.details.cached_pkg_name {
+        if pkg_name != self.details.cached_pkg_name {
+            self.details.cached_pkg_name = pkg_name.clone();
+            self.details.cached_deps = self.core.get_dependencies(&pkg_name);
+            self.details.cached_rdeps = self.core.get_reverse_dependencies(&pkg_name);
+            self.details.cached_details = self.core.cache().get_details(&pkg_name);
+        }
+        self.update_cached_files();
+    }
+
+    /// Load the selected package's files if the Files tab shows them. Lists
+    /// of packages that aren't installed come from the Contents indexes,
+    /// which are scanned in the background (see `poll_files`).
+    fn update_cached_files(&mut self) {
+        if self.details.tab != DetailsTab::Files {
+            return;
+        }
+        let Some(pkg) = self.selected_package() else {
+            self.details.contents_lookup.cancel();
+            self.details.cached_files.clear();
+            self.details.cached_files_pkg = None;
+            return;
+        };
+        if self.details.cached_files_pkg.as_ref() == Some(&pkg.name) {
             return;
         }
-        self.details.cached_pkg_name = pkg_name.clone();
-        self.details.cached_deps = self.core.get_dependencies(&pkg_name);
-        self.details.cached_rdeps = self.core.get_reverse_dependencies(&pkg_name);
+
+        let name = pkg.name.clone();
+        let installed = (!pkg.installed_version.is_empty())
+            .then(|| files::installed_files(&name, &pkg.architecture))
+            .flatten();
+        self.details.files_from_contents = installed.is_none();
+        match installed {
+            Some(files) => {
+                self.details.contents_lookup.cancel();
+                self.details.cached_files = files;
+            }
+            None => {
+                self.details.cached_files.clear();
+                self.details.contents_lookup.request(name.clone());
+            }
+        }
+        self.details.cached_files_pkg = Some(name);
+    }
+
+    /// Take the results of the background file lookups once they're ready:
+    /// the Contents scan for the Files tab and the owners of a path search
+    pub fn poll_files(&mut self) {
+        if let Some((name, files)) = self.details.contents_lookup.poll()
+            && self.details.cached_files_pkg.as_ref() == Some(&name) {
+                self.details.cached_files = files;
+        }
+
+        // A query edited since is not the one that was looked up
+        if let Some((query, owners)) = self.owner_lookup.poll()
+            && self.core.search_query().trim() == query {
+                self.core.set_path_results(owners);
+                self.refresh_ui_state();
+                self.status_message = format!(
+                    "Found {} packages owning files matching '{query}'",
+                    self.core.search_result_count().unwrap_or(0),
+                );
+        }
+    }
+
+    /// Whether the Files tab is still waiting for the Contents scan
+    pub fn files_loading(&self) -> bool {
+        self.details.contents_lookup.pending().is_some()
+    }
+
+    /// Files of the selected package that contain the filter text
+    pub fn visible_files(&self) -> impl Iterator<Item = &PackageFile> {
+        self.details.cached_files.iter()
+            .filter(|f| f.path.contains(self.details.file_filter.as_str()))
+    }
+
+    // === File filter ===
+
+    pub fn start_file_filter(&mut self) {
+        self.state = AppState::FilteringFiles;
+    }
+
+    pub fn file_filter_push(&mut self, c: char) {
+        self.details.file_filter.push(c);
+        self.details.scroll = 0;
+    }
+
+    pub fn file_filter_pop(&mut self) {
+        self.details.file_filter.pop();
+        self.details.scroll = 0;
+    }
+
+    pub fn cancel_file_filter(&mut self) {
+        self.details.file_filter.clear();
+        self.details.scroll = 0;
+        self.state = AppState::Listing;
     }
 
     // === Search ===
 
     pub fn start_search(&mut self) {
         match self.core.ensure_search_index() {
-            Ok(duration) => {
-                if duration.as_millis() > 0 {
+            Ok((count, duration)) => {
+                if count > 0 {
                     self.status_message = format!(
-                        "Search index built in {:.0}ms",
+                        "Search index updated ({count} packages) in {:.0}ms",
                         duration.as_secs_f64() * 1000.0
                     );
                 }
@@ -206,6 +423,7 @@ impl App {
     }
 
     pub fn cancel_search(&mut self) {
+        self.owner_lookup.cancel();
         self.core.clear_search();
         self.state = AppState::Listing;
         self.refresh_ui_state();
@@ -214,7 +432,16 @@ impl App {
 
     pub fn confirm_search(&mut self) {
         self.state = AppState::Listing;
-        if let Some(count) = self.core.search_result_count() {
+        if files::is_path_query(self.core.search_query()) {
+            // Reading every file list takes a while: show nothing until it's done
+            let query = self.core.search_query().trim().to_string();
+            self.core.set_path_results(HashMap::new());
+            self.refresh_ui_state();
+            self.status_message = format!("Looking up packages owning files matching '{query}'...");
+            self.owner_lookup.request(query);
+        } else if let Some(error) = self.core.search_error() {
+            self.status_message = format!("Search error: {error}");
+        } else if let Some(count) = self.core.search_result_count() {
             self.status_message = format!(
                 "Found {} packages matching '{}'",
                 count,
@@ -231,17 +458,62 @@ impl App {
         self.reset_selection();
     }
 
+    /// Rows of the filter pane: the status categories and custom filters, the repository tree
+    /// with only the branch of the active repository expanded, then sections
+    pub fn filter_rows(&self) -> Vec<FilterRow> {
+        let mut rows: Vec<FilterRow> = FilterCategory::all().iter().map(|&c| FilterRow::Category(c)).collect();
+        rows.extend(self.core.custom_filter_counts().into_iter().map(|(name, _)| FilterRow::Custom(name.to_string())));
+        rows.push(FilterRow::Heading("Repositories"));
+        rows.push(FilterRow::AnyRepo);
+
+        let active_path: Vec<RepoFilter> = std::iter::successors(self.core.repo_filter().cloned(), RepoFilter::parent)
+            .collect();
+        for repo in self.core.repo_counts().keys() {
+            if repo.parent().is_none_or(|parent| active_path.contains(&parent)) {
+                rows.push(FilterRow::Repo(repo.clone()));
+            }
+        }
+
+        rows.push(FilterRow::Heading("Sections"));
+        rows.push(FilterRow::AnySection);
+        rows.extend(self.core.section_counts().keys().map(|s| FilterRow::Section(s.clone())));
+        rows
+    }
+
+    /// Move the filter cursor one row up (negative delta) or down, and apply
+    /// the row it lands on. Status, repository and section filters combine.
     pub fn move_filter_selection(&mut self, delta: i32) {
+        let rows = self.filter_rows();
+        let current = self.ui.filter_state.selected().unwrap_or(0).min(rows.len() - 1);
+        let selectable = |row: &FilterRow| !matches!(row, FilterRow::Heading(_));
+        let target = if delta < 0 {
+            rows[..current].iter().rposition(selectable)
+        } else {
+            rows[current + 1..].iter().position(selectable).map(|i| current + 1 + i)
+        };
+        let Some(target) = target else {
+            return;
+        };
+
         // Cancel visual mode since the package list is about to change
         if self.ui.visual_mode {
             self.cancel_visual_mode();
         }
 
-        let filters = FilterCategory::all();
-        let current = self.ui.filter_state.selected().unwrap_or(0) as i32;
-        let new_idx = (current + delta).clamp(0, filters.len() as i32 - 1) as usize;
-        self.ui.filter_state.select(Some(new_idx));
-        self.core.apply_filter(filters[new_idx]);
+        let row = rows[target].clone();
+        match &row {
+            FilterRow::Category(category) => self.core.apply_filter(*category),
+            FilterRow::Custom(name) => self.core.apply_custom_filter(name),
+            FilterRow::AnyRepo => self.core.set_repo_filter(None),
+            FilterRow::Repo(repo) => self.core.set_repo_filter(Some(repo.clone())),
+            FilterRow::AnySection => self.core.set_section_filter(None),
+            FilterRow::Section(section) => self.core.set_section_filter(Some(section.clone())),
+            FilterRow::Heading(_) => {}
+        }
+
+        // The tree expands and collapses with the selection; follow the row
+        let idx = self.filter_rows().iter().position(|r| *r == row).unwrap_or(target);
+        self.ui.filter_state.select(Some(idx));
         self.refresh_ui_state();
     }
 
@@ -256,14 +528,147 @@ impl App {
         let pkg_name = self.core.cache().display_name(&pkg.name).to_string();
         let was_marked = pkg.status.is_marked();
 
+        // Held packages must be released before they can change
+        if !was_marked && self.core.is_held(id) {
+            self.status_message = format!("{pkg_name} is held - press '{}' to release the hold", self.key_hint(Action::Hold));
+            return;
+        }
+
         // Skip toggle for installed non-upgradable packages that aren't already marked
         if !was_marked && pkg.status == PackageStatus::Installed {
             self.status_message = format!("{pkg_name} is already installed and up to date");
             return;
         }
 
+        self.run_toggle(id, pkg_name, was_marked, |core| core.toggle(id));
+    }
+
+    /// Mark the selected package for install/upgrade (aptitude's `+`).
+    /// Unlike `toggle_current()` this never unmarks an install, but it does
+    /// cancel a pending removal.
+    pub fn install_current(&mut self) {
+        let Some(pkg) = self.selected_package() else {
+            return;
+        };
+        if matches!(pkg.status,
+            PackageStatus::MarkedForInstall | PackageStatus::MarkedForUpgrade | PackageStatus::MarkedForDowngrade
+        ) {
+            self.status_message = format!("{} is already marked", self.core.cache().display_name(&pkg.name));
+            return;
+        }
+        self.toggle_current();
+    }
+
+    /// Toggle the selected package's removal mark (`-` key, or `_` for purge).
+    /// Goes through the same preview/confirm flow as `toggle_current()`.
+    pub fn remove_current(&mut self, purge: bool) {
+        let Some(pkg) = self.selected_package() else {
+            return;
+        };
+        let id = pkg.id;
+        let pkg_name = self.core.cache().display_name(&pkg.name).to_string();
+        let was_marked = pkg.status.is_marked();
+
+        // Held packages must be released before they can change
+        if !was_marked && self.core.is_held(id) {
+            self.status_message = format!("{pkg_name} is held - release the hold with '{}' first", self.key_hint(Action::Hold));
+            return;
+        }
+
+        // Packages in dpkg's "rc" state can still be purged
+        let has_residual_config = pkg.status == PackageStatus::ResidualConfig
+            || self.core.user_intent(id) == UserIntent::Purge;
+        if pkg.installed_version.is_empty() && !(purge && has_residual_config) {
+            self.status_message = if has_residual_config {
+                format!("{pkg_name} is not installed - press '{}' to purge its configuration", self.key_hint(Action::Purge))
+            } else {
+                format!("{pkg_name} is not installed")
+            };
+            return;
+        }
+
+        self.run_toggle(id, pkg_name, was_marked, |core| core.toggle_remove(id, purge));
+    }
+
+    /// Hold or release the selected package in the dpkg selections (`=` key).
+    /// Holds take effect immediately and persist after synh8 exits.
+    pub fn toggle_hold(&mut self) {
+        let Some(pkg) = self.selected_package() else {
+            return;
+        };
+        let id = pkg.id;
+        let pkg_name = self.core.cache().display_name(&pkg.name).to_string();
+        let version = pkg.installed_version.clone();
+
+        if version.is_empty() {
+            self.status_message = format!("{pkg_name} is not installed");
+            return;
+        }
+
+        let hold = !self.core.is_held(id);
+        match self.core.set_hold(id, hold) {
+            Ok(()) => {
+                // A hold replaces any pending change to the package. Only
+                // drop it once the hold is in place.
+                if hold {
+                    self.core.unmark(id);
+                }
+                self.status_message = if hold {
+                    format!("{pkg_name} held at {version}")
+                } else {
+                    format!("Released hold on {pkg_name}")
+                };
+            }
+            Err(e) => self.status_message = e,
+        }
+
+        if self.core.has_marks() {
+            self.core.compute_plan();
+        }
+        self.refresh_ui_state();
+    }
+
+    /// Mark the version highlighted in the version picker for install
+    pub fn confirm_version(&mut self) {
+        let Some(id) = self.modals.version_pkg.take() else {
+            self.state = AppState::Listing;
+            return;
+        };
+        let Some(version) = self.modals.versions.get(self.modals.version_selection)
+            .map(|v| v.version.clone()) else {
+            self.state = AppState::Listing;
+            return;
+        };
+        let Some(pkg_name) = self.core.cache().fullname_of(id)
+            .map(|n| self.core.cache().display_name(n).to_string()) else {
+            self.state = AppState::Listing;
+            return;
+        };
+
+        if self.core.is_held(id) {
+            self.state = AppState::Listing;
+            self.status_message = format!("{pkg_name} is held - press '{}' to release it first", self.key_hint(Action::Hold));
+            return;
+        }
+
+        let was_marked = self.core.list().iter().any(|p| p.id == id && p.status.is_marked());
+        self.state = AppState::Listing;
+        self.run_toggle(id, pkg_name, was_marked, |core| core.mark_version(id, &version));
+    }
+
+    /// Run a toggle operation on the core and show its outcome
+    /// (confirmation modal when other packages are affected, status update otherwise)
+    fn run_toggle(
+        &mut self,
+        id: PackageId,
+        pkg_name: String,
+        was_marked: bool,
+        toggle: impl FnOnce(&mut ManagerState) -> ToggleResult,
+    ) {
         // Track if this was a user-marked package (vs dependency) BEFORE toggle
         let was_user_marked = self.core.is_user_marked(id);
+        let previous_intent = self.core.user_intent(id);
+        let prior_marks = self.core.user_marks();
 
         // Snapshot currently planned packages BEFORE the toggle so we can
         // show only the NEW dependencies in the confirmation modal
@@ -271,8 +676,8 @@ impl App {
             .map(|changes| changes.iter().map(|c| c.package).collect())
             .unwrap_or_default();
 
-        // Use the library's toggle() which handles cascade correctly
-        let result = self.core.toggle(id);
+        // Use the library's toggle which handles cascade correctly
+        let result = toggle(&mut self.core);
 
         match result {
             ToggleResult::Marked { package: _, additional } => {
@@ -283,8 +688,7 @@ impl App {
                     self.update_status_message();
                 } else {
                     // Build preview for confirmation modal (only new deps)
-                    let preview = self.core.build_mark_preview(id, &previously_planned);
-                    self.mark_preview = preview;
+                    self.mark_preview = self.core.build_mark_preview(id, &previously_planned, &prior_marks);
                     self.mark_preview_scroll = 0;
                     self.state = AppState::ShowingMarkConfirm;
                 }
@@ -304,9 +708,12 @@ impl App {
 
                     let preview = MarkPreview {
                         package_name: pkg_name,
+                        intent: previous_intent,
                         is_upgrade: was_marked,
+                        is_downgrade: false,
                         is_marking: false, // This is an unmark operation
                         was_user_marked, // Was the original package user-marked (vs dependency)?
+                        restore: self.core.changed_marks(&prior_marks, id),
                         additional_installs: Vec::new(),
                         additional_upgrades: also_names, // Reuse this field for "also unmarked"
                         additional_removes: Vec::new(),
@@ -337,53 +744,11 @@ impl App {
     }
 
     pub fn cancel_mark(&mut self) {
-        if let Some(ref preview) = self.mark_preview {
-            if preview.is_marking {
-                // Undo a mark operation - unmark the package
-                // First, find the ID using display_name comparison (immutable borrow scope)
-                let id_to_unmark = {
-                    let cache = self.core.cache();
-                    self.core.list().iter()
-                        .find(|p| cache.display_name(&p.name) == preview.package_name)
-                        .map(|p| p.id)
-                };
-                // Now mutate (borrow dropped)
-                if let Some(id) = id_to_unmark {
-                    self.core.unmark(id);
-                }
-            } else {
-                // Undo an unmark operation - re-mark the USER-MARKED packages only
-                // Dependencies will be restored automatically by compute_plan()
-                //
-                // If original was user-marked: re-mark it (deps follow)
-                // If original was a dependency: re-mark the also_unmarked (they were user-marked)
-                let names_to_remark: Vec<String> = if preview.was_user_marked {
-                    // Original package was user-marked, re-mark only it
-                    vec![preview.package_name.clone()]
-                } else {
-                    // Original was a dependency, re-mark the user-marked packages (in also_unmarked)
-                    preview.additional_upgrades.clone()
-                };
-
-                // First, collect all IDs to remark (immutable borrow scope)
-                let ids_to_remark: Vec<_> = {
-                    let cache = self.core.cache();
-                    names_to_remark.iter()
-                        .filter_map(|name| {
-                            self.core.list().iter()
-                                .find(|p| cache.display_name(&p.name) == *name)
-                                .map(|p| p.id)
-                        })
-                        .collect()
-                };
-
-                // Now mutate (borrow dropped)
-                for id in ids_to_remark {
-                    self.core.mark_install(id);
-                }
-                // Recompute plan after re-marking
-                self.core.compute_plan();
-            }
+        // Put back every mark the toggle changed: the package's own and, for
+        // an unmark, those that went with it, with their picked versions
+        if let Some(preview) = self.mark_preview.take() {
+            self.core.restore_marks(&preview.restore);
+            self.core.compute_plan();
         }
         self.mark_preview = None;
         self.refresh_ui_state();
@@ -399,6 +764,22 @@ impl App {
         self.show_changes_preview();
     }
 
+    /// Mark all packages with leftover configuration files for purge
+    /// (from the changes modal) and recompute the plan.
+    pub fn purge_residual_configs(&mut self) {
+        let count = self.core.mark_all_residual_purge();
+        if count == 0 {
+            self.status_message = "No residual configuration to purge".to_string();
+            return;
+        }
+        self.core.compute_plan();
+        self.refresh_ui_state();
+        self.modals.changes_scroll = 0;
+        self.modals.changes_tab = ChangesTab::Changes;
+        self.modals.simulation = None;
+        self.status_message = format!("Marked {count} packages with residual configuration for purge");
+    }
+
     pub fn unmark_all(&mut self) {
         self.core.reset();
         self.refresh_ui_state();
@@ -509,18 +890,28 @@ impl App {
         self.details.tab = match self.details.tab {
             DetailsTab::Info => DetailsTab::Dependencies,
             DetailsTab::Dependencies => DetailsTab::ReverseDeps,
-            DetailsTab::ReverseDeps => DetailsTab::Info,
+            DetailsTab::ReverseDeps => DetailsTab::Files,
+            DetailsTab::Files => DetailsTab::Info,
         };
         self.details.scroll = 0;
+        self.update_cached_files();
+        // Remembered for the next start
+        self.settings.details_tab = self.details.tab;
+        self.save_settings();
     }
 
     pub fn prev_details_tab(&mut self) {
         self.details.tab = match self.details.tab {
-            DetailsTab::Info => DetailsTab::ReverseDeps,
+            DetailsTab::Info => DetailsTab::Files,
             DetailsTab::Dependencies => DetailsTab::Info,
             DetailsTab::ReverseDeps => DetailsTab::Dependencies,
+            DetailsTab::Files => DetailsTab::ReverseDeps,
         };
         self.details.scroll = 0;
+        self.update_cached_files();
+        // Remembered for the next start
+        self.settings.details_tab = self.details.tab;
+        self.save_settings();
     }
 
     pub fn cycle_focus(&mut self) {
@@ -542,31 +933,132 @@ impl App {
     // === Modals ===
 
     pub fn show_changelog(&mut self) {
-        let pkg_name = match self.selected_package() {
-            Some(p) => p.name.clone(),
-            None => {
-                self.status_message = "No package selected".to_string();
-                return;
-            }
+        let Some(pkg) = self.selected_package() else {
+            self.status_message = "No package selected".to_string();
+            return;
+        };
+        let pkg_name = self.core.cache().display_name(&pkg.name).to_string();
+        let installed = (!pkg.installed_version.is_empty()).then(|| pkg.installed_version.clone());
+        let version = if pkg.candidate_version.is_empty() {
+            pkg.installed_version.clone()
+        } else {
+            pkg.candidate_version.clone()
         };
 
-        self.modals.changelog_content.clear();
-        self.modals.changelog_content.push(format!("Loading changelog for {pkg_name}..."));
-        self.modals.changelog_scroll = 0;
+        self.modals.changelog_from_changes = false;
+        self.open_changelog(vec![ChangelogTarget::new(pkg_name, installed, version)]);
+    }
 
-        match self.core.fetch_changelog(&pkg_name) {
-            Ok(lines) => {
-                self.modals.changelog_content = lines;
-            }
-            Err(e) => {
-                self.modals.changelog_content.clear();
-                self.modals.changelog_content.push(e);
+    /// Show what changes in the whole upgrade: the changelog delta of every
+    /// package the plan upgrades
+    pub fn show_upgrade_changelog(&mut self) {
+        let targets: Vec<ChangelogTarget> = self.core.planned_upgrades()
+            .into_iter()
+            .map(|(name, installed, candidate)| ChangelogTarget::new(name, Some(installed), candidate))
+            .collect();
+
+        if targets.is_empty() {
+            self.status_message = "No upgrades planned".to_string();
+            return;
+        }
+
+        self.modals.changelog_from_changes = true;
+        self.open_changelog(targets);
+    }
+
+    fn open_changelog(&mut self, mut targets: Vec<ChangelogTarget>) {
+        let requests = targets.iter()
+            .map(|t| ChangelogRequest {
+                name: t.name.clone(),
+                version: t.version.clone(),
+                installed_version: t.installed.clone(),
+            })
+            .collect();
+        self.modals.changelog.request(requests);
+
+        for target in &mut targets {
+            if let Some(lines) = self.modals.changelog.cached(&target.name, &target.version) {
+                target.fill(Ok(Changelog { lines: lines.to_vec(), fallback: None }));
             }
         }
 
+        self.modals.changelog_targets = targets;
+        self.modals.changelog_full = false;
+        self.modals.changelog_scroll = 0;
         self.state = AppState::ShowingChangelog;
     }
 
+    /// Pick up changelogs finished by the background worker
+    pub fn poll_changelog(&mut self) {
+        while let Some(((name, version), result)) = self.modals.changelog.poll() {
+            if let Some(target) = self.modals.changelog_targets.iter_mut()
+                .find(|t| t.name == name && t.version == version) {
+                    target.fill(result);
+            }
+        }
+    }
+
+    /// Switch between the installed → candidate delta and the full history
+    pub fn toggle_changelog_history(&mut self) {
+        self.modals.changelog_full = !self.modals.changelog_full;
+        self.modals.changelog_scroll = 0;
+    }
+
+    pub fn close_changelog(&mut self) {
+        self.modals.changelog.cancel();
+        self.state = if self.modals.changelog_from_changes {
+            AppState::ShowingChanges
+        } else {
+            AppState::Listing
+        };
+    }
+
+    pub fn show_versions(&mut self) {
+        let Some(pkg) = self.selected_package() else {
+            self.status_message = "No package selected".to_string();
+            return;
+        };
+        let id = pkg.id;
+
+        let versions = self.core.available_versions(id);
+        if versions.is_empty() {
+            self.status_message = "No versions available".to_string();
+            return;
+        }
+
+        // Start on the version that would be installed now
+        let chosen = self.core.chosen_version(id);
+        self.modals.version_selection = versions.iter()
+            .position(|v| Some(v.version.as_str()) == chosen)
+            .or_else(|| versions.iter().position(|v| v.is_candidate))
+            .unwrap_or(0);
+        self.modals.versions = versions;
+        self.modals.version_pkg = Some(id);
+        self.state = AppState::ShowingVersions;
+    }
+
+    pub fn move_version_selection(&mut self, delta: i32) {
+        let max = self.modals.versions.len().saturating_sub(1) as i32;
+        let current = self.modals.version_selection as i32;
+        self.modals.version_selection = (current + delta).clamp(0, max) as usize;
+    }
+
+    /// Show the keys of the current state in an overlay
+    pub fn show_help(&mut self) {
+        self.modals.help_return = Some(self.state);
+        self.modals.help_scroll = 0;
+        self.state = AppState::ShowingHelp;
+    }
+
+    pub fn close_help(&mut self) {
+        self.state = self.modals.help_return.take().unwrap_or(AppState::Listing);
+    }
+
+    /// State whose keys the help overlay lists
+    pub fn help_context(&self) -> AppState {
+        self.modals.help_return.unwrap_or(AppState::Listing)
+    }
+
     pub fn show_settings(&mut self) {
         self.settings_selection = 0;
         self.state = AppState::ShowingSettings;
@@ -592,29 +1084,69 @@ impl App {
                 self.core.set_sort(self.settings.sort_by, self.settings.sort_ascending);
                 self.col_widths = self.core.rebuild_list();
             }
+            8 => {
+                let all = FilterCategory::all();
+                let idx = all.iter().position(|&f| f == self.settings.default_filter).unwrap_or(0);
+                self.settings.default_filter = all[(idx + 1) % all.len()];
+            }
+            9 => {
+                let all = DetailsTab::all();
+                let idx = all.iter().position(|&t| t == self.settings.details_tab).unwrap_or(0);
+                self.settings.details_tab = all[(idx + 1) % all.len()];
+            }
+            10 => {
+                let all = ThemeName::all();
+                let idx = all.iter().position(|&n| n == self.settings.theme.name).unwrap_or(0);
+                self.settings.theme.name = all[(idx + 1) % all.len()];
+                self.theme = Theme::from_config(&self.settings.theme).0;
+            }
             _ => {}
         }
+        self.save_settings();
+    }
+
+    fn save_settings(&mut self) {
+        if let Err(e) = Config::from_settings(&self.settings).save() {
+            self.status_message = format!("Could not save settings: {e}");
+        }
     }
 
     pub fn settings_item_count() -> usize {
-        8
+        11
     }
 
     pub fn show_changes_preview(&mut self) {
         if self.has_pending_changes() {
             // Compute plan to get full changeset
             self.core.compute_plan();
-            self.state = AppState::ShowingChanges;
-            self.modals.changes_scroll = 0;
-        } else {
+        } else if self.core.residual_count() == 0 {
             self.status_message = "No changes to apply".to_string();
+            return;
+        }
+        // With nothing marked, the modal still offers to purge leftover configuration
+        self.state = AppState::ShowingChanges;
+        self.modals.changes_scroll = 0;
+        self.modals.changes_tab = ChangesTab::Changes;
+        self.modals.simulation = None;
+    }
+
+    /// Switch the changes modal between the change list and the dry run,
+    /// simulating the plan the first time
+    pub fn switch_changes_tab(&mut self) {
+        self.modals.changes_scroll = 0;
+        self.modals.changes_tab = match self.modals.changes_tab {
+            ChangesTab::Changes => ChangesTab::Simulation,
+            ChangesTab::Simulation => ChangesTab::Changes,
+        };
+        if self.modals.changes_tab == ChangesTab::Simulation && self.modals.simulation.is_none() {
+            self.modals.simulation = Some(self.core.simulate());
         }
     }
 
     // === Scrolling ===
 
     pub fn scroll_changelog(&mut self, delta: i32) {
-        let max_scroll = self.modals.changelog_content.len().saturating_sub(1) as u16;
+        let max_scroll = self.changelog_line_count().saturating_sub(1) as u16;
         let current = self.modals.changelog_scroll as i32;
         self.modals.changelog_scroll = (current + delta).clamp(0, max_scroll as i32) as u16;
     }
@@ -631,13 +1163,26 @@ impl App {
         self.mark_preview_scroll = (current + delta).clamp(0, max_scroll as i32) as usize;
     }
 
+    pub fn scroll_help(&mut self, delta: i32) {
+        let max_scroll = self.help_line_count().saturating_sub(1) as u16;
+        let current = self.modals.help_scroll as i32;
+        self.modals.help_scroll = (current + delta).clamp(0, max_scroll as i32) as u16;
+    }
+
     pub fn scroll_output(&mut self, delta: i32) {
         let max_scroll = self.output_lines.len().saturating_sub(1) as u16;
         let current = self.output_scroll as i32;
         self.output_scroll = (current + delta).clamp(0, max_scroll as i32) as u16;
     }
 
+    pub fn help_line_count(&self) -> usize {
+        self.keymap.bindings(self.help_context()).len() + 2 // Blank line + config hint
+    }
+
     pub fn changes_line_count(&self) -> usize {
+        if self.modals.changes_tab == ChangesTab::Simulation {
+            return self.simulation_line_count();
+        }
         match self.core.planned_changes() {
             Some(changes) => {
                 let mut lines = 2; // header + blank line
@@ -648,8 +1193,11 @@ impl App {
                     changes.iter().filter(|c| c.action == ChangeAction::Install && c.reason == ChangeReason::UserRequested).count(),
                     changes.iter().filter(|c| c.action == ChangeAction::Upgrade && c.reason == ChangeReason::Dependency).count(),
                     changes.iter().filter(|c| c.action == ChangeAction::Install && c.reason == ChangeReason::Dependency).count(),
+                    changes.iter().filter(|c| c.action == ChangeAction::Downgrade).count(),
                     changes.iter().filter(|c| c.action == ChangeAction::Remove && c.reason == ChangeReason::UserRequested).count(),
+                    changes.iter().filter(|c| c.action == ChangeAction::Remove && c.reason == ChangeReason::Dependency).count(),
                     changes.iter().filter(|c| c.action == ChangeAction::Remove && c.reason == ChangeReason::AutoRemove).count(),
+                    changes.iter().filter(|c| c.action == ChangeAction::Purge).count(),
                 ];
 
                 for count in categories {
@@ -659,12 +1207,51 @@ impl App {
                 }
 
                 lines += 3; // blank + download size + disk change
+                if self.core.residual_count() > 0 {
+                    lines += 2; // blank + purge hint
+                }
                 lines
             }
             None => 5,
         }
     }
 
+    /// Lines rendered by the simulation tab (mirrors `render_changes_modal`)
+    fn simulation_line_count(&self) -> usize {
+        match &self.modals.simulation {
+            Some(Ok(sim)) => {
+                let section = |count: usize| if count > 0 { 1 + count + 1 } else { 0 }; // header + items + blank
+                2 + section(sim.errors.len()) + section(sim.broken.len()) + section(sim.steps.len())
+                    + section(sim.triggers.len()).max(1)
+            }
+            _ => 3,
+        }
+    }
+
+    /// Lines rendered by the changelog view (mirrors `render_changelog_view`)
+    pub fn changelog_line_count(&self) -> usize {
+        let targets = &self.modals.changelog_targets;
+        let mut lines = usize::from(self.modals.changelog.is_loading()) * 2; // spinner + blank
+
+        for target in targets {
+            if targets.len() > 1 {
+                lines += 2; // package heading + blank
+            }
+            if !target.loaded {
+                lines += 2;
+                continue;
+            }
+            let entries = target.visible_entries(self.modals.changelog_full);
+            if entries.is_empty() {
+                lines += target.raw.len().max(1) + 1;
+            }
+            for entry in entries {
+                lines += 1 + entry.changes.len() + 2; // header + body + trailer + blank
+            }
+        }
+        lines
+    }
+
     pub fn mark_confirm_line_count(&self) -> usize {
         if let Some(ref preview) = self.mark_preview {
             let mut count = 2; // Header lines
@@ -685,6 +1272,11 @@ impl App {
 
     // === Status message ===
 
+    /// Key for a list-view action, for use in status messages
+    fn key_hint(&self, action: Action) -> String {
+        self.keymap.hint(AppState::Listing, action)
+    }
+
     pub fn update_status_message(&mut self) {
         let has_marks = self.core.has_marks();
 
@@ -695,9 +1287,10 @@ impl App {
                 .count();
 
             self.status_message = format!(
-                "{} packages marked | {} upgradable | Press 'u' to review",
+                "{} packages marked | {} upgradable | Press '{}' to review",
                 mark_count,
-                self.core.upgradable_count()
+                self.core.upgradable_count(),
+                self.key_hint(Action::ShowChanges)
             );
         } else {
             self.status_message = format!("{} packages upgradable", self.core.upgradable_count());
@@ -717,7 +1310,7 @@ impl App {
         self.state = AppState::Upgrading;
 
         let progress_state = Rc::new(RefCell::new(
-            ProgressState::new("Applying Changes")?,
+            ProgressState::new("Applying Changes", self.theme.clone())?,
         ));
 
         let acq = TuiAcquireProgress::new(Rc::clone(&progress_state));
@@ -730,7 +1323,9 @@ impl App {
         // Without this, dpkg's conffile prompt would deadlock since we've
         // captured stdout and it can't interact with the user.
         let config = rust_apt::config::Config::new();
-        config.set_vector("Dpkg::Options", &vec!["--force-confdef", "--force-confold"]);
+        let mut options = vec!["--force-confdef".to_string(), "--force-confold".to_string()];
+        options.extend(root::dpkg_options());
+        config.set_vector("Dpkg::Options", &options);
 
         // Suppress debconf prompts (use package defaults).
         // Safety: we're single-threaded, no concurrent env reads.
@@ -746,16 +1341,16 @@ impl App {
         self.output_lines = redirect.output();
         self.output_scroll = 0;
 
-        match result {
-            Ok(()) => {
-                self.state = AppState::Done;
-                self.status_message = "Changes applied successfully. Press 'q' to quit or 'r' to refresh.".to_string();
-            }
-            Err(e) => {
-                self.state = AppState::Done;
-                self.status_message = format!("Error: {e}. Press 'q' to quit or 'r' to refresh.");
-            }
-        }
+        let keys = format!(
+            "Press '{}' to quit or '{}' to refresh.",
+            self.keymap.hint(AppState::Done, Action::Quit),
+            self.keymap.hint(AppState::Done, Action::Refresh),
+        );
+        self.state = AppState::Done;
+        self.status_message = match result {
+            Ok(()) => format!("Changes applied successfully. {keys}"),
+            Err(e) => format!("Error: {e}. {keys}"),
+        };
 
         // redirect drops here, restoring stdout/stderr and cleaning up temp file
         Ok(())
@@ -774,7 +1369,7 @@ impl App {
         }
 
         let progress_state = Rc::new(RefCell::new(
-            ProgressState::new("Updating Package Lists")?,
+            ProgressState::new("Updating Package Lists", self.theme.clone())?,
         ));
 
         let acq = TuiAcquireProgress::new(Rc::clone(&progress_state));
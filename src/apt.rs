@@ -3,17 +3,23 @@
 //! This module provides a thin wrapper around rust-apt with PackageId handles.
 //! User intent tracking is handled by the core module, not here.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 use color_eyre::Result;
 use rust_apt::cache::{Cache, PackageSort};
 use rust_apt::error::AptErrors;
 use rust_apt::progress::{AcquireProgress, InstallProgress};
-use rust_apt::{Package, Version};
+use rust_apt::{Package, PkgCurrentState, PkgSelectedState, Version};
 
+use crate::backend::{Backend, MarkedChange, PackageView};
+use crate::deps::{DepAlternative, DependencySource, OrGroup, Satisfier, VersionOp};
+use crate::filter::{Field, PackageFacts, Value};
+use crate::root;
 use crate::types::*;
 
 /// Manages APT cache interactions with stable PackageId handles.
+/// Marking, resolving and commit go through the `Backend` impl.
 /// Each unique package (including multi-arch variants) gets its own PackageId.
 pub struct AptCache {
     cache: Cache,
@@ -25,20 +31,29 @@ pub struct AptCache {
     native_arch: String,
     /// Cached suffix for display_name stripping (e.g., ":amd64")
     native_arch_suffix: String,
+    /// All configured architectures, native first (APT::Architectures)
+    architectures: Vec<String>,
+    /// Policy candidates replaced by `set_candidate_version`, restored by `restore_candidates`
+    original_candidates: HashMap<PackageId, String>,
 }
 
 impl AptCache {
     /// Create a new AptCache with a fresh APT cache, pre-populating all PackageIds.
-    /// Each multi-arch variant gets its own unique PackageId.
+    /// Each multi-arch variant gets its own unique PackageId. Opens the
+    /// `--root` tree when `root::set` was called first.
     pub fn new() -> Result<Self> {
         let cache = Cache::new::<&str>(&[])?;
 
-        // Get native architecture from dpkg
-        let native_arch = std::process::Command::new("dpkg")
-            .arg("--print-architecture")
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .unwrap_or_else(|_| "amd64".to_string());
+        // Native architecture as APT sees it (the tree's under --root), else dpkg's
+        let native_arch = rust_apt::config::Config::new().get("APT::Architecture")
+            .filter(|arch| !arch.is_empty())
+            .unwrap_or_else(|| {
+                std::process::Command::new("dpkg")
+                    .arg("--print-architecture")
+                    .output()
+                    .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                    .unwrap_or_else(|_| "amd64".to_string())
+            });
         let mut fullname_to_id = HashMap::new();
         let mut id_to_fullname = Vec::new();
 
@@ -53,12 +68,21 @@ impl AptCache {
 
         let native_arch_suffix = format!(":{native_arch}");
 
+        let mut architectures = vec![native_arch.clone()];
+        for arch in rust_apt::config::Config::new().get_architectures() {
+            if !architectures.contains(&arch) {
+                architectures.push(arch);
+            }
+        }
+
         Ok(Self {
             cache,
             fullname_to_id,
             id_to_fullname,
             native_arch,
             native_arch_suffix,
+            architectures,
+            original_candidates: HashMap::new(),
         })
     }
 
@@ -124,77 +148,6 @@ impl AptCache {
         self.cache.get_changes(false)
     }
 
-    // ========================================================================
-    // Marking operations (low-level, crate-only)
-    // These are only accessible from core.rs via SharedState.
-    // External code must use ManagerState methods which enforce the typestate.
-    // ========================================================================
-
-    /// Mark a package for install/upgrade (by name)
-    pub(crate) fn mark_install(&self, name: &str) {
-        if let Some(pkg) = self.cache.get(name) {
-            pkg.mark_install(true, true);
-            // Note: We don't call protect() because we manage state through
-            // user_intent in core.rs, not through APT's protection mechanism.
-            // This allows clear_all_marks() to properly reset the cache.
-        }
-    }
-
-    /// Mark a package for install/upgrade (by id)
-    pub(crate) fn mark_install_id(&self, id: PackageId) {
-        if let Some(name) = self.fullname_of(id) {
-            self.mark_install(name);
-        }
-    }
-
-    /// Mark a package for removal (by name)
-    pub(crate) fn mark_delete(&self, name: &str) {
-        if let Some(pkg) = self.cache.get(name) {
-            pkg.mark_delete(false);
-            // Note: No protect() - we manage state through user_intent
-        }
-    }
-
-    /// Mark a package for removal (by id)
-    pub(crate) fn mark_delete_id(&self, id: PackageId) {
-        if let Some(name) = self.fullname_of(id) {
-            self.mark_delete(name);
-        }
-    }
-
-    /// Mark a package to keep current version (unmark)
-    pub(crate) fn mark_keep(&self, name: &str) {
-        if let Some(pkg) = self.cache.get(name) {
-            pkg.mark_keep();
-        }
-    }
-
-    /// Mark a package to keep (by id)
-    pub(crate) fn mark_keep_id(&self, id: PackageId) {
-        if let Some(name) = self.fullname_of(id) {
-            self.mark_keep(name);
-        }
-    }
-
-    /// Clear all marks on all packages
-    pub(crate) fn clear_all_marks(&self) {
-        // Collect fullnames first to avoid iterator invalidation issues
-        let to_clear: Vec<_> = self.cache.get_changes(false)
-            .map(|pkg| pkg.fullname(false))
-            .collect();
-
-        for fullname in &to_clear {
-            if let Some(pkg) = self.cache.get(fullname) {
-                pkg.mark_keep();
-            }
-        }
-    }
-
-    /// Resolve dependencies
-    pub(crate) fn resolve(&mut self) -> Result<(), AptErrors> {
-        self.cache.resolve(true)
-    }
-
     // ========================================================================
     // Package info extraction (status determined by APT state only)
     // ========================================================================
@@ -220,7 +173,14 @@ impl AptCache {
     /// Returns BASE status (installed/upgradable/not-installed) - ignores APT marks.
     /// The core module will compute final display status based on user_intent.
     pub fn extract_package_info(&self, pkg: &Package) -> Option<PackageInfo> {
-        let candidate = pkg.candidate()?;
+        let residual = is_residual_config(pkg);
+        // Packages left in dpkg's "rc" state often have no candidate any more;
+        // fall back to the version recorded in the dpkg status file
+        let candidate = match pkg.candidate() {
+            Some(c) => c,
+            None if residual => pkg.versions().next()?,
+            None => return None,
+        };
 
         // Return BASE status only - ignore APT marks
         // core.rs will overlay user_intent and dependency info for display
@@ -230,6 +190,8 @@ impl AptCache {
             } else {
                 PackageStatus::Installed
             }
+        } else if residual {
+            PackageStatus::ResidualConfig
         } else {
             PackageStatus::NotInstalled
         };
@@ -257,6 +219,46 @@ impl AptCache {
         })
     }
 
+    /// List every version of a package with its origin and pin priority,
+    /// newest first (the order APT keeps them in)
+    pub fn available_versions(&self, id: PackageId) -> Vec<AvailableVersion> {
+        let Some(pkg) = self.get_by_id(id) else {
+            return Vec::new();
+        };
+
+        let installed = pkg.installed().map(|v| v.version().to_string());
+        let candidate = self.original_candidates.get(&id).cloned()
+            .or_else(|| pkg.candidate().map(|v| v.version().to_string()));
+
+        pkg.versions()
+            .map(|ver| {
+                // Prefer a real archive over the dpkg status file ("now")
+                let files: Vec<_> = ver.package_files().collect();
+                let file = files.iter()
+                    .find(|f| f.archive() != Some("now"))
+                    .or(files.first());
+
+                let version = ver.version().to_string();
+                AvailableVersion {
+                    origin: file.and_then(|f| f.origin()).unwrap_or("local").to_string(),
+                    archive: file.and_then(|f| f.archive()).unwrap_or("now").to_string(),
+                    component: file.and_then(|f| f.component()).unwrap_or_default().to_string(),
+                    priority: ver.priority(),
+                    is_installed: installed.as_deref() == Some(version.as_str()),
+                    is_candidate: candidate.as_deref() == Some(version.as_str()),
+                    download_size: ver.size(),
+                    installed_size: ver.installed_size(),
+                    version,
+                }
+            })
+            .collect()
+    }
+
+    /// Get the record fields of a package's candidate (or installed) version
+    pub fn get_details(&self, name: &str) -> PackageDetails {
+        self.cache.get(name).map(|pkg| package_details(&pkg)).unwrap_or_default()
+    }
+
     // ========================================================================
     // Dependency queries
     // ========================================================================
@@ -333,24 +335,6 @@ impl AptCache {
     // Cache lifecycle
     // ========================================================================
 
-    /// Full refresh - reload cache from disk
-    pub fn refresh(&mut self) -> Result<()> {
-        self.cache = Cache::new::<&str>(&[])?;
-        // Note: We keep the id mappings - they're still valid names
-        Ok(())
-    }
-
-    /// Commit changes using native APT progress (text output)
-    pub(crate) fn commit(&mut self) -> Result<()> {
-        let mut acquire_progress = AcquireProgress::apt();
-        let mut install_progress = InstallProgress::apt();
-
-        let cache = std::mem::replace(&mut self.cache, Cache::new::<&str>(&[])?);
-        cache.commit(&mut acquire_progress, &mut install_progress)?;
-
-        Ok(())
-    }
-
     /// Commit changes using caller-provided progress implementations
     pub(crate) fn commit_with_progress(
         &mut self,
@@ -358,6 +342,7 @@ impl AptCache {
         install_progress: &mut InstallProgress,
     ) -> Result<()> {
         let cache = std::mem::replace(&mut self.cache, Cache::new::<&str>(&[])?);
+        self.original_candidates.clear();
         cache.commit(acquire_progress, install_progress)?;
         Ok(())
     }
@@ -368,6 +353,7 @@ impl AptCache {
         acquire_progress: &mut AcquireProgress,
     ) -> Result<()> {
         let cache = std::mem::replace(&mut self.cache, Cache::new::<&str>(&[])?);
+        self.original_candidates.clear();
         cache.update(acquire_progress)?;
         // Reload cache after update to pick up new package lists
         self.cache = Cache::new::<&str>(&[])?;
@@ -385,6 +371,454 @@ pub struct AptPackageState {
     pub marked_upgrade: bool,
 }
 
+/// Check if a package was removed but left its configuration files behind (dpkg "rc" state)
+pub fn is_residual_config(pkg: &Package) -> bool {
+    pkg.current_state() == PkgCurrentState::ConfigFiles
+}
+
+/// Read the record fields of a package's candidate (or installed) version
+pub fn package_details(pkg: &Package) -> PackageDetails {
+    let Some(version) = pkg.candidate().or_else(|| pkg.installed()) else {
+        return PackageDetails::default();
+    };
+
+    // APT's long description repeats the summary on its first line, and
+    // marks paragraph breaks with " ."
+    let long_description = version.description().unwrap_or_default()
+        .lines()
+        .skip(1)
+        .map(|line| match line.trim() {
+            "." => "",
+            _ => line.strip_prefix(' ').unwrap_or(line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    PackageDetails {
+        long_description,
+        maintainer: version.get_record("Maintainer").unwrap_or_default(),
+        homepage: version.get_record("Homepage").unwrap_or_default(),
+        source: version.source_name().to_string(),
+        provides: version.provides().map(|p| p.name().to_string()).collect(),
+    }
+}
+
+/// Section a package is listed under, without the component prefix
+/// (`universe/libs` and `libs` are both `libs`). Uses the same version
+/// as the package list.
+pub fn package_section(pkg: &Package) -> Option<String> {
+    let version = pkg.candidate().or_else(|| {
+        is_residual_config(pkg).then(|| pkg.versions().next()).flatten()
+    })?;
+    Some(section_name(version.section().unwrap_or("unknown")).to_string())
+}
+
+/// Strip the component from a `component/section` section
+pub fn section_name(section: &str) -> &str {
+    section.rsplit('/').next().unwrap_or(section)
+}
+
+/// Every repository tree node a package belongs to, through the package files
+/// of all its versions. The dpkg status file ("now") is not a repository.
+pub fn package_repos(pkg: &Package) -> Vec<RepoFilter> {
+    let mut repos = Vec::new();
+    for version in pkg.versions() {
+        for file in version.package_files().filter(|f| f.archive() != Some("now")) {
+            let origin = file.origin().or_else(|| file.label()).or_else(|| file.site()).unwrap_or("(unnamed)");
+            let archive = file.archive().unwrap_or("(none)");
+            let nodes = [
+                RepoFilter::Origin { origin: origin.to_string(), archive: None, component: None },
+                RepoFilter::Origin { origin: origin.to_string(), archive: Some(archive.to_string()), component: None },
+                RepoFilter::Origin {
+                    origin: origin.to_string(),
+                    archive: Some(archive.to_string()),
+                    component: Some(file.component().unwrap_or("(none)").to_string()),
+                },
+            ];
+            for node in nodes {
+                if !repos.contains(&node) {
+                    repos.push(node);
+                }
+            }
+        }
+    }
+
+    let installed_from_repo = pkg.installed()
+        .is_some_and(|v| v.package_files().any(|f| f.archive() != Some("now")));
+    if pkg.is_installed() && !installed_from_repo {
+        repos.push(RepoFilter::NotConfigured);
+    }
+    repos
+}
+
+/// What custom filters see of a package, read from the APT cache on demand
+pub struct AptPackageFacts<'a> {
+    pub cache: &'a AptCache,
+    pub pkg: &'a Package<'a>,
+}
+
+impl PackageFacts for AptPackageFacts<'_> {
+    fn value(&self, field: Field) -> Value {
+        let pkg = self.pkg;
+        let version = || pkg.candidate().or_else(|| pkg.installed());
+        let text = |value: Option<String>| Value::Text(value.into_iter().collect());
+        // Values from the package files of every version, without duplicates
+        let from_files = |get: &dyn Fn(&rust_apt::PackageFile) -> Option<String>| {
+            let mut values: Vec<String> = Vec::new();
+            for v in pkg.versions() {
+                for file in v.package_files().filter(|f| f.archive() != Some("now")) {
+                    if let Some(value) = get(&file)
+                        && !values.contains(&value) {
+                            values.push(value);
+                    }
+                }
+            }
+            Value::Text(values)
+        };
+
+        match field {
+            Field::Name => Value::Text(vec![pkg.name().to_string()]),
+            Field::Section => {
+                let section = version().and_then(|v| v.section().ok().map(str::to_string)).unwrap_or_default();
+                Value::Text(vec![section_name(&section).to_string(), section])
+            }
+            Field::Arch => Value::Text(vec![pkg.arch().to_string()]),
+            Field::Origin => from_files(&|f| f.origin().map(str::to_string)),
+            Field::Archive => from_files(&|f| f.archive().map(str::to_string)),
+            Field::Priority => text(version().and_then(|v| v.priority_str().ok().map(str::to_string))),
+            Field::Maintainer => text(version().and_then(|v| v.get_record("Maintainer"))),
+            Field::Source => text(version().map(|v| v.source_name().to_string())),
+            Field::InstalledVersion => text(pkg.installed().map(|v| v.version().to_string())),
+            Field::CandidateVersion => text(pkg.candidate().map(|v| v.version().to_string())),
+            Field::InstalledSize => Value::Number(version().map_or(0, |v| v.installed_size())),
+            Field::DownloadSize => Value::Number(version().map_or(0, |v| v.size())),
+            Field::Rdeps => Value::Number(self.installed_rdeps() as u64),
+            Field::Installed => Value::Flag(pkg.is_installed()),
+            Field::Upgradable => Value::Flag(pkg.is_upgradable()),
+            Field::Auto => Value::Flag(pkg.is_installed() && pkg.is_auto_installed()),
+            Field::Held => Value::Flag(is_held(pkg)),
+            Field::Residual => Value::Flag(is_residual_config(pkg)),
+            Field::Essential => Value::Flag(pkg.is_essential()),
+            Field::Broken => Value::Flag(pkg.is_now_broken()),
+            Field::HasRdeps => Value::Flag(self.installed_rdeps() > 0),
+        }
+    }
+
+    fn compare_versions(&self, a: &str, b: &str) -> Ordering {
+        self.cache.compare_versions(a, b)
+    }
+}
+
+impl AptPackageFacts<'_> {
+    /// Installed packages that depend on, pre-depend on or recommend this one
+    fn installed_rdeps(&self) -> usize {
+        let mut names: Vec<&str> = Vec::new();
+        for (dep_type, deps) in self.pkg.rdepends() {
+            if !matches!(dep_type, rust_apt::DepType::Depends | rust_apt::DepType::PreDepends | rust_apt::DepType::Recommends) {
+                continue;
+            }
+            for base_dep in deps.iter().flat_map(|dep| dep.iter()) {
+                let name = base_dep.name();
+                if !names.contains(&name) && self.cache.cache.get(name).is_some_and(|p| p.is_installed()) {
+                    names.push(name);
+                }
+            }
+        }
+        names.len()
+    }
+}
+
+impl PackageView for AptPackageFacts<'_> {
+    fn fullname(&self) -> String {
+        self.pkg.fullname(false)
+    }
+
+    fn name(&self) -> &str {
+        self.pkg.name()
+    }
+
+    fn is_installed(&self) -> bool {
+        self.pkg.is_installed()
+    }
+
+    fn is_upgradable(&self) -> bool {
+        self.pkg.is_upgradable()
+    }
+
+    fn is_marked(&self) -> bool {
+        self.pkg.marked_install() || self.pkg.marked_upgrade() || self.pkg.marked_delete()
+    }
+
+    fn is_residual_config(&self) -> bool {
+        is_residual_config(self.pkg)
+    }
+
+    fn is_held(&self) -> bool {
+        is_held(self.pkg)
+    }
+
+    fn repos(&self) -> Vec<RepoFilter> {
+        package_repos(self.pkg)
+    }
+
+    fn section(&self) -> Option<String> {
+        package_section(self.pkg)
+    }
+}
+
+/// Check if a package is held in the dpkg selections
+pub fn is_held(pkg: &Package) -> bool {
+    pkg.selected_state() == PkgSelectedState::Hold
+}
+
+impl DependencySource for AptCache {
+    fn native_arch(&self) -> &str {
+        &self.native_arch
+    }
+
+    fn depends(&self, fullname: &str) -> Vec<OrGroup> {
+        let Some(pkg) = self.cache.get(fullname) else {
+            return Vec::new();
+        };
+        let Some(version) = pkg.candidate().or_else(|| pkg.installed()) else {
+            return Vec::new();
+        };
+        let Some(dependencies) = version.dependencies() else {
+            return Vec::new();
+        };
+
+        dependencies.iter()
+            .filter(|dep| matches!(dep.dep_type(), rust_apt::DepType::Depends | rust_apt::DepType::PreDepends))
+            .map(|dep| {
+                dep.iter()
+                    .map(|base_dep| DepAlternative {
+                        name: base_dep.name().to_string(),
+                        // APT already resolved the qualifier: the target lives in
+                        // the depender's arch, the named arch, or the "any" pseudo-arch
+                        arch: Some(base_dep.target_package().arch().to_string()),
+                        constraint: base_dep.comp_type()
+                            .and_then(VersionOp::parse)
+                            .zip(base_dep.version().map(str::to_string)),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn satisfiers(&self, name: &str) -> Vec<Satisfier> {
+        let mut result = Vec::new();
+
+        for arch in &self.architectures {
+            let Some(pkg) = self.cache.get(&format!("{name}:{arch}")) else {
+                continue;
+            };
+
+            // The real package, if it has a version to install
+            if let Some(version) = pkg.candidate().or_else(|| pkg.installed()) {
+                result.push(Satisfier {
+                    fullname: pkg.fullname(false),
+                    arch: version.arch().to_string(),
+                    version: Some(version.version().to_string()),
+                });
+            }
+
+            // Packages whose candidate provides this name
+            for provider in pkg.provides() {
+                let version = provider.version();
+                if !version.is_candidate() {
+                    continue;
+                }
+                let parent = version.parent();
+                result.push(Satisfier {
+                    fullname: parent.fullname(false),
+                    arch: version.arch().to_string(),
+                    version: provider.version_str().ok().map(str::to_string),
+                });
+            }
+        }
+
+        result
+    }
+
+    fn compare_versions(&self, a: &str, b: &str) -> Ordering {
+        rust_apt::util::cmp_versions(a, b)
+    }
+}
+
+impl Backend for AptCache {
+    fn get_id(&self, fullname: &str) -> Option<PackageId> {
+        AptCache::get_id(self, fullname)
+    }
+
+    fn id_for(&mut self, fullname: &str) -> PackageId {
+        AptCache::id_for(self, fullname)
+    }
+
+    fn fullname_of(&self, id: PackageId) -> Option<&str> {
+        AptCache::fullname_of(self, id)
+    }
+
+    fn display_name<'a>(&self, fullname: &'a str) -> &'a str {
+        AptCache::display_name(self, fullname)
+    }
+
+    fn for_each_package(&self, upgradable_only: bool, visit: &mut dyn FnMut(&dyn PackageView)) {
+        let sort = if upgradable_only {
+            PackageSort::default().upgradable()
+        } else {
+            PackageSort::default()
+        };
+        for pkg in self.cache.packages(&sort) {
+            visit(&AptPackageFacts { cache: self, pkg: &pkg });
+        }
+    }
+
+    fn package_info(&self, fullname: &str) -> Option<PackageInfo> {
+        self.extract_package_info_by_name(fullname)
+    }
+
+    fn installed_version(&self, id: PackageId) -> Option<String> {
+        self.get_by_id(id)?.installed().map(|v| v.version().to_string())
+    }
+
+    fn candidate_version(&self, id: PackageId) -> Option<String> {
+        self.get_by_id(id)?.candidate().map(|v| v.version().to_string())
+    }
+
+    fn available_versions(&self, id: PackageId) -> Vec<AvailableVersion> {
+        AptCache::available_versions(self, id)
+    }
+
+    fn clear_all_marks(&mut self) {
+        // Collect fullnames first to avoid iterator invalidation issues
+        let to_clear: Vec<_> = self.cache.get_changes(false)
+            .map(|pkg| pkg.fullname(false))
+            .collect();
+
+        for fullname in &to_clear {
+            if let Some(pkg) = self.cache.get(fullname) {
+                pkg.mark_keep();
+            }
+        }
+    }
+
+    fn restore_candidates(&mut self) {
+        for (id, version) in self.original_candidates.drain() {
+            if let Some(name) = self.id_to_fullname.get(id.index())
+                && let Some(pkg) = self.cache.get(name)
+                && let Some(ver) = pkg.get_version(&version) {
+                    ver.set_candidate();
+            }
+        }
+    }
+
+    /// The policy candidate is remembered so `restore_candidates` can undo this.
+    fn set_candidate_version(&mut self, id: PackageId, version: &str) -> bool {
+        let Some(name) = self.id_to_fullname.get(id.index()) else {
+            return false;
+        };
+        let Some(pkg) = self.cache.get(name) else {
+            return false;
+        };
+        let Some(ver) = pkg.get_version(version) else {
+            return false;
+        };
+
+        if !self.original_candidates.contains_key(&id)
+            && let Some(candidate) = pkg.candidate() {
+                self.original_candidates.insert(id, candidate.version().to_string());
+        }
+        ver.set_candidate();
+        true
+    }
+
+    fn mark_install(&mut self, id: PackageId) {
+        if let Some(pkg) = self.get_by_id(id) {
+            pkg.mark_install(true, true);
+            // Note: We don't call protect() because we manage state through
+            // user_intent in core.rs, not through APT's protection mechanism.
+            // This allows clear_all_marks() to properly reset the cache.
+        }
+    }
+
+    fn mark_delete(&mut self, id: PackageId, purge: bool) {
+        if let Some(pkg) = self.get_by_id(id) {
+            pkg.mark_delete(purge);
+            // Note: No protect() - we manage state through user_intent
+        }
+    }
+
+    fn mark_keep(&mut self, id: PackageId) {
+        if let Some(pkg) = self.get_by_id(id) {
+            pkg.mark_keep();
+        }
+    }
+
+    /// The same thing `apt-mark hold/unhold` does: holds are written to the
+    /// dpkg selections immediately, not at commit time.
+    fn set_hold(&mut self, id: PackageId, hold: bool) -> Result<(), String> {
+        let name = AptCache::fullname_of(self, id).ok_or_else(|| "Unknown package".to_string())?;
+        let action = if hold { "hold" } else { "unhold" };
+
+        match std::process::Command::new("apt-mark").args(root::apt_options()).args([action, name]).output() {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => {
+                let err = String::from_utf8_lossy(&output.stderr);
+                Err(format!("apt-mark {action} failed: {}", err.trim()))
+            }
+            Err(e) => Err(format!("Failed to run apt-mark: {e}")),
+        }
+    }
+
+    fn resolve(&mut self) -> Result<(), String> {
+        self.cache.resolve(true).map_err(|e| format_apt_errors(&e))
+    }
+
+    fn changes(&self) -> Vec<MarkedChange> {
+        self.cache.get_changes(false)
+            .map(|pkg| MarkedChange {
+                fullname: pkg.fullname(false),
+                is_installed: pkg.is_installed(),
+                is_auto: pkg.is_auto_installed(),
+                marked_install: pkg.marked_install(),
+                marked_upgrade: pkg.marked_upgrade(),
+                marked_downgrade: pkg.marked_downgrade(),
+                marked_delete: pkg.marked_delete(),
+                marked_purge: pkg.marked_purge(),
+                candidate_sizes: pkg.candidate().map(|c| (c.size(), c.installed_size())),
+                installed_size: pkg.installed().map(|v| v.installed_size()),
+            })
+            .collect()
+    }
+
+    fn get_dependencies(&self, name: &str) -> Vec<(String, String)> {
+        AptCache::get_dependencies(self, name)
+    }
+
+    fn get_reverse_dependencies(&self, name: &str) -> Vec<(String, String)> {
+        AptCache::get_reverse_dependencies(self, name)
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.cache = Cache::new::<&str>(&[])?;
+        self.original_candidates.clear();
+        // Note: We keep the id mappings - they're still valid names
+        Ok(())
+    }
+
+    /// Commit changes using native APT progress (text output)
+    fn commit(&mut self) -> Result<()> {
+        let mut acquire_progress = AcquireProgress::apt();
+        let mut install_progress = InstallProgress::apt();
+
+        let cache = std::mem::replace(&mut self.cache, Cache::new::<&str>(&[])?);
+        self.original_candidates.clear();
+        cache.commit(&mut acquire_progress, &mut install_progress)?;
+
+        Ok(())
+    }
+}
+
 /// Helper function to order dependency types by priority
 fn dep_type_order(t: &str) -> u8 {
     match t {
@@ -0,0 +1,136 @@
+//! Package backend abstraction
+//!
+//! Everything the typestate manager in core.rs needs from the package system:
+//! stable package ids, the package list, marking and resolving, the changes
+//! the resolver settled on, dependency queries and commit. `AptCache`
+//! implements it over libapt; `fake::FakeBackend` keeps a fixture in memory so
+//! the manager can be exercised without a live system.
+
+use color_eyre::Result;
+
+use crate::deps::DependencySource;
+use crate::filter::PackageFacts;
+use crate::types::*;
+
+/// One package as the list filters and package counts see it
+pub trait PackageView: PackageFacts {
+    /// Full name including arch (e.g., "libfoo:amd64")
+    fn fullname(&self) -> String;
+    /// Base name without arch
+    fn name(&self) -> &str;
+    fn is_installed(&self) -> bool;
+    fn is_upgradable(&self) -> bool;
+    /// Marked for install, upgrade or removal in the backend
+    fn is_marked(&self) -> bool;
+    /// Removed with its configuration files left behind (dpkg "rc" state)
+    fn is_residual_config(&self) -> bool;
+    /// Held in the dpkg selections
+    fn is_held(&self) -> bool;
+    /// Every repository tree node the package belongs to
+    fn repos(&self) -> Vec<RepoFilter>;
+    /// Section without the component prefix
+    fn section(&self) -> Option<String>;
+}
+
+/// A package the resolver marked for a change, as `plan()` reads it back
+#[derive(Debug, Clone, Default)]
+pub struct MarkedChange {
+    pub fullname: String,
+    pub is_installed: bool,
+    pub is_auto: bool,
+    pub marked_install: bool,
+    pub marked_upgrade: bool,
+    pub marked_downgrade: bool,
+    pub marked_delete: bool,
+    pub marked_purge: bool,
+    /// Download and installed size of the candidate
+    pub candidate_sizes: Option<(u64, u64)>,
+    /// Installed size of the installed version
+    pub installed_size: Option<u64>,
+}
+
+/// The package system the manager drives
+pub trait Backend: DependencySource {
+    // === PackageId management ===
+
+    /// Get the PackageId for a full name (returns None if not known)
+    fn get_id(&self, fullname: &str) -> Option<PackageId>;
+
+    /// Get or create a stable PackageId for a full name
+    fn id_for(&mut self, fullname: &str) -> PackageId;
+
+    /// Get the full name for a PackageId
+    fn fullname_of(&self, id: PackageId) -> Option<&str>;
+
+    /// Display name for a package (strips the native arch suffix)
+    fn display_name<'a>(&self, fullname: &'a str) -> &'a str {
+        fullname.strip_suffix(self.native_arch())
+            .and_then(|name| name.strip_suffix(':'))
+            .unwrap_or(fullname)
+    }
+
+    // === Package list ===
+
+    /// Visit every package, or only the upgradable ones
+    fn for_each_package(&self, upgradable_only: bool, visit: &mut dyn FnMut(&dyn PackageView));
+
+    /// Package info with its BASE status (installed/upgradable/not-installed),
+    /// ignoring marks
+    fn package_info(&self, fullname: &str) -> Option<PackageInfo>;
+
+    /// Version currently installed, if any
+    fn installed_version(&self, id: PackageId) -> Option<String>;
+
+    /// Version that would be installed, if any
+    fn candidate_version(&self, id: PackageId) -> Option<String>;
+
+    /// Every available version of a package, newest first
+    fn available_versions(&self, id: PackageId) -> Vec<AvailableVersion>;
+
+    // === Marking ===
+    // Meant for core.rs only: everything else goes through `ManagerState`,
+    // which derives the marks from user intent.
+
+    /// Clear all marks on all packages
+    fn clear_all_marks(&mut self);
+
+    /// Restore the policy candidate of every package changed by `set_candidate_version`
+    fn restore_candidates(&mut self);
+
+    /// Make a specific version the install candidate for a package
+    fn set_candidate_version(&mut self, id: PackageId, version: &str) -> bool;
+
+    /// Mark a package for install/upgrade, pulling in its dependencies
+    fn mark_install(&mut self, id: PackageId);
+
+    /// Mark a package for removal. `purge` also removes its configuration files.
+    fn mark_delete(&mut self, id: PackageId, purge: bool);
+
+    /// Mark a package to keep its current version
+    fn mark_keep(&mut self, id: PackageId);
+
+    /// Set or release a dpkg hold, persisted immediately
+    fn set_hold(&mut self, id: PackageId, hold: bool) -> Result<(), String>;
+
+    /// Resolve dependencies of the current marks (errors are user-facing)
+    fn resolve(&mut self) -> Result<(), String>;
+
+    /// Packages with pending changes after `resolve`
+    fn changes(&self) -> Vec<MarkedChange>;
+
+    // === Dependency queries ===
+
+    /// Forward dependencies as (type, name) pairs
+    fn get_dependencies(&self, name: &str) -> Vec<(String, String)>;
+
+    /// Reverse dependencies as (type, name) pairs
+    fn get_reverse_dependencies(&self, name: &str) -> Vec<(String, String)>;
+
+    // === Lifecycle ===
+
+    /// Reload the package state from disk
+    fn refresh(&mut self) -> Result<()>;
+
+    /// Apply the marked changes to the system
+    fn commit(&mut self) -> Result<()>;
+}
@@ -10,8 +10,12 @@
 //!   info <name>         Show package state
 //!   toggle <name>       Toggle package mark (simulates Space key)
 //!   reset               Clear all marks
-//!   list [filter]       List packages (upgradable, installed, all, marked)
+//!   list [filter]       List packages (upgradable, installed, all, marked, residual)
+//!   deps <name>         Show a package's dependencies
+//!
+//! `--json` anywhere prints the versioned documents of `synh8::json` instead.
 
+use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::path::Path;
@@ -19,6 +23,7 @@ use std::path::Path;
 use color_eyre::Result;
 
 use synh8::core::ManagerState;
+use synh8::json;
 use synh8::types::*;
 
 const STATE_FILE: &str = "debug_state.json";
@@ -26,24 +31,25 @@ const STATE_FILE: &str = "debug_state.json";
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let args: Vec<String> = env::args().collect();
+    let json = env::args().any(|a| a == "--json");
+    let args: Vec<String> = env::args().filter(|a| a != "--json").collect();
     let cmd = args.get(1).map(String::as_str).unwrap_or("help");
 
     match cmd {
-        "status" => cmd_status()?,
+        "status" => cmd_status(json)?,
         "info" => {
             let name = args.get(2).ok_or_else(|| color_eyre::eyre::eyre!("Usage: info <package_name>"))?;
-            cmd_info(name)?;
+            cmd_info(name, json)?;
         }
         "toggle" => {
             let name = args.get(2).ok_or_else(|| color_eyre::eyre::eyre!("Usage: toggle <package_name>"))?;
-            cmd_toggle(name)?;
+            cmd_toggle(name, json)?;
         }
         "reset" => cmd_reset()?,
-        "list" => cmd_list(args.get(2).map(String::as_str))?,
+        "list" => cmd_list(args.get(2).map(String::as_str), json)?,
         "deps" => {
             let name = args.get(2).ok_or_else(|| color_eyre::eyre::eyre!("Usage: deps <package_name>"))?;
-            cmd_deps(name)?;
+            cmd_deps(name, json)?;
         }
         "help" | _ => {
             println!("Debug CLI for synh8 package manager");
@@ -53,7 +59,10 @@ fn main() -> Result<()> {
             println!("  info <name>         Show package state");
             println!("  toggle <name>       Toggle package mark (simulates Space key)");
             println!("  reset               Clear all marks");
-            println!("  list [filter]       List packages (upgradable, installed, all, marked)");
+            println!("  list [filter]       List packages (upgradable, installed, all, marked, residual)");
+            println!("  deps <name>         Show a package's dependencies");
+            println!();
+            println!("Add --json for machine-readable output.");
             println!();
             println!("Example flow (from docs/user-flow.md):");
             println!("  cli reset");
@@ -67,9 +76,23 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn cmd_status() -> Result<()> {
+fn cmd_status(json: bool) -> Result<()> {
     let state = load_state()?;
 
+    if json {
+        // Marked packages with the plan that resolves them
+        let marked: Vec<PackageInfo> = state.list().iter()
+            .filter(|p| p.status.is_marked())
+            .cloned()
+            .collect();
+        println!("{}", json::to_string(&json::Status {
+            marked: &marked,
+            upgradable_count: state.upgradable_count(),
+            plan: json::Plan::new(&state),
+        })?);
+        return Ok(());
+    }
+
     println!("=== Package Manager Status ===");
     println!();
 
@@ -93,13 +116,19 @@ fn cmd_status() -> Result<()> {
     Ok(())
 }
 
-fn cmd_info(name: &str) -> Result<()> {
+fn cmd_info(name: &str, json: bool) -> Result<()> {
     let state = load_state()?;
 
     let pkg = state.list().iter()
         .find(|p| p.name == name)
         .cloned();
 
+    if json {
+        let packages: Vec<PackageInfo> = pkg.into_iter().collect();
+        println!("{}", json::to_string(&json::PackageList { packages: &packages })?);
+        return Ok(());
+    }
+
     match pkg {
         Some(p) => {
             println!("{}: {}", p.name, status_display(p.status));
@@ -112,7 +141,7 @@ fn cmd_info(name: &str) -> Result<()> {
     Ok(())
 }
 
-fn cmd_toggle(name: &str) -> Result<()> {
+fn cmd_toggle(name: &str, json: bool) -> Result<()> {
     let mut state = load_state()?;
 
     // Find the package
@@ -128,12 +157,37 @@ fn cmd_toggle(name: &str) -> Result<()> {
         }
     };
 
+    // Packages already in the plan don't count as pulled in by this toggle
+    state.compute_plan();
+    let previously_planned: HashSet<PackageId> = state.planned_changes()
+        .unwrap_or_default()
+        .iter()
+        .map(|c| c.package)
+        .collect();
+
     // Use the library's toggle function
     let result = state.toggle(pkg.id);
 
     // Get names for display
     let cache = state.cache();
 
+    if json {
+        let name_of = |id: &PackageId| cache.fullname_of(*id).unwrap_or("(unknown)").to_string();
+        let toggle = match &result {
+            ToggleResult::Marked { package, .. } => json::Toggle::Marked {
+                preview: json::Preview::new(&state, *package, &previously_planned),
+            },
+            ToggleResult::Unmarked { package, also_unmarked } => json::Toggle::Unmarked {
+                package: name_of(package),
+                also_unmarked: also_unmarked.iter().map(name_of).collect(),
+            },
+            ToggleResult::NoChange { package } => json::Toggle::NoChange { package: name_of(package) },
+        };
+        println!("{}", json::to_string(&toggle)?);
+        save_state(&state)?;
+        return Ok(());
+    }
+
     match &result {
         ToggleResult::Marked { package, additional } => {
             let pkg_name = cache.fullname_of(*package).unwrap_or("(unknown)");
@@ -183,13 +237,14 @@ fn cmd_reset() -> Result<()> {
     Ok(())
 }
 
-fn cmd_list(filter: Option<&str>) -> Result<()> {
+fn cmd_list(filter: Option<&str>, json: bool) -> Result<()> {
     let mut state = load_state()?;
 
     let filter_cat = match filter {
         Some("upgradable") | None => FilterCategory::Upgradable,
         Some("installed") => FilterCategory::Installed,
         Some("marked") => FilterCategory::MarkedChanges,
+        Some("residual") => FilterCategory::ResidualConfig,
         Some("all") => FilterCategory::All,
         Some(f) => {
             println!("Unknown filter: {}. Using 'upgradable'", f);
@@ -201,6 +256,10 @@ fn cmd_list(filter: Option<&str>) -> Result<()> {
     state.rebuild_list();
 
     let list = state.list();
+    if json {
+        println!("{}", json::to_string(&json::PackageList { packages: list })?);
+        return Ok(());
+    }
     println!("Packages ({}) - filter: {:?}:", list.len(), filter_cat);
     println!();
 
@@ -215,10 +274,18 @@ fn cmd_list(filter: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-fn cmd_deps(name: &str) -> Result<()> {
+fn cmd_deps(name: &str, json: bool) -> Result<()> {
     let state = load_state()?;
     let deps = state.get_dependencies(name);
 
+    if json {
+        println!("{}", json::to_string(&json::Dependencies {
+            package: name,
+            dependencies: json::Dependency::from_pairs(deps),
+        })?);
+        return Ok(());
+    }
+
     println!("Dependencies for {}:", name);
     for (dep_type, dep_name) in deps {
         println!("  {} {}", dep_type, dep_name);
@@ -233,9 +300,12 @@ fn status_display(status: PackageStatus) -> &'static str {
         PackageStatus::Installed => "Installed",
         PackageStatus::NotInstalled => "NotInstalled",
         PackageStatus::Upgradable => "Upgradable",
+        PackageStatus::ResidualConfig => "ResidualConfig",
         PackageStatus::MarkedForInstall => "MarkedForInstall",
         PackageStatus::MarkedForUpgrade => "MarkedForUpgrade",
+        PackageStatus::MarkedForDowngrade => "MarkedForDowngrade",
         PackageStatus::MarkedForRemove => "MarkedForRemove",
+        PackageStatus::MarkedForPurge => "MarkedForPurge",
         PackageStatus::Keep => "Keep",
         PackageStatus::Broken => "Broken",
     }
@@ -0,0 +1,443 @@
+//! Changelog loading
+//!
+//! Changelogs are fetched on a worker thread so the UI keeps drawing while
+//! `apt-get changelog` downloads. Results are cached per package/version, and
+//! the installed version's changelog is read from /usr/share/doc first so it
+//! works offline.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use flate2::read::GzDecoder;
+
+use crate::deps::compare_versions;
+use crate::root;
+
+/// Spinner frames shown while a changelog is loading
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Package name and version identifying one changelog
+pub type ChangelogKey = (String, String);
+
+/// One changelog to load
+#[derive(Debug, Clone)]
+pub struct ChangelogRequest {
+    pub name: String,
+    pub version: String,
+    /// Enables reading the local copy from /usr/share/doc
+    pub installed_version: Option<String>,
+}
+
+/// A loaded changelog
+#[derive(Debug, Clone)]
+pub struct Changelog {
+    pub lines: Vec<String>,
+    /// Why the requested version couldn't be fetched, when `lines` are the
+    /// installed version's changelog instead. Such results aren't cached.
+    pub fallback: Option<String>,
+}
+
+/// A changelog fetch running on the worker thread
+struct PendingFetch {
+    key: ChangelogKey,
+    rx: Receiver<Result<Changelog, String>>,
+    /// The running `apt-get changelog` process, so cancel can kill it
+    child: Arc<Mutex<ChildSlot>>,
+}
+
+/// Shared between a fetch and `cancel`. Both sides update it under the lock,
+/// so a cancel can't slip in between spawning the process and storing it.
+#[derive(Default)]
+struct ChildSlot {
+    child: Option<Child>,
+    cancelled: bool,
+}
+
+/// Loads changelogs in the background, one at a time, and caches the results
+#[derive(Default)]
+pub struct ChangelogLoader {
+    cache: HashMap<ChangelogKey, Vec<String>>,
+    queue: VecDeque<ChangelogRequest>,
+    pending: Option<PendingFetch>,
+    started: Option<Instant>,
+    total: usize,
+}
+
+impl ChangelogLoader {
+    /// Get a cached changelog
+    pub fn cached(&self, name: &str, version: &str) -> Option<&[String]> {
+        self.cache.get(&(name.to_string(), version.to_string())).map(Vec::as_slice)
+    }
+
+    /// Queue changelogs for loading, skipping cached ones.
+    /// Any fetches still queued or in flight are cancelled.
+    pub fn request(&mut self, requests: Vec<ChangelogRequest>) {
+        self.cancel();
+        self.queue = requests.into_iter()
+            .filter(|r| self.cached(&r.name, &r.version).is_none())
+            .collect();
+        self.total = self.queue.len();
+        self.started = Some(Instant::now());
+        self.start_next();
+    }
+
+    fn start_next(&mut self) {
+        let Some(request) = self.queue.pop_front() else {
+            return;
+        };
+
+        let key = (request.name.clone(), request.version.clone());
+        let (tx, rx) = mpsc::channel();
+        let child = Arc::new(Mutex::new(ChildSlot::default()));
+
+        let worker_child = Arc::clone(&child);
+        std::thread::spawn(move || {
+            let result = load_changelog(
+                &request.name,
+                &request.version,
+                request.installed_version.as_deref(),
+                &worker_child,
+            );
+            // The receiver is gone if the fetch was cancelled
+            tx.send(result).ok();
+        });
+
+        self.pending = Some(PendingFetch { key, rx, child });
+    }
+
+    /// Check whether the pending fetch finished, starting the next queued one.
+    /// Returns each finished key and result once.
+    pub fn poll(&mut self) -> Option<(ChangelogKey, Result<Changelog, String>)> {
+        let pending = self.pending.as_ref()?;
+        let result = match pending.rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err("Changelog worker stopped unexpectedly".to_string()),
+        };
+
+        let key = self.pending.take()?.key;
+        // A fallback stands in for a fetch that failed; retry it next time
+        if let Ok(changelog) = &result
+            && changelog.fallback.is_none() {
+                self.cache.insert(key.clone(), changelog.lines.clone());
+        }
+        self.start_next();
+        Some((key, result))
+    }
+
+    /// Abandon all queued fetches, killing `apt-get changelog` if it's still running
+    pub fn cancel(&mut self) {
+        self.queue.clear();
+        if let Some(pending) = self.pending.take()
+            && let Ok(mut slot) = pending.child.lock() {
+                slot.cancelled = true;
+                if let Some(child) = slot.child.as_mut() {
+                    child.kill().ok();
+                }
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Number of changelogs finished and requested in the current batch
+    pub fn progress(&self) -> (usize, usize) {
+        let remaining = self.queue.len() + usize::from(self.pending.is_some());
+        (self.total - remaining, self.total)
+    }
+
+    /// Current spinner frame for the pending fetch
+    pub fn spinner(&self) -> char {
+        let elapsed = self.started.map_or(0, |t| t.elapsed().as_millis());
+        SPINNER[(elapsed / 100) as usize % SPINNER.len()]
+    }
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// One entry of a Debian changelog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogEntry {
+    pub package: String,
+    pub version: String,
+    pub distribution: String,
+    pub urgency: String,
+    pub author: String,
+    pub date: String,
+    pub changes: Vec<String>, // Body lines ("* ..." bullets, continuations, "[ Name ]" groups)
+}
+
+/// Parse Debian changelog text into entries, newest first.
+/// Lines outside of entries (e.g. notes added by apt) are skipped.
+pub fn parse_changelog(lines: &[String]) -> Vec<ChangelogEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<ChangelogEntry> = None;
+
+    for line in lines {
+        if let Some(entry) = parse_header(line) {
+            entries.extend(current.replace(entry));
+        } else if let Some(trailer) = line.strip_prefix(" -- ") {
+            if let Some(mut entry) = current.take() {
+                let (author, date) = trailer.split_once("  ").unwrap_or((trailer, ""));
+                entry.author = author.trim().to_string();
+                entry.date = date.trim().to_string();
+                while entry.changes.last().is_some_and(String::is_empty) {
+                    entry.changes.pop();
+                }
+                entries.push(entry);
+            }
+        } else if let Some(entry) = current.as_mut() {
+            let text = line.strip_prefix("  ").unwrap_or(line).trim_end();
+            // Skip the blank line between header and body
+            if !(text.is_empty() && entry.changes.is_empty()) {
+                entry.changes.push(text.to_string());
+            }
+        }
+    }
+
+    entries.extend(current);
+    entries
+}
+
+/// Parse "package (version) distribution; urgency=level"
+fn parse_header(line: &str) -> Option<ChangelogEntry> {
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (package, rest) = line.split_once(" (")?;
+    let (version, rest) = rest.split_once(')')?;
+    let (distribution, metadata) = rest.split_once(';').unwrap_or((rest, ""));
+    let urgency = metadata.split(',')
+        .find_map(|kv| kv.trim().strip_prefix("urgency="))
+        .unwrap_or_default();
+
+    Some(ChangelogEntry {
+        package: package.trim().to_string(),
+        version: version.trim().to_string(),
+        distribution: distribution.trim().to_string(),
+        urgency: urgency.to_string(),
+        author: String::new(),
+        date: String::new(),
+        changes: Vec::new(),
+    })
+}
+
+/// Entries newer than `installed` up to and including `candidate`.
+/// Without an installed version every entry up to the candidate is included.
+pub fn entries_between<'a>(
+    entries: &'a [ChangelogEntry],
+    installed: Option<&str>,
+    candidate: &str,
+) -> impl Iterator<Item = &'a ChangelogEntry> {
+    entries.iter().filter(move |e| {
+        compare_versions(&e.version, candidate) != Ordering::Greater
+            && installed.is_none_or(|inst| compare_versions(&e.version, inst) == Ordering::Greater)
+    })
+}
+
+/// Load a changelog: the local copy for the installed version, otherwise
+/// `apt-get changelog`, falling back to the local copy when offline
+fn load_changelog(
+    name: &str,
+    version: &str,
+    installed_version: Option<&str>,
+    child: &Mutex<ChildSlot>,
+) -> Result<Changelog, String> {
+    let local = installed_version.and_then(|_| read_local_changelog(name));
+
+    if installed_version == Some(version)
+        && let Some(lines) = local {
+            return Ok(Changelog { lines, fallback: None });
+    }
+
+    match fetch_changelog(name, version, child) {
+        Ok(lines) => Ok(Changelog { lines, fallback: None }),
+        Err(e) => match (local, installed_version) {
+            (Some(lines), Some(installed)) => Ok(Changelog {
+                lines,
+                fallback: Some(format!("{e} - showing the changelog of the installed version {installed}")),
+            }),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Read /usr/share/doc/<pkg>/changelog.Debian.gz, or changelog.gz for
+/// native packages
+fn read_local_changelog(name: &str) -> Option<Vec<String>> {
+    let name = name.split(':').next().unwrap_or(name);
+    let file = ["changelog.Debian.gz", "changelog.gz"].iter()
+        .find_map(|file| std::fs::File::open(root::path(&format!("/usr/share/doc/{name}/{file}"))).ok())?;
+
+    let mut content = String::new();
+    GzDecoder::new(file).read_to_string(&mut content).ok()?;
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    (!lines.is_empty()).then_some(lines)
+}
+
+/// Run `apt-get changelog <pkg>=<version>`, publishing the child so it can be killed
+fn fetch_changelog(name: &str, version: &str, child: &Mutex<ChildSlot>) -> Result<Vec<String>, String> {
+    let mut process = Command::new("apt-get")
+        .args(root::apt_options())
+        .args(["changelog", &format!("{name}={version}")])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run apt-get changelog: {e}"))?;
+
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    {
+        let mut slot = child.lock().map_err(|_| "Changelog fetch cancelled")?;
+        if slot.cancelled {
+            process.kill().ok();
+            process.wait().ok();
+            return Err("Changelog fetch cancelled".to_string());
+        }
+        slot.child = Some(process);
+    }
+
+    // Drain stderr alongside stdout so a chatty apt-get can't fill its pipe and stall
+    let stderr_reader = std::thread::spawn(move || {
+        let mut err = String::new();
+        if let Some(mut e) = stderr {
+            e.read_to_string(&mut err).ok();
+        }
+        err
+    });
+    let mut content = String::new();
+    if let Some(mut out) = stdout {
+        out.read_to_string(&mut content).ok();
+    }
+    let err = stderr_reader.join().unwrap_or_default();
+
+    let status = child.lock().ok()
+        .and_then(|mut slot| slot.child.take())
+        .map(|mut process| process.wait())
+        .ok_or("Changelog fetch cancelled")?
+        .map_err(|e| format!("Failed to run apt-get changelog: {e}"))?;
+
+    if !status.success() {
+        return Err(format!("Error: {}", err.trim()));
+    }
+
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    if lines.is_empty() {
+        Ok(vec!["No changelog available.".to_string()])
+    } else {
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = "\
+Get:1 https://metadata.ftp-master.debian.org foo 1:2.0-1 Changelog [2 kB]
+foo (1:2.0-1) unstable; urgency=medium, binary-only=yes
+
+  [ Alice ]
+  * New upstream release.
+    - Continued line.
+
+ -- Alice Example <alice@example.org>  Mon, 01 Jan 2024 12:00:00 +0000
+
+foo (1.5-1) unstable; urgency=low
+
+  * Bump.
+
+ -- Bob <bob@example.org>  Sun, 01 Oct 2023 12:00:00 +0000
+
+foo (1.5~rc1-1) experimental; urgency=low
+
+  * Release candidate.
+
+ -- Bob <bob@example.org>  Fri, 01 Sep 2023 12:00:00 +0000
+
+foo (1.0-1) unstable; urgency=low
+
+  * Initial release.
+
+ -- Bob <bob@example.org>  Sat, 01 Jul 2023 12:00:00 +0000
+";
+
+    fn entries() -> Vec<ChangelogEntry> {
+        let lines: Vec<String> = CHANGELOG.lines().map(str::to_string).collect();
+        parse_changelog(&lines)
+    }
+
+    fn versions(installed: Option<&str>, candidate: &str) -> Vec<String> {
+        entries_between(&entries(), installed, candidate).map(|e| e.version.clone()).collect()
+    }
+
+    #[test]
+    fn parses_headers_and_trailers() {
+        let entries = entries();
+        assert_eq!(entries.len(), 4);
+
+        let first = &entries[0];
+        assert_eq!(first.package, "foo");
+        assert_eq!(first.version, "1:2.0-1");
+        assert_eq!(first.distribution, "unstable");
+        assert_eq!(first.urgency, "medium");
+        assert_eq!(first.author, "Alice Example <alice@example.org>");
+        assert_eq!(first.date, "Mon, 01 Jan 2024 12:00:00 +0000");
+        // Leading blank line and apt's "Get:" note are dropped, trailing blank lines trimmed
+        assert_eq!(first.changes, ["[ Alice ]", "* New upstream release.", "  - Continued line."]);
+
+        assert_eq!(entries[3].changes, ["* Initial release."]);
+    }
+
+    #[test]
+    fn entry_without_trailer_is_kept() {
+        let lines: Vec<String> = ["foo (1.0) unstable; urgency=low", "", "  * Truncated"]
+            .iter().map(|l| l.to_string()).collect();
+        let entries = parse_changelog(&lines);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].author, "");
+        assert_eq!(entries[0].changes, ["* Truncated"]);
+    }
+
+    #[test]
+    fn header_needs_a_version_and_no_indent() {
+        assert!(parse_header("foo unstable; urgency=low").is_none());
+        assert!(parse_header("  foo (1.0) unstable").is_none());
+        assert_eq!(parse_header("foo (1.0) unstable").unwrap().urgency, "");
+    }
+
+    #[test]
+    fn delta_excludes_installed_and_includes_candidate() {
+        assert_eq!(versions(Some("1.5-1"), "1:2.0-1"), ["1:2.0-1"]);
+        assert_eq!(versions(Some("1.0-1"), "1.5-1"), ["1.5-1", "1.5~rc1-1"]);
+        assert_eq!(versions(None, "1.5~rc1-1"), ["1.5~rc1-1", "1.0-1"]);
+        assert!(versions(Some("1.5-1"), "1.5-1").is_empty());
+    }
+
+    #[test]
+    fn delta_orders_epochs_and_tildes() {
+        // The epoch puts 1:2.0 above any 1.x, 1.5~rc1 sorts before 1.5
+        assert_eq!(versions(Some("1.5~rc1-1"), "1:2.0-1"), ["1:2.0-1", "1.5-1"]);
+        assert_eq!(versions(Some("1.0-1"), "1.5~rc2"), ["1.5~rc1-1"]);
+        // Same upstream version, but only the candidate has the epoch
+        assert_eq!(versions(Some("2.0-1"), "1:2.0-1"), ["1:2.0-1"]);
+    }
+
+    #[test]
+    fn candidate_missing_from_changelog() {
+        // A candidate newer than the newest entry still shows everything since installed
+        assert_eq!(versions(Some("1.5-1"), "1:3.0-1"), ["1:2.0-1"]);
+        // One between entries stops at the older entry
+        assert_eq!(versions(Some("1.0-1"), "1.7-1"), ["1.5-1", "1.5~rc1-1"]);
+        // Installed version not in the changelog either
+        assert_eq!(versions(Some("1.2-1"), "1.5-1"), ["1.5-1", "1.5~rc1-1"]);
+    }
+}
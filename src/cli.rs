@@ -0,0 +1,637 @@
+//! Non-interactive command line
+//!
+//! `synh8 <command>` drives the same `ManagerState` as the TUI and prints the
+//! result to stdout, so scripts get synh8's planning without a terminal UI.
+//! Without a command the TUI starts. `--json` prints the versioned documents
+//! of `json.rs` instead of text.
+
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use color_eyre::Result;
+
+use synh8::config::Config;
+use synh8::core::{ManagerState, check_apt_lock, is_root};
+use synh8::files;
+use synh8::filter::Filter;
+use synh8::json;
+use synh8::progress::StdioRedirect;
+use synh8::root;
+use synh8::types::*;
+
+pub const USAGE: &str = "\
+Usage: synh8 [--root DIR [--no-chroot-scripts]] [COMMAND]
+
+Without a command, synh8 opens the package manager TUI (as root, unless
+--root is given).
+
+Commands:
+  list [FILTER]        List packages. FILTER is upgradable, marked_changes,
+                       installed, not_installed, residual_config, held, all
+                       (the default) or a named filter from config.toml
+  show PACKAGE         Show a package's versions, record fields and dependencies
+  search QUERY...      Search packages (same query language as `/` in the TUI;
+                       a query starting with `/` finds the owners of a path)
+  plan [MARKS]         Resolve the marks and print the changes they lead to
+  apply [MARKS] [-y]   Plan, ask for confirmation and apply (as root)
+  apply [MARKS] --dry-run
+                       Plan and simulate with `apt-get -s`: the dpkg steps in
+                       order, predicted broken states and triggers
+
+Marks (repeatable, comma-separated):
+  --install PKG[=VERSION],...   Install or upgrade, optionally to a given version
+  --remove PKG,...              Remove
+  --purge PKG,...               Remove along with configuration files
+
+Options:
+  -y, --yes            Apply without asking; dpkg keeps changed config files
+  -s, --dry-run        Simulate instead of applying (no root needed)
+  --json               Print JSON (schema version in the `schema` field);
+                       `apply --json` needs --yes
+  --root DIR           Manage the system under DIR (a chroot, container image
+                       or test tree) instead of the host: APT's configuration
+                       directories, dpkg status, sources and locks are read
+                       from there
+  --no-chroot-scripts  With --root: have dpkg run maintainer scripts without
+                       chrooting into DIR, so a tree you own can be managed
+                       without root. The scripts then act on the host's
+                       paths; only use this for trees whose scripts are safe
+                       to run that way
+  -h, --help           Show this help
+  -V, --version        Show the version";
+
+/// What the command line asks for
+pub enum Invocation {
+    Tui { root: Option<RootArgs> },
+    Help,
+    Version,
+    Run { command: Command, json: bool, root: Option<RootArgs> },
+}
+
+/// `--root DIR` and the flags that go with it
+pub struct RootArgs {
+    pub dir: PathBuf,
+    pub chrootless_scripts: bool,
+}
+
+pub enum Command {
+    List { filter: Option<String> },
+    Show { name: String },
+    Search { query: String },
+    Plan(Marks),
+    Apply { marks: Marks, options: ApplyOptions },
+}
+
+/// Flags of `apply`
+#[derive(Default)]
+pub struct ApplyOptions {
+    yes: bool,
+    dry_run: bool,
+}
+
+/// Packages named by `--install`, `--remove` and `--purge`
+#[derive(Default)]
+pub struct Marks {
+    install: Vec<String>,
+    remove: Vec<String>,
+    purge: Vec<String>,
+}
+
+impl Marks {
+    fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.remove.is_empty() && self.purge.is_empty()
+    }
+}
+
+/// Parse the arguments after the program name
+pub fn parse(args: &[String]) -> Result<Invocation, String> {
+    let json = args.iter().any(|a| a == "--json");
+    let args: Vec<String> = args.iter().filter(|a| *a != "--json").cloned().collect();
+    let (root, args) = take_root(args)?;
+    let Some(command) = args.first() else {
+        if json {
+            return Err("`--json` needs a command".to_string());
+        }
+        return Ok(Invocation::Tui { root });
+    };
+    let rest = &args[1..];
+
+    let command = match command.as_str() {
+        "-h" | "--help" | "help" => return Ok(Invocation::Help),
+        "-V" | "--version" => return Ok(Invocation::Version),
+        "list" => match rest {
+            [] => Command::List { filter: None },
+            [filter] => Command::List { filter: Some(filter.clone()) },
+            _ => return Err("`list` takes at most one filter".to_string()),
+        },
+        "show" => match rest {
+            [name] => Command::Show { name: name.clone() },
+            _ => return Err("`show` takes one package name".to_string()),
+        },
+        "search" => {
+            if rest.is_empty() {
+                return Err("`search` needs a query".to_string());
+            }
+            Command::Search { query: rest.join(" ") }
+        }
+        "plan" => {
+            let (marks, options) = parse_marks(rest)?;
+            if options.yes || options.dry_run {
+                return Err("`--yes` and `--dry-run` only apply to `apply`".to_string());
+            }
+            Command::Plan(marks)
+        }
+        "apply" => {
+            let (marks, options) = parse_marks(rest)?;
+            if json && !options.yes && !options.dry_run {
+                return Err("`apply --json` can't ask for confirmation, add --yes".to_string());
+            }
+            Command::Apply { marks, options }
+        }
+        other => return Err(format!("Unknown command `{other}`")),
+    };
+    Ok(Invocation::Run { command, json, root })
+}
+
+/// Split `--root DIR` or `--root=DIR` and `--no-chroot-scripts` off the
+/// arguments
+fn take_root(args: Vec<String>) -> Result<(Option<RootArgs>, Vec<String>), String> {
+    let mut root = None;
+    let mut chrootless_scripts = false;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--no-chroot-scripts" {
+            chrootless_scripts = true;
+            continue;
+        }
+        let dir = match arg.strip_prefix("--root") {
+            Some("") => args.next().ok_or_else(|| "`--root` needs a directory".to_string())?,
+            Some(inline) if inline.starts_with('=') => inline[1..].to_string(),
+            _ => {
+                rest.push(arg);
+                continue;
+            }
+        };
+        if root.replace(PathBuf::from(dir)).is_some() {
+            return Err("`--root` given more than once".to_string());
+        }
+    }
+    if chrootless_scripts && root.is_none() {
+        return Err("`--no-chroot-scripts` needs `--root`".to_string());
+    }
+    Ok((root.map(|dir| RootArgs { dir, chrootless_scripts }), rest))
+}
+
+/// Parse `--install a,b --remove=c -y` style arguments
+fn parse_marks(args: &[String]) -> Result<(Marks, ApplyOptions), String> {
+    let mut marks = Marks::default();
+    let mut options = ApplyOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-y" | "--yes" => {
+                options.yes = true;
+                continue;
+            }
+            "-s" | "--dry-run" => {
+                options.dry_run = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        let list = match flag {
+            "--install" => &mut marks.install,
+            "--remove" => &mut marks.remove,
+            "--purge" => &mut marks.purge,
+            _ => return Err(format!("Unexpected argument `{arg}`")),
+        };
+        let value = inline
+            .or_else(|| args.next().map(String::as_str))
+            .ok_or_else(|| format!("`{flag}` needs a package list"))?;
+        list.extend(value.split(',').filter(|name| !name.is_empty()).map(str::to_string));
+    }
+
+    if marks.is_empty() {
+        return Err("Nothing to plan: use --install, --remove or --purge".to_string());
+    }
+    Ok((marks, options))
+}
+
+/// Run a command. Returns the process exit status.
+pub fn run(command: Command, json: bool) -> Result<i32> {
+    let mut core = ManagerState::new()?;
+    match command {
+        Command::List { filter } => cmd_list(&mut core, filter.as_deref(), json),
+        Command::Show { name } => cmd_show(&core, &name, json),
+        Command::Search { query } => cmd_search(&mut core, &query, json),
+        Command::Plan(marks) => cmd_plan(&mut core, &marks, json),
+        Command::Apply { marks, options } => cmd_apply(&mut core, &marks, &options, json),
+    }
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+fn cmd_list(core: &mut ManagerState, filter: Option<&str>, json: bool) -> Result<i32> {
+    let name = filter.unwrap_or("all");
+    if let Some(&category) = FilterCategory::all().iter().find(|c| category_name(**c) == name) {
+        core.apply_filter(category);
+    } else {
+        let (config, warnings) = Config::load();
+        let (filters, filter_warnings) = Filter::from_config(&config.filters);
+        for warning in warnings.iter().chain(&filter_warnings) {
+            eprintln!("Warning: {warning}");
+        }
+        if !filters.iter().any(|(n, _)| n == name) {
+            eprintln!("Unknown filter `{name}`");
+            return Ok(2);
+        }
+        core.set_custom_filters(filters);
+        core.apply_custom_filter(name);
+    }
+    core.rebuild_list();
+    print_packages(core, json)?;
+    Ok(0)
+}
+
+fn cmd_show(core: &ManagerState, name: &str, json: bool) -> Result<i32> {
+    let cache = core.cache();
+    let Some(mut info) = cache.extract_package_info_by_name(name) else {
+        eprintln!("Package `{name}` not found");
+        return Ok(1);
+    };
+    let details = cache.get_details(&info.name);
+    if core.is_held(info.id) {
+        info.status = PackageStatus::Keep;
+    }
+    let versions = core.available_versions(info.id);
+    let deps = core.get_dependencies(&info.name);
+
+    if json {
+        println!("{}", json::to_string(&json::PackageShow {
+            package: &info,
+            details: &details,
+            versions: &versions,
+            dependencies: json::Dependency::from_pairs(deps),
+        })?);
+        return Ok(0);
+    }
+
+    println!("Package: {}", cache.display_name(&info.name));
+    println!("Status: {}", info.status.label());
+    println!("Architecture: {}", info.architecture);
+    println!("Section: {}", info.section);
+    if !info.installed_version.is_empty() {
+        println!("Installed: {}", info.installed_version);
+    }
+    println!("Candidate: {}", info.candidate_version);
+    println!("Installed-Size: {}", info.installed_size_str());
+    println!("Download-Size: {}", PackageInfo::size_str(info.download_size));
+    for (label, value) in [("Maintainer", &details.maintainer), ("Homepage", &details.homepage), ("Source", &details.source)] {
+        if !value.is_empty() {
+            println!("{label}: {value}");
+        }
+    }
+    if !details.provides.is_empty() {
+        println!("Provides: {}", details.provides.join(", "));
+    }
+
+    println!("Versions:");
+    for v in &versions {
+        let marker = match (v.is_installed, v.is_candidate) {
+            (true, _) => "I",
+            (false, true) => "C",
+            (false, false) => " ",
+        };
+        println!("  {marker} {} {} {}/{} {}", v.version, v.priority, v.origin, v.archive, v.component);
+    }
+
+    if !deps.is_empty() {
+        println!("Dependencies:");
+        for (dep_type, dep_name) in deps {
+            println!("  {dep_type}: {dep_name}");
+        }
+    }
+
+    println!("Description: {}", info.description);
+    for line in details.long_description.lines() {
+        println!(" {}", if line.is_empty() { "." } else { line });
+    }
+    Ok(0)
+}
+
+fn cmd_search(core: &mut ManagerState, query: &str, json: bool) -> Result<i32> {
+    if files::is_path_query(query) {
+        core.set_search_query(query)?;
+        core.search_paths();
+    } else {
+        core.ensure_search_index()?;
+        core.set_search_query(query)?;
+        if let Some(error) = core.search_error() {
+            eprintln!("Invalid query: {error}");
+            return Ok(2);
+        }
+    }
+    core.apply_filter(FilterCategory::All);
+    core.rebuild_list();
+    print_packages(core, json)?;
+    Ok(0)
+}
+
+fn cmd_plan(core: &mut ManagerState, marks: &Marks, json: bool) -> Result<i32> {
+    if let Err(e) = apply_marks(core, marks) {
+        eprintln!("{e}");
+        return Ok(1);
+    }
+    if json {
+        println!("{}", json::to_string(&json::Plan::new(core))?);
+        return Ok(plan_status(core));
+    }
+    print_plan(core);
+    Ok(plan_status(core))
+}
+
+fn cmd_apply(core: &mut ManagerState, marks: &Marks, options: &ApplyOptions, json: bool) -> Result<i32> {
+    if options.dry_run {
+        return dry_run(core, marks, json);
+    }
+    if !is_root() && root::get().is_none() {
+        eprintln!("`apply` must be run as root");
+        return Ok(1);
+    }
+    if let Some(msg) = check_apt_lock() {
+        eprintln!("{msg}");
+        return Ok(1);
+    }
+    if let Err(e) = apply_marks(core, marks) {
+        eprintln!("{e}");
+        return Ok(1);
+    }
+
+    let status = plan_status(core);
+    let nothing_to_do = core.planned_changes().unwrap_or_default().is_empty();
+    if json {
+        return apply_json(core, status != 0 || nothing_to_do);
+    }
+    print_plan(core);
+    if status != 0 || nothing_to_do {
+        return Ok(status);
+    }
+
+    if options.yes {
+        keep_conffiles();
+    } else {
+        print!("Apply these changes? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Aborted.");
+            return Ok(1);
+        }
+    }
+
+    match core.commit() {
+        Ok(()) => {
+            println!("Changes applied successfully.");
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            Ok(1)
+        }
+    }
+}
+
+/// `apply --dry-run`: plan, then simulate the plan with `apt-get -s`
+fn dry_run(core: &mut ManagerState, marks: &Marks, json: bool) -> Result<i32> {
+    if let Err(e) = apply_marks(core, marks) {
+        eprintln!("{e}");
+        return Ok(1);
+    }
+    let simulation = match core.simulate() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("{e}");
+            return Ok(1);
+        }
+    };
+    let failed = plan_status(core) != 0 || !simulation.errors.is_empty() || !simulation.broken.is_empty();
+
+    if json {
+        println!("{}", json::to_string(&json::DryRun { plan: json::Plan::new(core), simulation: &simulation })?);
+        return Ok(i32::from(failed));
+    }
+
+    print_plan(core);
+    println!();
+    println!("Simulated transaction (nothing was changed):");
+    for step in &simulation.steps {
+        let breaks = if step.breaks.is_empty() {
+            String::new()
+        } else {
+            format!("  (breaks {} until configured)", step.breaks.join(", "))
+        };
+        println!("  {} {} {}{breaks}", step.kind.tag(), step.package, step.detail);
+    }
+    if !simulation.triggers.is_empty() {
+        println!("Triggers:");
+        for trigger in &simulation.triggers {
+            println!("  {}: {}", trigger.package, trigger.name);
+        }
+    }
+    if !simulation.broken.is_empty() {
+        eprintln!("Unmet dependencies:");
+        for broken in &simulation.broken {
+            eprintln!("  {broken}");
+        }
+    }
+    for error in &simulation.errors {
+        eprintln!("Error: {error}");
+    }
+    Ok(i32::from(failed))
+}
+
+/// `apply --json`: commit with apt/dpkg output captured, then print the
+/// plan, the outcome and that output as one document
+fn apply_json(core: &mut ManagerState, skip: bool) -> Result<i32> {
+    let plan = serde_json::to_value(json::Plan::new(core))?;
+    if skip {
+        println!("{}", json::to_string(&json::Applied { plan, applied: false, error: None, output: Vec::new() })?);
+        return Ok(plan_status(core));
+    }
+
+    keep_conffiles();
+    let redirect = StdioRedirect::capture()?;
+    let result = core.commit();
+    let output = redirect.output();
+    drop(redirect);
+
+    let error = result.err().map(|e| e.to_string());
+    let status = i32::from(error.is_some());
+    println!("{}", json::to_string(&json::Applied { plan, applied: error.is_none(), error, output })?);
+    Ok(status)
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+/// Nobody is there to answer dpkg's conffile and debconf prompts: keep
+/// changed config files and use the package defaults
+fn keep_conffiles() {
+    let config = rust_apt::config::Config::new();
+    let mut options = vec!["--force-confdef".to_string(), "--force-confold".to_string()];
+    options.extend(root::dpkg_options());
+    config.set_vector("Dpkg::Options", &options);
+    // Safety: we're single-threaded, no concurrent env reads.
+    unsafe { std::env::set_var("DEBIAN_FRONTEND", "noninteractive"); }
+}
+
+/// Exit status for the current plan: 1 if it has errors
+fn plan_status(core: &ManagerState) -> i32 {
+    i32::from(!core.plan_errors().unwrap_or_default().is_empty())
+}
+
+/// Config and CLI name of a filter category
+fn category_name(category: FilterCategory) -> &'static str {
+    match category {
+        FilterCategory::Upgradable => "upgradable",
+        FilterCategory::MarkedChanges => "marked_changes",
+        FilterCategory::Installed => "installed",
+        FilterCategory::NotInstalled => "not_installed",
+        FilterCategory::ResidualConfig => "residual_config",
+        FilterCategory::Held => "held",
+        FilterCategory::All => "all",
+    }
+}
+
+/// Set the user intent of every package in `marks`, then plan
+fn apply_marks(core: &mut ManagerState, marks: &Marks) -> Result<(), String> {
+    let resolve = |core: &ManagerState, name: &str| {
+        core.cache().extract_package_info_by_name(name)
+            .map(|info| info.id)
+            .ok_or_else(|| format!("Package `{name}` not found"))
+    };
+
+    for spec in &marks.install {
+        let (name, version) = match spec.split_once('=') {
+            Some((name, version)) => (name, Some(version)),
+            None => (spec.as_str(), None),
+        };
+        let id = resolve(core, name)?;
+        if let Some(version) = version {
+            if !core.available_versions(id).iter().any(|v| v.version == version) {
+                return Err(format!("Version {version} of `{name}` not found"));
+            }
+            core.mark_version(id, version);
+        } else {
+            core.mark_install(id);
+        }
+    }
+    for name in &marks.remove {
+        let id = resolve(core, name)?;
+        core.set_intent(id, UserIntent::Remove);
+    }
+    for name in &marks.purge {
+        let id = resolve(core, name)?;
+        core.set_intent(id, UserIntent::Purge);
+    }
+
+    core.compute_plan();
+    Ok(())
+}
+
+/// Print the planned changes grouped by action, then their sizes and errors
+fn print_plan(core: &ManagerState) {
+    let changes = core.planned_changes().unwrap_or_default();
+    let cache = core.cache();
+
+    if changes.is_empty() {
+        println!("Nothing to do.");
+    }
+
+    let groups = [
+        (ChangeAction::Install, "INSTALL", "+"),
+        (ChangeAction::Upgrade, "UPGRADE", "↑"),
+        (ChangeAction::Downgrade, "DOWNGRADE", "↓"),
+        (ChangeAction::Remove, "REMOVE", "-"),
+        (ChangeAction::Purge, "PURGE", "_"),
+    ];
+    for (action, heading, symbol) in groups {
+        let group: Vec<_> = changes.iter().filter(|c| c.action == action).collect();
+        if group.is_empty() {
+            continue;
+        }
+        println!("{heading} ({}):", group.len());
+        for change in group {
+            let name = cache.fullname_of(change.package)
+                .map(|n| cache.display_name(n).to_string())
+                .unwrap_or_else(|| format!("(unknown:{})", change.package.index()));
+            let pkg = cache.get_by_id(change.package);
+            let installed = pkg.as_ref().and_then(|p| p.installed()).map(|v| v.version().to_string());
+            let candidate = pkg.as_ref().and_then(|p| p.candidate()).map(|v| v.version().to_string());
+            let versions = match (action, installed, candidate) {
+                (ChangeAction::Install, _, Some(new)) => new,
+                (ChangeAction::Upgrade | ChangeAction::Downgrade, Some(old), Some(new)) => format!("{old} → {new}"),
+                (_, Some(old), _) => old,
+                _ => String::new(),
+            };
+            let reason = match change.reason {
+                ChangeReason::UserRequested => "",
+                ChangeReason::Dependency => " (dependency)",
+                ChangeReason::AutoRemove => " (no longer needed)",
+            };
+            println!("  {symbol} {name} {versions}{reason}");
+        }
+    }
+
+    if !changes.is_empty() {
+        let download_size: u64 = changes.iter().map(|c| c.download_size).sum();
+        let size_change: i64 = changes.iter().map(|c| c.size_change).sum();
+        let sign = if size_change >= 0 { "+" } else { "-" };
+        println!();
+        println!("Download size: {}", PackageInfo::size_str(download_size));
+        println!("Disk space change: {sign}{}", PackageInfo::size_str(size_change.unsigned_abs()));
+    }
+
+    for error in core.plan_errors().unwrap_or_default() {
+        eprintln!("Error: {error}");
+    }
+}
+
+/// Print the current package list, one package per line
+fn print_packages(core: &ManagerState, json: bool) -> Result<()> {
+    if json {
+        println!("{}", json::to_string(&json::PackageList { packages: core.list() })?);
+        return Ok(());
+    }
+
+    let cache = core.cache();
+    let rows: Vec<(&str, &str, String, &str)> = core.list().iter()
+        .map(|pkg| {
+            let version = match pkg.status {
+                PackageStatus::Upgradable => format!("{} → {}", pkg.installed_version, pkg.candidate_version),
+                _ if !pkg.installed_version.is_empty() => pkg.installed_version.clone(),
+                _ => pkg.candidate_version.clone(),
+            };
+            (pkg.status.symbol(), cache.display_name(&pkg.name), version, pkg.description.as_str())
+        })
+        .collect();
+
+    let name_width = rows.iter().map(|r| r.1.chars().count()).max().unwrap_or(0);
+    let version_width = rows.iter().map(|r| r.2.chars().count()).max().unwrap_or(0);
+    for (symbol, name, version, description) in rows {
+        println!("{symbol} {name:name_width$}  {version:version_width$}  {description}");
+    }
+    Ok(())
+}
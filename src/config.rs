@@ -0,0 +1,335 @@
+//! Configuration file (~/.config/synh8/config.toml)
+//!
+//! Holds everything that should survive a restart: column layout, sort order,
+//! the filter and details tab shown at startup, the color theme, key
+//! bindings and custom filters. Unknown keys are reported as warnings so a typo or a config
+//! written by a newer version never prevents synh8 from starting.
+
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::keymap::KeysConfig;
+use crate::theme::ThemeConfig;
+use crate::types::{DetailsTab, FilterCategory, Settings, SortBy};
+
+/// Column visibility (`[columns]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnsConfig {
+    pub status: bool,
+    pub name: bool,
+    pub section: bool,
+    pub installed_version: bool,
+    pub candidate_version: bool,
+    pub download_size: bool,
+}
+
+/// Sort order (`[sort]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortConfig {
+    pub by: SortBy,
+    pub ascending: bool,
+}
+
+/// Contents of config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub default_filter: FilterCategory,
+    pub details_tab: DetailsTab,
+    pub columns: ColumnsConfig,
+    pub sort: SortConfig,
+    pub theme: ThemeConfig,
+    pub keys: KeysConfig,
+    /// Custom filters: name -> expression (see `filter.rs`)
+    pub filters: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::from_settings(&Settings::default())
+    }
+}
+
+impl Default for ColumnsConfig {
+    fn default() -> Self {
+        Config::default().columns
+    }
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Config::default().sort
+    }
+}
+
+impl Config {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            default_filter: settings.default_filter,
+            details_tab: settings.details_tab,
+            columns: ColumnsConfig {
+                status: settings.show_status_column,
+                name: settings.show_name_column,
+                section: settings.show_section_column,
+                installed_version: settings.show_installed_version_column,
+                candidate_version: settings.show_candidate_version_column,
+                download_size: settings.show_download_size_column,
+            },
+            sort: SortConfig {
+                by: settings.sort_by,
+                ascending: settings.sort_ascending,
+            },
+            theme: settings.theme.clone(),
+            keys: settings.keys.clone(),
+            filters: settings.filters.clone(),
+        }
+    }
+
+    pub fn to_settings(&self) -> Settings {
+        Settings {
+            show_status_column: self.columns.status,
+            show_name_column: self.columns.name,
+            show_section_column: self.columns.section,
+            show_installed_version_column: self.columns.installed_version,
+            show_candidate_version_column: self.columns.candidate_version,
+            show_download_size_column: self.columns.download_size,
+            sort_by: self.sort.by,
+            sort_ascending: self.sort.ascending,
+            default_filter: self.default_filter,
+            details_tab: self.details_tab,
+            theme: self.theme.clone(),
+            keys: self.keys.clone(),
+            filters: self.filters.clone(),
+        }
+    }
+
+    /// Parse config text. Returns the config and warnings about unknown keys.
+    pub fn parse(content: &str) -> Result<(Self, Vec<String>), String> {
+        let table: toml::Table = content.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let config: Config = table.clone().try_into().map_err(|e: toml::de::Error| e.message().to_string())?;
+
+        // Everything we understood serializes back; anything else was ignored
+        let known = toml::Table::try_from(&config).map_err(|e| e.to_string())?;
+        let mut warnings = Vec::new();
+        collect_unknown_keys(&table, &known, "", &mut warnings);
+
+        Ok((config, warnings))
+    }
+
+    /// Load the config file. A missing file gives the defaults; a broken
+    /// file gives the defaults plus a warning.
+    pub fn load() -> (Self, Vec<String>) {
+        let Some(path) = config_path() else {
+            return (Self::default(), Vec::new());
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return (Self::default(), Vec::new());
+        };
+
+        match Self::parse(&content) {
+            Ok(result) => result,
+            Err(e) => (Self::default(), vec![format!("{}: {e} (using defaults)", path.display())]),
+        }
+    }
+
+    /// Write the settings that differ from what config.toml holds now,
+    /// creating ~/.config/synh8 if needed. Everything else in the file,
+    /// unknown keys included, is left as it is. A file that doesn't load is
+    /// never overwritten: the defaults it fell back to would replace the
+    /// user's filters, keys and theme.
+    pub fn save(&self) -> Result<(), String> {
+        let path = config_path().ok_or("No home directory to save the config in")?;
+        let (mut file, current) = match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let (current, _) = Self::parse(&content)
+                    .map_err(|e| format!("{}: {e} (fix it to save settings)", path.display()))?;
+                let file: toml::Table = content.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+                (file, current)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (toml::Table::new(), Self::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+
+        let old = toml::Table::try_from(&current).map_err(|e| e.to_string())?;
+        let new = toml::Table::try_from(self).map_err(|e| e.to_string())?;
+        write_changes(&mut file, &old, &new);
+        let content = toml::to_string_pretty(&file).map_err(|e| e.to_string())?;
+
+        if let Some(dir) = path.parent() {
+            let created: Vec<PathBuf> = dir.ancestors()
+                .take_while(|d| !d.exists())
+                .map(Path::to_path_buf)
+                .collect();
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+            for dir in &created {
+                give_to_sudo_user(dir);
+            }
+        }
+        std::fs::write(&path, content).map_err(|e| format!("{}: {e}", path.display()))?;
+        give_to_sudo_user(&path);
+        Ok(())
+    }
+}
+
+/// Copy the values of `new` that differ from `old` into `file`
+fn write_changes(file: &mut toml::Table, old: &toml::Table, new: &toml::Table) {
+    for (key, value) in new {
+        match (value, old.get(key)) {
+            (toml::Value::Table(new_sub), Some(toml::Value::Table(old_sub))) => {
+                let entry = file.entry(key.clone()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+                if !entry.is_table() {
+                    *entry = toml::Value::Table(toml::Table::new());
+                }
+                if let toml::Value::Table(file_sub) = entry {
+                    write_changes(file_sub, old_sub, new_sub);
+                }
+                if entry.as_table().is_some_and(toml::Table::is_empty) {
+                    file.remove(key);
+                }
+            }
+            (value, old) if old != Some(value) => {
+                file.insert(key.clone(), value.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Report keys of `file` that have no counterpart in `known`
+fn collect_unknown_keys(file: &toml::Table, known: &toml::Table, prefix: &str, warnings: &mut Vec<String>) {
+    for (key, value) in file {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+        match (value, known.get(key)) {
+            (_, None) => warnings.push(format!("Unknown config key `{path}` (ignored)")),
+            (toml::Value::Table(sub), Some(toml::Value::Table(known_sub))) => {
+                collect_unknown_keys(sub, known_sub, &path, warnings);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Path of config.toml: $XDG_CONFIG_HOME/synh8, or ~/.config/synh8.
+/// Under sudo this is the invoking user's config, not root's.
+pub fn config_path() -> Option<PathBuf> {
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    let base = match sudo_user() {
+        // sudo may keep root's XDG_CONFIG_HOME; that one isn't the user's
+        Some(user) => xdg
+            .filter(|dir| !root_home().is_some_and(|root| dir.starts_with(root)))
+            .unwrap_or_else(|| user.home.join(".config")),
+        None => xdg.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?,
+    };
+    Some(base.join("synh8").join("config.toml"))
+}
+
+/// The user who ran `sudo synh8`
+struct SudoUser {
+    home: PathBuf,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+/// Look up SUDO_USER in the password database when running as root
+fn sudo_user() -> Option<SudoUser> {
+    // SAFETY: geteuid has no preconditions
+    if unsafe { libc::geteuid() } != 0 {
+        return None;
+    }
+    let name = std::env::var("SUDO_USER").ok().filter(|n| !n.is_empty() && n != "root")?;
+    let name = CString::new(name).ok()?;
+    // SAFETY: name is NUL-terminated; the entry is copied out before any
+    // other passwd call can overwrite it
+    unsafe {
+        let pw = libc::getpwnam(name.as_ptr());
+        if pw.is_null() || (*pw).pw_dir.is_null() {
+            return None;
+        }
+        let home = PathBuf::from(std::ffi::OsStr::from_bytes(CStr::from_ptr((*pw).pw_dir).to_bytes()));
+        Some(SudoUser { home, uid: (*pw).pw_uid, gid: (*pw).pw_gid })
+    }
+}
+
+/// root's home directory from the password database
+fn root_home() -> Option<PathBuf> {
+    // SAFETY: the entry is copied out before any other passwd call can overwrite it
+    unsafe {
+        let pw = libc::getpwuid(0);
+        if pw.is_null() || (*pw).pw_dir.is_null() {
+            return None;
+        }
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(CStr::from_ptr((*pw).pw_dir).to_bytes())))
+    }
+}
+
+/// synh8 runs under sudo, so files written into the invoking user's home
+/// would end up owned by root. Hand them back to that user. Paths outside
+/// that home, or inside root's, are left alone.
+fn give_to_sudo_user(path: &Path) {
+    let Some(user) = sudo_user() else {
+        return;
+    };
+    let in_root_home = root_home().is_some_and(|root| path.starts_with(root));
+    if !path.starts_with(&user.home) || in_root_home {
+        return;
+    }
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return;
+    };
+    // SAFETY: c_path is a valid NUL-terminated string for the duration of the call
+    unsafe {
+        libc::chown(c_path.as_ptr(), user.uid, user.gid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_writes_only_changed_keys() {
+        let content = "\
+future_option = 1
+
+[columns]
+section = false
+
+[filters]
+big = \"installed_size > 100M\"
+";
+        let mut file: toml::Table = content.parse().unwrap();
+        let (current, warnings) = Config::parse(content).unwrap();
+        assert_eq!(warnings.len(), 1);
+
+        let mut changed = current.clone();
+        changed.columns.name = false;
+        let old = toml::Table::try_from(&current).unwrap();
+        let new = toml::Table::try_from(&changed).unwrap();
+        write_changes(&mut file, &old, &new);
+
+        let expected: toml::Table = "\
+future_option = 1
+
+[columns]
+section = false
+name = false
+
+[filters]
+big = \"installed_size > 100M\"
+".parse().unwrap();
+        assert_eq!(file, expected);
+    }
+
+    #[test]
+    fn broken_file_fails_to_parse() {
+        assert!(Config::parse("[columns]\nname = \"yes\"\n").is_err());
+    }
+}
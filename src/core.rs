@@ -4,17 +4,25 @@
 //! - `user_intent: HashMap<PackageId, UserIntent>` is the single source of truth
 //! - APT marks are derived from intent via `plan()`
 //! - State transitions are enforced at compile time
+//! - The package system sits behind the `Backend` trait (`AptCache` unless
+//!   a test supplies another)
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::marker::PhantomData;
 use std::os::unix::io::AsRawFd;
 
 use color_eyre::Result;
-use rust_apt::cache::PackageSort;
 
-use crate::apt::{AptCache, format_apt_errors};
+use crate::apt::AptCache;
+use crate::backend::{Backend, MarkedChange};
+use crate::deps;
+use crate::files;
+use crate::filter::Filter;
+use crate::query;
+use crate::root;
 use crate::search::SearchIndex;
+use crate::simulate::{self, Simulation};
 use crate::types::*;
 
 // ============================================================================
@@ -26,7 +34,12 @@ use crate::types::*;
 pub struct SearchState {
     pub index: Option<SearchIndex>,
     pub query: String,
-    pub results: Option<HashSet<String>>,
+    /// Matching base package names with their rank (lower is better)
+    pub results: Option<HashMap<String, f64>>,
+    /// Why the current query doesn't parse (the previous results stay shown)
+    pub error: Option<String>,
+    /// Lowercased words and phrases the results matched, for highlighting
+    pub terms: Vec<String>,
 }
 
 /// Sort configuration
@@ -51,48 +64,113 @@ impl Default for SortSettings {
 
 /// State shared across all PackageManager states.
 /// Fields are private to prevent bypassing the typestate API.
-struct SharedState {
-    cache: AptCache,
+struct SharedState<B = AptCache> {
+    cache: B,
     user_intent: HashMap<PackageId, UserIntent>,
+    /// Specific versions picked by the user for packages with install intent
+    version_choice: HashMap<PackageId, String>,
+    /// Packages held in the dpkg selections (persistent, unlike user_intent)
+    held: HashSet<PackageId>,
     search: SearchState,
     list: Vec<PackageInfo>,
     upgradable_count: usize,
     installed_count: usize,
+    residual_count: usize,
     total_count: usize,
+    /// Packages per repository tree node
+    repo_counts: BTreeMap<RepoFilter, usize>,
+    /// Packages per section (component prefix stripped)
+    section_counts: BTreeMap<String, usize>,
+    /// The packages behind `repo_counts` and `section_counts`, for narrowing
+    /// the list without asking the backend again
+    repo_members: HashMap<RepoFilter, HashSet<PackageId>>,
+    section_members: HashMap<String, HashSet<PackageId>>,
+    /// Named filters from the config, with their package counts
+    custom_filters: Vec<(String, Filter)>,
+    custom_counts: HashMap<String, usize>,
     selected_filter: FilterCategory,
+    /// Custom filter used instead of `selected_filter`
+    custom_filter: Option<String>,
+    /// Repository and section the list is narrowed to, on top of `selected_filter`
+    repo_filter: Option<RepoFilter>,
+    section_filter: Option<String>,
     sort_settings: SortSettings,
 }
 
-impl SharedState {
-    fn new(cache: AptCache) -> Self {
+impl<B: Backend> SharedState<B> {
+    fn new(cache: B) -> Self {
         Self {
             cache,
             user_intent: HashMap::new(),
+            version_choice: HashMap::new(),
+            held: HashSet::new(),
             search: SearchState::default(),
             list: Vec::new(),
             upgradable_count: 0,
             installed_count: 0,
+            residual_count: 0,
             total_count: 0,
+            repo_counts: BTreeMap::new(),
+            section_counts: BTreeMap::new(),
+            repo_members: HashMap::new(),
+            section_members: HashMap::new(),
+            custom_filters: Vec::new(),
+            custom_counts: HashMap::new(),
             selected_filter: FilterCategory::Upgradable,
+            custom_filter: None,
+            repo_filter: None,
+            section_filter: None,
             sort_settings: SortSettings::default(),
         }
     }
 
-    /// Compute and cache package counts from the APT cache
+    /// Compute and cache package counts and dpkg holds from the backend
     fn compute_cache_counts(&mut self) {
         self.upgradable_count = 0;
         self.installed_count = 0;
+        self.residual_count = 0;
         self.total_count = 0;
-
-        for pkg in self.cache.packages(&PackageSort::default()) {
+        self.repo_counts.clear();
+        self.section_counts.clear();
+        self.repo_members.clear();
+        self.section_members.clear();
+        self.custom_counts.clear();
+        self.held.clear();
+
+        self.cache.for_each_package(false, &mut |pkg| {
             self.total_count += 1;
+            let id = self.cache.get_id(&pkg.fullname());
+            for repo in pkg.repos() {
+                *self.repo_counts.entry(repo.clone()).or_default() += 1;
+                if let Some(id) = id {
+                    self.repo_members.entry(repo).or_default().insert(id);
+                }
+            }
+            if let Some(section) = pkg.section() {
+                *self.section_counts.entry(section.clone()).or_default() += 1;
+                if let Some(id) = id {
+                    self.section_members.entry(section).or_default().insert(id);
+                }
+            }
+            for (name, filter) in &self.custom_filters {
+                let count = self.custom_counts.entry(name.clone()).or_default();
+                if filter.matches(pkg) {
+                    *count += 1;
+                }
+            }
+            if pkg.is_held()
+                && let Some(id) = id {
+                    self.held.insert(id);
+            }
             if pkg.is_installed() {
                 self.installed_count += 1;
                 if pkg.is_upgradable() {
                     self.upgradable_count += 1;
                 }
+            } else if pkg.is_residual_config() {
+                self.residual_count += 1;
             }
-        }
+        });
     }
 }
 
@@ -101,8 +179,8 @@ impl SharedState {
 // ============================================================================
 
 /// Package manager with compile-time state tracking
-pub struct PackageManager<S> {
-    shared: SharedState,
+pub struct PackageManager<S, B = AptCache> {
+    shared: SharedState<B>,
     state: S,
     _phantom: PhantomData<S>,
 }
@@ -111,7 +189,13 @@ pub struct PackageManager<S> {
 impl PackageManager<Clean> {
     /// Create a new PackageManager in Clean state
     pub fn new() -> Result<Self> {
-        let cache = AptCache::new()?;
+        Ok(Self::with_backend(AptCache::new()?))
+    }
+}
+
+impl<B: Backend> PackageManager<Clean, B> {
+    /// Create a new PackageManager in Clean state on top of any backend
+    pub fn with_backend(cache: B) -> Self {
         let mut shared = SharedState::new(cache);
         shared.compute_cache_counts();
 
@@ -121,11 +205,11 @@ impl PackageManager<Clean> {
             _phantom: PhantomData,
         };
         mgr.rebuild_list();
-        Ok(mgr)
+        mgr
     }
 
     /// Mark a package for install/upgrade, transitioning to Dirty
-    pub fn mark_install(mut self, id: PackageId) -> PackageManager<Dirty> {
+    pub fn mark_install(mut self, id: PackageId) -> PackageManager<Dirty, B> {
         self.shared.user_intent.insert(id, UserIntent::Install);
         PackageManager {
             shared: self.shared,
@@ -135,7 +219,7 @@ impl PackageManager<Clean> {
     }
 
     /// Mark a package for removal, transitioning to Dirty
-    pub fn mark_remove(mut self, id: PackageId) -> PackageManager<Dirty> {
+    pub fn mark_remove(mut self, id: PackageId) -> PackageManager<Dirty, B> {
         self.shared.user_intent.insert(id, UserIntent::Remove);
         PackageManager {
             shared: self.shared,
@@ -143,10 +227,20 @@ impl PackageManager<Clean> {
             _phantom: PhantomData,
         }
     }
+
+    /// Set intent for a package, transitioning to Dirty
+    pub fn set_intent(self, id: PackageId, intent: UserIntent) -> PackageManager<Dirty, B> {
+        PackageManager {
+            shared: self.shared,
+            state: Dirty,
+            _phantom: PhantomData,
+        }
+        .set_intent(id, intent)
+    }
 }
 
 // Dirty state - has user marks, no computed plan
-impl PackageManager<Dirty> {
+impl<B: Backend> PackageManager<Dirty, B> {
     /// Mark a package for install/upgrade
     pub fn mark_install(mut self, id: PackageId) -> Self {
         self.shared.user_intent.insert(id, UserIntent::Install);
@@ -156,12 +250,14 @@ impl PackageManager<Dirty> {
     /// Mark a package for removal
     pub fn mark_remove(mut self, id: PackageId) -> Self {
         self.shared.user_intent.insert(id, UserIntent::Remove);
+        self.shared.version_choice.remove(&id);
         self
     }
 
     /// Unmark a package (remove user intent)
     pub fn unmark(mut self, id: PackageId) -> Self {
         self.shared.user_intent.remove(&id);
+        self.shared.version_choice.remove(&id);
         self
     }
 
@@ -171,13 +267,18 @@ impl PackageManager<Dirty> {
             UserIntent::Default => { self.shared.user_intent.remove(&id); }
             _ => { self.shared.user_intent.insert(id, intent); }
         }
+        if intent != UserIntent::Install {
+            self.shared.version_choice.remove(&id);
+        }
         self
     }
 
     /// Reset all marks, returning to Clean state
-    pub fn reset(mut self) -> PackageManager<Clean> {
+    pub fn reset(mut self) -> PackageManager<Clean, B> {
         self.shared.user_intent.clear();
+        self.shared.version_choice.clear();
         self.shared.cache.clear_all_marks();
+        self.shared.cache.restore_candidates();
         PackageManager {
             shared: self.shared,
             state: Clean,
@@ -186,52 +287,60 @@ impl PackageManager<Dirty> {
     }
 
     /// Compute plan from user intent, transitioning to Planned
-    pub fn plan(mut self) -> PackageManager<Planned> {
-        // 1. Clear all APT marks
+    pub fn plan(mut self) -> PackageManager<Planned, B> {
+        // 1. Clear all APT marks and candidate overrides, then apply picked versions
         self.shared.cache.clear_all_marks();
+        self.shared.cache.restore_candidates();
+        for (&id, version) in &self.shared.version_choice {
+            self.shared.cache.set_candidate_version(id, version);
+        }
 
         // 2. Apply user intent to APT cache
         for (&id, &intent) in &self.shared.user_intent {
             match intent {
-                UserIntent::Install => self.shared.cache.mark_install_id(id),
-                UserIntent::Remove => self.shared.cache.mark_delete_id(id),
-                UserIntent::Hold => self.shared.cache.mark_keep_id(id),
+                UserIntent::Install => self.shared.cache.mark_install(id),
+                UserIntent::Remove => self.shared.cache.mark_delete(id, false),
+                UserIntent::Purge => self.shared.cache.mark_delete(id, true),
+                UserIntent::Hold => self.shared.cache.mark_keep(id),
                 UserIntent::Default => {}
             }
         }
 
+        // Held packages stay at their current version unless the user
+        // explicitly asked for something else
+        for &id in &self.shared.held {
+            if !self.shared.user_intent.contains_key(&id) {
+                self.shared.cache.mark_keep(id);
+            }
+        }
+
         // 3. Resolve dependencies
         let errors = match self.shared.cache.resolve() {
             Ok(()) => Vec::new(),
-            Err(e) => vec![format_apt_errors(&e)],
+            Err(e) => vec![e],
         };
 
         // 4. Build changeset from APT state
         // Collect package data first to avoid borrow conflict
-        let change_data: Vec<_> = self.shared.cache.get_changes()
-            .map(|pkg| {
-                let fullname = pkg.fullname(false);
-                let is_installed = pkg.is_installed();
-                let marked_install = pkg.marked_install();
-                let marked_upgrade = pkg.marked_upgrade();
-                let marked_delete = pkg.marked_delete();
-                let candidate_info = pkg.candidate().map(|c| (c.size(), c.installed_size()));
-                (fullname, is_installed, marked_install, marked_upgrade, marked_delete, candidate_info)
-            })
-            .collect();
+        let change_data = self.shared.cache.changes();
 
         let mut changes = Vec::new();
         let mut download_size = 0u64;
         let mut install_size_change = 0i64;
 
-        for (fullname, is_installed, marked_install, marked_upgrade, marked_delete, candidate_info) in change_data {
+        for MarkedChange {
+            fullname, is_installed, is_auto, marked_install, marked_upgrade, marked_downgrade,
+            marked_delete, marked_purge, candidate_sizes, installed_size,
+        } in change_data {
             // Use FULL name for ID lookup - PackageId maps to full names
             let id = self.shared.cache.id_for(&fullname);
 
             let is_user_requested = self.shared.user_intent.contains_key(&id);
 
-            let (action, reason) = if marked_install || marked_upgrade {
-                let action = if is_installed {
+            let (action, reason) = if marked_install || marked_upgrade || marked_downgrade {
+                let action = if marked_downgrade {
+                    ChangeAction::Downgrade
+                } else if is_installed {
                     ChangeAction::Upgrade
                 } else {
                     ChangeAction::Install
@@ -243,23 +352,32 @@ impl PackageManager<Dirty> {
                 };
                 (action, reason)
             } else if marked_delete {
+                // Manually installed packages only get removed by the resolver
+                // when they depend on (or conflict with) something the user changed
                 let reason = if is_user_requested {
                     ChangeReason::UserRequested
-                } else {
+                } else if is_auto {
                     ChangeReason::AutoRemove
+                } else {
+                    ChangeReason::Dependency
+                };
+                let action = if marked_purge {
+                    ChangeAction::Purge
+                } else {
+                    ChangeAction::Remove
                 };
-                (ChangeAction::Remove, reason)
+                (action, reason)
             } else {
                 continue;
             };
 
-            let (pkg_download, pkg_size_change) = if let Some((dl_size, inst_size)) = candidate_info {
-                let sz = if action == ChangeAction::Remove {
-                    -(inst_size as i64)
-                } else {
-                    inst_size as i64
-                };
-                (dl_size, sz)
+            let (pkg_download, pkg_size_change) = if matches!(action, ChangeAction::Remove | ChangeAction::Purge) {
+                // Removals download nothing and free what the installed version uses
+                (0, -(installed_size.unwrap_or(0) as i64))
+            } else if let Some((dl_size, inst_size)) = candidate_sizes {
+                // Upgrades and downgrades replace the installed version
+                let replaced = installed_size.unwrap_or(0) as i64;
+                (dl_size, inst_size as i64 - replaced)
             } else {
                 (0, 0)
             };
@@ -298,7 +416,7 @@ impl PackageManager<Dirty> {
 }
 
 // Planned state - dependencies resolved, changeset computed
-impl PackageManager<Planned> {
+impl<B: Backend> PackageManager<Planned, B> {
     /// Get the computed changes
     pub fn changes(&self) -> &[PlannedChange] {
         &self.state.changes
@@ -320,12 +438,18 @@ impl PackageManager<Planned> {
                     ChangeAction::Install => PackageStatus::MarkedForInstall,
                     ChangeAction::Upgrade => PackageStatus::MarkedForUpgrade,
                     ChangeAction::Remove => PackageStatus::MarkedForRemove,
-                    ChangeAction::Downgrade => PackageStatus::MarkedForUpgrade,
+                    ChangeAction::Purge => PackageStatus::MarkedForPurge,
+                    ChangeAction::Downgrade => PackageStatus::MarkedForDowngrade,
                 };
             }
         }
     }
 
+    /// Get the whole plan (changes, totals and errors)
+    pub fn planned(&self) -> &Planned {
+        &self.state
+    }
+
     /// Get total download size
     pub fn download_size(&self) -> u64 {
         self.state.download_size
@@ -347,7 +471,7 @@ impl PackageManager<Planned> {
     }
 
     /// Go back to modify marks (keeps marks, discards plan)
-    pub fn modify(self) -> PackageManager<Dirty> {
+    pub fn modify(self) -> PackageManager<Dirty, B> {
         PackageManager {
             shared: self.shared,
             state: Dirty,
@@ -356,10 +480,11 @@ impl PackageManager<Planned> {
     }
 
     /// Commit the changes to the system
-    pub fn commit(mut self) -> Result<PackageManager<Clean>> {
+    pub fn commit(mut self) -> Result<PackageManager<Clean, B>> {
         self.shared.cache.commit()?;
         self.shared.user_intent.clear();
-        self.shared.search.index = None; // Invalidate search index
+        self.shared.version_choice.clear();
+        self.shared.search.index = None; // Re-synced from disk on next search
 
         Ok(PackageManager {
             shared: self.shared,
@@ -367,7 +492,10 @@ impl PackageManager<Planned> {
             _phantom: PhantomData,
         })
     }
+}
 
+// Committing with progress reporting needs the rust-apt progress types
+impl PackageManager<Planned> {
     /// Commit the changes using caller-provided progress implementations
     pub fn commit_with_progress(
         mut self,
@@ -376,6 +504,7 @@ impl PackageManager<Planned> {
     ) -> Result<PackageManager<Clean>> {
         self.shared.cache.commit_with_progress(acquire_progress, install_progress)?;
         self.shared.user_intent.clear();
+        self.shared.version_choice.clear();
         self.shared.search.index = None;
 
         Ok(PackageManager {
@@ -390,14 +519,14 @@ impl PackageManager<Planned> {
 // Shared functionality (all states)
 // ============================================================================
 
-impl<S: ReadableState> PackageManager<S> {
+impl<S: ReadableState, B: Backend> PackageManager<S, B> {
     /// Get the shared state (read-only for most things)
-    pub fn shared(&self) -> &SharedState {
+    pub fn shared(&self) -> &SharedState<B> {
         &self.shared
     }
 
     /// Get mutable access to shared state
-    pub fn shared_mut(&mut self) -> &mut SharedState {
+    pub fn shared_mut(&mut self) -> &mut SharedState<B> {
         &mut self.shared
     }
 
@@ -426,6 +555,32 @@ impl<S: ReadableState> PackageManager<S> {
         self.shared.user_intent.contains_key(&id)
     }
 
+    /// Check if a package is held in the dpkg selections
+    pub fn is_held(&self, id: PackageId) -> bool {
+        self.shared.held.contains(&id)
+    }
+
+    /// Get the version the user picked for a package, if any
+    pub fn chosen_version(&self, id: PackageId) -> Option<&str> {
+        self.shared.version_choice.get(&id).map(String::as_str)
+    }
+
+    /// List every available version of a package with origin and pin priority
+    pub fn available_versions(&self, id: PackageId) -> Vec<AvailableVersion> {
+        self.shared.cache.available_versions(id)
+    }
+
+    /// Set or release a dpkg hold. Persisted immediately via the dpkg selections.
+    pub fn set_hold(&mut self, id: PackageId, hold: bool) -> Result<(), String> {
+        self.shared.cache.set_hold(id, hold)?;
+        if hold {
+            self.shared.held.insert(id);
+        } else {
+            self.shared.held.remove(&id);
+        }
+        Ok(())
+    }
+
     /// Get current filter
     pub fn selected_filter(&self) -> FilterCategory {
         self.shared.selected_filter
@@ -443,7 +598,7 @@ impl<S: ReadableState> PackageManager<S> {
 
     /// Get search result count
     pub fn search_result_count(&self) -> Option<usize> {
-        self.shared.search.results.as_ref().map(std::collections::HashSet::len)
+        self.shared.search.results.as_ref().map(HashMap::len)
     }
 
     // === Filtering & Listing ===
@@ -451,6 +606,34 @@ impl<S: ReadableState> PackageManager<S> {
     /// Apply a filter category and rebuild the package list
     pub fn apply_filter(&mut self, filter: FilterCategory) {
         self.shared.selected_filter = filter;
+        self.shared.custom_filter = None;
+        self.rebuild_list();
+    }
+
+    /// Apply a named custom filter in place of the filter category
+    pub fn apply_custom_filter(&mut self, name: &str) {
+        self.shared.custom_filter = Some(name.to_string());
+        self.rebuild_list();
+    }
+
+    /// Replace the custom filters and recount them
+    pub fn set_custom_filters(&mut self, filters: Vec<(String, Filter)>) {
+        if self.shared.custom_filter.as_ref().is_some_and(|active| !filters.iter().any(|(name, _)| name == active)) {
+            self.shared.custom_filter = None;
+        }
+        self.shared.custom_filters = filters;
+        self.shared.compute_cache_counts();
+    }
+
+    /// Narrow the list to a repository (None for any) and rebuild it
+    pub fn set_repo_filter(&mut self, repo: Option<RepoFilter>) {
+        self.shared.repo_filter = repo;
+        self.rebuild_list();
+    }
+
+    /// Narrow the list to a section (None for any) and rebuild it
+    pub fn set_section_filter(&mut self, section: Option<String>) {
+        self.shared.section_filter = section;
         self.rebuild_list();
     }
 
@@ -458,52 +641,69 @@ impl<S: ReadableState> PackageManager<S> {
     pub fn rebuild_list(&mut self) -> ColumnWidths {
         self.shared.list.clear();
 
-        let sort = if self.shared.selected_filter == FilterCategory::Upgradable {
-            PackageSort::default().upgradable()
-        } else {
-            PackageSort::default()
-        };
+        let custom = self.shared.custom_filter.as_ref()
+            .and_then(|name| self.shared.custom_filters.iter().find(|(n, _)| n == name))
+            .map(|(_, filter)| filter);
+
+        let upgradable_only = custom.is_none() && self.shared.selected_filter == FilterCategory::Upgradable;
 
         // First pass: collect package full names that match filters
-        // (avoids borrow conflict between cache iteration and extract_package_info)
+        // (avoids borrow conflict between cache iteration and package_info)
         // Use full names to properly handle multi-arch packages
-        let matching_fullnames: Vec<String> = {
+        let mut matching_fullnames: Vec<String> = Vec::new();
+        {
             let search_results = &self.shared.search.results;
             let user_intent = &self.shared.user_intent;
-            let fullname_to_id = &self.shared.cache.fullname_to_id;
-
-            self.shared.cache.packages(&sort)
-                .filter(|pkg| {
-                    let matches_category = match self.shared.selected_filter {
-                        FilterCategory::Upgradable => pkg.is_upgradable(),
-                        FilterCategory::MarkedChanges => {
-                            // Check both user_intent (works in Dirty state) and
-                            // APT marks (works in Planned state for dependencies)
-                            let has_user_intent = fullname_to_id.get(&pkg.fullname(false))
-                                .map(|id| user_intent.contains_key(id))
-                                .unwrap_or(false);
-                            has_user_intent || pkg.marked_install() || pkg.marked_upgrade() || pkg.marked_delete()
-                        }
-                        FilterCategory::Installed => pkg.is_installed(),
-                        FilterCategory::NotInstalled => !pkg.is_installed(),
-                        FilterCategory::All => true,
-                    };
+            let held = &self.shared.held;
+            let cache = &self.shared.cache;
+
+            // Members of the repository and section the list is narrowed to
+            let narrowed: Vec<Option<&HashSet<PackageId>>> = [
+                self.shared.repo_filter.as_ref().map(|repo| self.shared.repo_members.get(repo)),
+                self.shared.section_filter.as_ref().map(|section| self.shared.section_members.get(section)),
+            ].into_iter().flatten().collect();
+
+            cache.for_each_package(upgradable_only, &mut |pkg| {
+                let matches_category = match (custom, self.shared.selected_filter) {
+                    (Some(filter), _) => filter.matches(pkg),
+                    (None, FilterCategory::Upgradable) => pkg.is_upgradable(),
+                    (None, FilterCategory::MarkedChanges) => {
+                        // Check both user_intent (works in Dirty state) and
+                        // APT marks (works in Planned state for dependencies)
+                        let has_user_intent = cache.get_id(&pkg.fullname())
+                            .map(|id| user_intent.contains_key(&id))
+                            .unwrap_or(false);
+                        has_user_intent || pkg.is_marked()
+                    }
+                    (None, FilterCategory::Installed) => pkg.is_installed(),
+                    (None, FilterCategory::NotInstalled) => !pkg.is_installed(),
+                    (None, FilterCategory::ResidualConfig) => pkg.is_residual_config(),
+                    (None, FilterCategory::Held) => cache.get_id(&pkg.fullname())
+                        .map(|id| held.contains(&id))
+                        .unwrap_or(false),
+                    (None, FilterCategory::All) => true,
+                };
 
-                    // Search matches base name for user convenience
-                    let matches_search = match search_results {
-                        Some(results) => results.contains(pkg.name()),
-                        None => true,
-                    };
+                // Search matches base name for user convenience
+                let matches_search = match search_results {
+                    Some(results) => results.contains_key(pkg.name()),
+                    None => true,
+                };
 
-                    matches_category && matches_search
-                })
-                .map(|pkg| pkg.fullname(false))
-                .collect()
-        };
+                let matches_narrowing = narrowed.is_empty()
+                    || cache.get_id(&pkg.fullname()).is_some_and(|id| {
+                        narrowed.iter().all(|members| members.is_some_and(|m| m.contains(&id)))
+                    });
+
+                if matches_category && matches_search && matches_narrowing {
+                    matching_fullnames.push(pkg.fullname());
+                }
+            });
+        }
 
         // Second pass: extract full package info
         for fullname in matching_fullnames {
-            if let Some(mut info) = self.shared.cache.extract_package_info_by_name(&fullname) {
+            if let Some(mut info) = self.shared.cache.package_info(&fullname) {
                 // Update status based on user intent
                 if let Some(&intent) = self.shared.user_intent.get(&info.id) {
                     info.status = match intent {
@@ -515,9 +715,12 @@ impl<S: ReadableState> PackageManager<S> {
                             }
                         }
                         UserIntent::Remove => PackageStatus::MarkedForRemove,
+                        UserIntent::Purge => PackageStatus::MarkedForPurge,
                         UserIntent::Hold => PackageStatus::Keep,
                         UserIntent::Default => info.status,
                     };
+                } else if self.shared.held.contains(&info.id) {
+                    info.status = PackageStatus::Keep;
                 }
                 self.shared.list.push(info);
             }
@@ -537,14 +740,24 @@ impl<S: ReadableState> PackageManager<S> {
         col_widths
     }
 
-    /// Sort the package list
+    /// Sort the package list. While a query is set the list is ranked by
+    /// relevance, best match first, whatever the configured order.
     fn sort_list(&mut self) {
+        if let Some(ranks) = &self.shared.search.results {
+            let rank = |pkg: &PackageInfo| {
+                let base = pkg.name.split(':').next().unwrap_or(&pkg.name);
+                ranks.get(base).copied().unwrap_or(f64::MAX)
+            };
+            self.shared.list.sort_by(|a, b| rank(a).total_cmp(&rank(b)).then_with(|| a.name.cmp(&b.name)));
+            return;
+        }
+
         let sort_by = self.shared.sort_settings.sort_by;
         let ascending = self.shared.sort_settings.ascending;
 
         self.shared.list.sort_by(|a, b| {
             let ord = match sort_by {
-                SortBy::Name => a.name.cmp(&b.name),
+                SortBy::Name | SortBy::Relevance => a.name.cmp(&b.name),
                 SortBy::Section => a.section.cmp(&b.section),
                 SortBy::InstalledVersion => a.installed_version.cmp(&b.installed_version),
                 SortBy::CandidateVersion => a.candidate_version.cmp(&b.candidate_version),
@@ -553,6 +766,15 @@ impl<S: ReadableState> PackageManager<S> {
         });
     }
 
+    /// The order the list is currently in
+    pub fn effective_sort(&self) -> SortBy {
+        if self.shared.search.results.is_some() {
+            SortBy::Relevance
+        } else {
+            self.shared.sort_settings.sort_by
+        }
+    }
+
     /// Update sort settings and re-sort
     pub fn set_sort(&mut self, sort_by: SortBy, ascending: bool) {
         self.shared.sort_settings.sort_by = sort_by;
@@ -562,33 +784,74 @@ impl<S: ReadableState> PackageManager<S> {
 
     // === Search ===
 
-    /// Ensure search index is built
-    pub fn ensure_search_index(&mut self) -> Result<std::time::Duration> {
-        if self.shared.search.index.is_none() {
-            let mut index = SearchIndex::new()?;
-            let (_count, duration) = index.build(&self.shared.cache)?;
-            self.shared.search.index = Some(index);
-            return Ok(duration);
-        }
-        Ok(std::time::Duration::ZERO)
-    }
-
-    /// Set search query and execute search
+    /// Set search query and execute search.
+    /// A query that doesn't parse sets `search_error` and keeps the previous results.
     pub fn set_search_query(&mut self, query: &str) -> Result<()> {
         self.shared.search.query = query.to_string();
+        self.shared.search.error = None;
+
+        if query.trim().is_empty() {
+            self.shared.search.results = None;
+            self.shared.search.terms.clear();
+            return Ok(());
+        }
 
-        if query.is_empty() {
+        // File paths are only looked up on confirm; until then nothing matches
+        if files::is_path_query(query) {
             self.shared.search.results = None;
-        } else if let Some(ref index) = self.shared.search.index {
-            self.shared.search.results = Some(index.search(query)?);
+            self.shared.search.terms.clear();
+            return Ok(());
+        }
+
+        let expr = match query::parse(query) {
+            Ok(expr) => expr,
+            Err(e) => {
+                self.shared.search.error = Some(e.to_string());
+                return Ok(());
+            }
+        };
+        if let Some(ref index) = self.shared.search.index {
+            self.shared.search.results = Some(index.search(&expr)?);
+            self.shared.search.terms = expr.positive_terms()
+                .into_iter()
+                .map(|(_, text, _)| text.to_lowercase())
+                .collect();
         }
         Ok(())
     }
 
+    /// Search for the packages owning files that match the current path query
+    pub fn search_paths(&mut self) {
+        let owners = files::find_owners(&self.shared.search.query);
+        self.set_path_results(owners);
+    }
+
+    /// Show the owners of the current path query, found by `files::find_owners`
+    pub fn set_path_results(&mut self, owners: HashMap<String, f64>) {
+        self.shared.search.results = Some(owners);
+        self.shared.search.terms.clear();
+    }
+
+    /// Terms to highlight in package details (empty when not searching)
+    pub fn search_terms(&self) -> &[String] {
+        if self.shared.search.results.is_some() {
+            &self.shared.search.terms
+        } else {
+            &[]
+        }
+    }
+
+    /// Get the parse error of the current query, if any
+    pub fn search_error(&self) -> Option<&str> {
+        self.shared.search.error.as_deref()
+    }
+
     /// Clear search query and results
     pub fn clear_search(&mut self) {
         self.shared.search.query.clear();
         self.shared.search.results = None;
+        self.shared.search.error = None;
+        self.shared.search.terms.clear();
     }
 
     // === Dependency Queries ===
@@ -603,30 +866,6 @@ impl<S: ReadableState> PackageManager<S> {
         self.shared.cache.get_reverse_dependencies(name)
     }
 
-    /// Fetch changelog for a package
-    pub fn fetch_changelog(&self, name: &str) -> Result<Vec<String>, String> {
-        match std::process::Command::new("apt")
-            .args(["changelog", name])
-            .output()
-        {
-            Ok(output) => {
-                if output.status.success() {
-                    let content = String::from_utf8_lossy(&output.stdout);
-                    let lines: Vec<String> = content.lines().map(std::string::ToString::to_string).collect();
-                    if lines.is_empty() {
-                        Ok(vec!["No changelog available.".to_string()])
-                    } else {
-                        Ok(lines)
-                    }
-                } else {
-                    let err = String::from_utf8_lossy(&output.stderr);
-                    Err(format!("Error: {err}"))
-                }
-            }
-            Err(e) => Err(format!("Failed to run apt changelog: {e}")),
-        }
-    }
-
     /// Update all cached package counts
     pub fn update_cache_counts(&mut self) {
         self.shared.compute_cache_counts();
@@ -640,12 +879,29 @@ impl<S: ReadableState> PackageManager<S> {
 
         self.shared.cache.refresh().map_err(|e| e.to_string())?;
         self.shared.user_intent.clear();
+        self.shared.version_choice.clear();
         self.shared.search.index = None;
         self.shared.search.query.clear();
         self.shared.search.results = None;
+        self.shared.search.error = None;
         self.update_cache_counts();
         Ok(())
     }
+}
+
+// The search index and `apt update` work on the APT cache itself
+impl<S: ReadableState> PackageManager<S> {
+    /// Ensure the search index is open and up to date. Returns how many
+    /// packages had to be re-indexed and how long that took.
+    pub fn ensure_search_index(&mut self) -> Result<(usize, std::time::Duration)> {
+        if self.shared.search.index.is_none() {
+            let mut index = SearchIndex::open()?;
+            let result = index.sync(&self.shared.cache)?;
+            self.shared.search.index = Some(index);
+            return Ok(result);
+        }
+        Ok((0, std::time::Duration::ZERO))
+    }
 
     /// Run `apt update` with caller-provided progress, then refresh
     pub fn update_with_progress(
@@ -659,9 +915,11 @@ impl<S: ReadableState> PackageManager<S> {
         self.shared.cache.update_with_progress(acquire_progress)
             .map_err(|e| e.to_string())?;
         self.shared.user_intent.clear();
+        self.shared.version_choice.clear();
         self.shared.search.index = None;
         self.shared.search.query.clear();
         self.shared.search.results = None;
+        self.shared.search.error = None;
         self.update_cache_counts();
         Ok(())
     }
@@ -674,22 +932,78 @@ impl<S: ReadableState> PackageManager<S> {
 /// Wrapper enum that allows mutable access without consuming self
 /// This is necessary for TUI where we can't easily handle typestate transitions
 #[derive(Default)]
-pub enum ManagerState {
-    Clean(PackageManager<Clean>),
-    Dirty(PackageManager<Dirty>),
-    Planned(PackageManager<Planned>),
+pub enum ManagerState<B = AptCache> {
+    Clean(PackageManager<Clean, B>),
+    Dirty(PackageManager<Dirty, B>),
+    Planned(PackageManager<Planned, B>),
     /// Temporary placeholder during state transitions (never observed externally)
     #[default]
     Transitioning,
 }
 
-
 impl ManagerState {
     /// Create a new manager in Clean state
     pub fn new() -> Result<Self> {
         Ok(ManagerState::Clean(PackageManager::new()?))
     }
 
+    /// Dry-run the planned changes with `apt-get -s` (nothing planned
+    /// simulates nothing)
+    pub fn simulate(&self) -> Result<Simulation, String> {
+        simulate::simulate(self.cache(), self.planned_changes().unwrap_or_default())
+    }
+
+    pub fn ensure_search_index(&mut self) -> Result<(usize, std::time::Duration)> {
+        match self {
+            ManagerState::Clean(m) => m.ensure_search_index(),
+            ManagerState::Dirty(m) => m.ensure_search_index(),
+            ManagerState::Planned(m) => m.ensure_search_index(),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    /// Commit planned changes with caller-provided progress implementations
+    pub fn commit_with_progress(
+        &mut self,
+        acquire_progress: &mut rust_apt::progress::AcquireProgress,
+        install_progress: &mut rust_apt::progress::InstallProgress,
+    ) -> Result<()> {
+        *self = match std::mem::take(self) {
+            ManagerState::Clean(m) => ManagerState::Clean(m),
+            ManagerState::Dirty(m) => {
+                let planned = m.plan();
+                let clean = planned.commit_with_progress(acquire_progress, install_progress)?;
+                ManagerState::Clean(clean)
+            }
+            ManagerState::Planned(m) => {
+                let clean = m.commit_with_progress(acquire_progress, install_progress)?;
+                ManagerState::Clean(clean)
+            }
+            ManagerState::Transitioning => panic!("ManagerState::Transitioning should not be observed"),
+        };
+        Ok(())
+    }
+
+    /// Run `apt update` with caller-provided progress
+    pub fn update_with_progress(
+        &mut self,
+        acquire_progress: &mut rust_apt::progress::AcquireProgress,
+    ) -> Result<(), String> {
+        match self {
+            ManagerState::Clean(m) => m.update_with_progress(acquire_progress),
+            ManagerState::Dirty(m) => m.update_with_progress(acquire_progress),
+            ManagerState::Planned(m) => m.update_with_progress(acquire_progress),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+}
+
+impl<B: Backend> ManagerState<B> {
+    /// Create a new manager in Clean state on top of any backend
+    pub fn with_backend(backend: B) -> Self {
+        ManagerState::Clean(PackageManager::with_backend(backend))
+    }
+
     /// Check if in Clean state
     pub fn is_clean(&self) -> bool {
         matches!(self, ManagerState::Clean(_))
@@ -707,12 +1021,33 @@ impl ManagerState {
 
     /// Get the planned changes (only valid in Planned state)
     pub fn planned_changes(&self) -> Option<&[PlannedChange]> {
+        self.planned().map(|p| p.changes.as_slice())
+    }
+
+    /// Get the plan with its totals and errors (only valid in Planned state)
+    pub fn planned(&self) -> Option<&Planned> {
         match self {
-            ManagerState::Planned(m) => Some(m.changes()),
+            ManagerState::Planned(m) => Some(m.planned()),
             _ => None,
         }
     }
 
+    /// Display name, installed version and new version of every planned upgrade
+    pub fn planned_upgrades(&self) -> Vec<(String, String, String)> {
+        let cache = self.cache();
+        self.planned_changes()
+            .unwrap_or_default()
+            .iter()
+            .filter(|c| c.action == ChangeAction::Upgrade)
+            .filter_map(|c| {
+                let installed = cache.installed_version(c.package)?;
+                let candidate = cache.candidate_version(c.package)?;
+                let name = cache.display_name(cache.fullname_of(c.package)?).to_string();
+                Some((name, installed, candidate))
+            })
+            .collect()
+    }
+
     /// Get plan errors (only valid in Planned state)
     pub fn plan_errors(&self) -> Option<&[String]> {
         match self {
@@ -814,6 +1149,51 @@ impl ManagerState {
         }
     }
 
+    pub fn effective_sort(&self) -> SortBy {
+        match self {
+            ManagerState::Clean(m) => m.effective_sort(),
+            ManagerState::Dirty(m) => m.effective_sort(),
+            ManagerState::Planned(m) => m.effective_sort(),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn search_error(&self) -> Option<&str> {
+        match self {
+            ManagerState::Clean(m) => m.search_error(),
+            ManagerState::Dirty(m) => m.search_error(),
+            ManagerState::Planned(m) => m.search_error(),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn search_paths(&mut self) {
+        match self {
+            ManagerState::Clean(m) => m.search_paths(),
+            ManagerState::Dirty(m) => m.search_paths(),
+            ManagerState::Planned(m) => m.search_paths(),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn set_path_results(&mut self, owners: HashMap<String, f64>) {
+        match self {
+            ManagerState::Clean(m) => m.set_path_results(owners),
+            ManagerState::Dirty(m) => m.set_path_results(owners),
+            ManagerState::Planned(m) => m.set_path_results(owners),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn search_terms(&self) -> &[String] {
+        match self {
+            ManagerState::Clean(m) => m.search_terms(),
+            ManagerState::Dirty(m) => m.search_terms(),
+            ManagerState::Planned(m) => m.search_terms(),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
     pub fn get_dependencies(&self, name: &str) -> Vec<(String, String)> {
         match self {
             ManagerState::Clean(m) => m.get_dependencies(name),
@@ -832,15 +1212,6 @@ impl ManagerState {
         }
     }
 
-    pub fn fetch_changelog(&self, name: &str) -> Result<Vec<String>, String> {
-        match self {
-            ManagerState::Clean(m) => m.fetch_changelog(name),
-            ManagerState::Dirty(m) => m.fetch_changelog(name),
-            ManagerState::Planned(m) => m.fetch_changelog(name),
-            ManagerState::Transitioning => panic!("Transitioning state observed"),
-        }
-    }
-
     // Mutating methods that work in any state
 
     pub fn apply_filter(&mut self, filter: FilterCategory) {
@@ -875,15 +1246,6 @@ impl ManagerState {
         }
     }
 
-    pub fn ensure_search_index(&mut self) -> Result<std::time::Duration> {
-        match self {
-            ManagerState::Clean(m) => m.ensure_search_index(),
-            ManagerState::Dirty(m) => m.ensure_search_index(),
-            ManagerState::Planned(m) => m.ensure_search_index(),
-            ManagerState::Transitioning => panic!("Transitioning state observed"),
-        }
-    }
-
     pub fn set_search_query(&mut self, query: &str) -> Result<()> {
         match self {
             ManagerState::Clean(m) => m.set_search_query(query),
@@ -947,39 +1309,206 @@ impl ManagerState {
         }
     }
 
+    pub fn apply_custom_filter(&mut self, name: &str) {
+        match self {
+            ManagerState::Clean(m) => m.apply_custom_filter(name),
+            ManagerState::Dirty(m) => m.apply_custom_filter(name),
+            ManagerState::Planned(m) => m.apply_custom_filter(name),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn set_custom_filters(&mut self, filters: Vec<(String, Filter)>) {
+        match self {
+            ManagerState::Clean(m) => m.set_custom_filters(filters),
+            ManagerState::Dirty(m) => m.set_custom_filters(filters),
+            ManagerState::Planned(m) => m.set_custom_filters(filters),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    /// The custom filter in use instead of the filter category, if any
+    pub fn custom_filter(&self) -> Option<&str> {
+        self.shared().custom_filter.as_deref()
+    }
+
+    /// Names of the custom filters with their package counts
+    pub fn custom_filter_counts(&self) -> Vec<(&str, usize)> {
+        let shared = self.shared();
+        shared.custom_filters.iter()
+            .map(|(name, _)| (name.as_str(), shared.custom_counts.get(name).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    pub fn set_repo_filter(&mut self, repo: Option<RepoFilter>) {
+        match self {
+            ManagerState::Clean(m) => m.set_repo_filter(repo),
+            ManagerState::Dirty(m) => m.set_repo_filter(repo),
+            ManagerState::Planned(m) => m.set_repo_filter(repo),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn repo_filter(&self) -> Option<&RepoFilter> {
+        self.shared().repo_filter.as_ref()
+    }
+
+    pub fn set_section_filter(&mut self, section: Option<String>) {
+        match self {
+            ManagerState::Clean(m) => m.set_section_filter(section),
+            ManagerState::Dirty(m) => m.set_section_filter(section),
+            ManagerState::Planned(m) => m.set_section_filter(section),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    pub fn section_filter(&self) -> Option<&str> {
+        self.shared().section_filter.as_deref()
+    }
+
+    /// Sections with their package counts, by name
+    pub fn section_counts(&self) -> &BTreeMap<String, usize> {
+        &self.shared().section_counts
+    }
+
+    /// Repository tree nodes with their package counts, in tree order (each
+    /// node followed by its children, by name)
+    pub fn repo_counts(&self) -> &BTreeMap<RepoFilter, usize> {
+        &self.shared().repo_counts
+    }
+
     /// Get the count for a filter category
     pub fn filter_count(&self, filter: FilterCategory) -> usize {
-        let (upgradable, installed, total, user_marks) = match self {
-            ManagerState::Clean(m) => (m.shared.upgradable_count, m.shared.installed_count, m.shared.total_count, m.shared.user_intent.len()),
-            ManagerState::Dirty(m) => (m.shared.upgradable_count, m.shared.installed_count, m.shared.total_count, m.shared.user_intent.len()),
-            ManagerState::Planned(m) => (m.shared.upgradable_count, m.shared.installed_count, m.shared.total_count, m.shared.user_intent.len()),
-            ManagerState::Transitioning => panic!("Transitioning state observed"),
-        };
+        let shared = self.shared();
 
         match filter {
-            FilterCategory::Upgradable => upgradable,
-            FilterCategory::MarkedChanges => user_marks,
-            FilterCategory::Installed => installed,
-            FilterCategory::NotInstalled => total - installed,
-            FilterCategory::All => total,
+            FilterCategory::Upgradable => shared.upgradable_count,
+            FilterCategory::MarkedChanges => shared.user_intent.len(),
+            FilterCategory::Installed => shared.installed_count,
+            FilterCategory::NotInstalled => shared.total_count - shared.installed_count,
+            FilterCategory::ResidualConfig => shared.residual_count,
+            FilterCategory::Held => shared.held.len(),
+            FilterCategory::All => shared.total_count,
         }
     }
 
-    /// Mark all upgradable packages in the entire cache (not just filtered view)
+    /// Mark all upgradable packages in the entire cache (not just filtered view).
+    /// Held packages are kept back, like `apt upgrade` does.
     pub fn mark_all_upgradable(&mut self) {
-        let upgradable_ids: Vec<PackageId> = {
-            let cache = self.cache();
-            cache.packages(&PackageSort::default().upgradable())
-                .map(|pkg| pkg.fullname(false))
-                .filter_map(|name| cache.get_id(&name))
-                .collect()
-        };
+        let mut upgradable_ids: Vec<PackageId> = Vec::new();
+        {
+            let shared = self.shared();
+            shared.cache.for_each_package(true, &mut |pkg| {
+                if let Some(id) = shared.cache.get_id(&pkg.fullname())
+                    && !shared.held.contains(&id) {
+                        upgradable_ids.push(id);
+                }
+            });
+        }
 
         for id in upgradable_ids {
             self.mark_install(id);
         }
     }
 
+    /// Mark every package with leftover configuration files (dpkg "rc" state) for purge.
+    /// Returns the number of packages marked.
+    pub fn mark_all_residual_purge(&mut self) -> usize {
+        let mut residual_ids: Vec<PackageId> = Vec::new();
+        {
+            let cache = self.cache();
+            cache.for_each_package(false, &mut |pkg| {
+                if pkg.is_residual_config()
+                    && let Some(id) = cache.get_id(&pkg.fullname()) {
+                        residual_ids.push(id);
+                }
+            });
+        }
+
+        let count = residual_ids.len();
+        for id in residual_ids {
+            self.set_intent(id, UserIntent::Purge);
+        }
+        count
+    }
+
+    /// Check if a package is held in the dpkg selections
+    pub fn is_held(&self, id: PackageId) -> bool {
+        self.shared().held.contains(&id)
+    }
+
+    /// Set or release a dpkg hold on a package. An existing plan is
+    /// discarded, since the hold changes what the resolver may touch.
+    pub fn set_hold(&mut self, id: PackageId, hold: bool) -> Result<(), String> {
+        let result = match self {
+            ManagerState::Clean(m) => m.set_hold(id, hold),
+            ManagerState::Dirty(m) => m.set_hold(id, hold),
+            ManagerState::Planned(m) => m.set_hold(id, hold),
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        };
+
+        if self.is_planned() {
+            *self = match std::mem::take(self) {
+                ManagerState::Planned(m) => ManagerState::Dirty(m.modify()),
+                other => other,
+            };
+        }
+        result
+    }
+
+    /// Get the version the user picked for a package, if any
+    pub fn chosen_version(&self, id: PackageId) -> Option<&str> {
+        self.shared().version_choice.get(&id).map(String::as_str)
+    }
+
+    /// Every user mark with its picked version, to restore later
+    pub fn user_marks(&self) -> Vec<PriorMark> {
+        let shared = self.shared();
+        shared.user_intent.iter()
+            .map(|(&package, &intent)| PriorMark {
+                package,
+                intent,
+                version: shared.version_choice.get(&package).cloned(),
+            })
+            .collect()
+    }
+
+    /// The marks in `before` that have changed since, plus `package`'s
+    /// (unmarked unless `before` lists it)
+    pub fn changed_marks(&self, before: &[PriorMark], package: PackageId) -> Vec<PriorMark> {
+        let mut marks = before.to_vec();
+        if !marks.iter().any(|mark| mark.package == package) {
+            marks.push(PriorMark { package, intent: UserIntent::Default, version: None });
+        }
+        marks.retain(|mark| {
+            self.user_intent(mark.package) != mark.intent
+                || self.chosen_version(mark.package) != mark.version.as_deref()
+        });
+        marks
+    }
+
+    /// Put back marks recorded by `user_marks`, intent and picked version
+    pub fn restore_marks(&mut self, marks: &[PriorMark]) {
+        for mark in marks {
+            self.set_intent(mark.package, mark.intent);
+            let version_choice = &mut self.shared_mut().version_choice;
+            match &mark.version {
+                Some(version) => { version_choice.insert(mark.package, version.clone()); }
+                None => { version_choice.remove(&mark.package); }
+            }
+        }
+    }
+
+    /// List every available version of a package with origin and pin priority
+    pub fn available_versions(&self, id: PackageId) -> Vec<AvailableVersion> {
+        self.cache().available_versions(id)
+    }
+
+    /// Number of packages with leftover configuration files
+    pub fn residual_count(&self) -> usize {
+        self.shared().residual_count
+    }
+
     // State-specific shared access
 
     pub fn sort_settings(&self) -> &SortSettings {
@@ -991,8 +1520,27 @@ impl ManagerState {
         }
     }
 
-    /// Get reference to the APT cache for ID lookups
-    pub fn cache(&self) -> &AptCache {
+    /// Shared state of whichever typestate is current
+    fn shared(&self) -> &SharedState<B> {
+        match self {
+            ManagerState::Clean(m) => &m.shared,
+            ManagerState::Dirty(m) => &m.shared,
+            ManagerState::Planned(m) => &m.shared,
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    fn shared_mut(&mut self) -> &mut SharedState<B> {
+        match self {
+            ManagerState::Clean(m) => &mut m.shared,
+            ManagerState::Dirty(m) => &mut m.shared,
+            ManagerState::Planned(m) => &mut m.shared,
+            ManagerState::Transitioning => panic!("Transitioning state observed"),
+        }
+    }
+
+    /// Get reference to the backend (the APT cache) for ID lookups
+    pub fn cache(&self) -> &B {
         match self {
             ManagerState::Clean(m) => &m.shared.cache,
             ManagerState::Dirty(m) => &m.shared.cache,
@@ -1007,7 +1555,7 @@ impl ManagerState {
 // ============================================================================
 
 /// Helper to take ownership and perform state transition
-impl ManagerState {
+impl<B: Backend> ManagerState<B> {
     /// Mark a package for install, handling state transitions
     pub fn mark_install(&mut self, id: PackageId) {
         *self = match std::mem::take(self) {
@@ -1028,6 +1576,22 @@ impl ManagerState {
         };
     }
 
+    /// Set the user intent for a package, handling state transitions.
+    /// `UserIntent::Default` behaves like `unmark()`.
+    pub fn set_intent(&mut self, id: PackageId, intent: UserIntent) {
+        if intent == UserIntent::Default {
+            self.unmark(id);
+            return;
+        }
+
+        *self = match std::mem::take(self) {
+            ManagerState::Clean(m) => ManagerState::Dirty(m.set_intent(id, intent)),
+            ManagerState::Dirty(m) => ManagerState::Dirty(m.set_intent(id, intent)),
+            ManagerState::Planned(m) => ManagerState::Dirty(m.modify().set_intent(id, intent)),
+            ManagerState::Transitioning => panic!("ManagerState::Transitioning should not be observed"),
+        };
+    }
+
     /// Unmark a package from user_intent (low-level, doesn't handle cascade).
     /// For proper toggle behavior with cascade, use `toggle()` instead.
     pub fn unmark(&mut self, id: PackageId) {
@@ -1064,12 +1628,46 @@ impl ManagerState {
             self.toggle_unmark(id)
         } else {
             // MARK flow
-            self.toggle_mark_impl(id)
+            self.toggle_mark_impl(id, UserIntent::Install)
         }
     }
 
-    /// Internal: handle marking a package
-    fn toggle_mark_impl(&mut self, id: PackageId) -> ToggleResult {
+    /// Toggle a package's removal mark (`purge` also deletes its configuration).
+    ///
+    /// - If the package already has this removal intent: unmarks with cascade
+    /// - Otherwise: marks it for removal + computes plan, so reverse
+    ///   dependencies that must go with it are reported as `additional`
+    pub fn toggle_remove(&mut self, id: PackageId, purge: bool) -> ToggleResult {
+        self.compute_plan();
+        self.rebuild_list();
+
+        let intent = if purge { UserIntent::Purge } else { UserIntent::Remove };
+        if self.user_intent(id) == intent {
+            self.toggle_unmark(id)
+        } else {
+            self.toggle_mark_impl(id, intent)
+        }
+    }
+
+    /// Mark a specific version of a package for install, upgrade or downgrade.
+    /// Picking the installed version drops any pending change instead.
+    pub fn mark_version(&mut self, id: PackageId, version: &str) -> ToggleResult {
+        let installed = self.cache().installed_version(id);
+
+        if installed.as_deref() == Some(version) {
+            self.shared_mut().version_choice.remove(&id);
+            if self.is_user_marked(id) {
+                return self.toggle_unmark(id);
+            }
+            return ToggleResult::Unmarked { package: id, also_unmarked: Vec::new() };
+        }
+
+        self.shared_mut().version_choice.insert(id, version.to_string());
+        self.toggle_mark_impl(id, UserIntent::Install)
+    }
+
+    /// Internal: handle marking a package with the given intent
+    fn toggle_mark_impl(&mut self, id: PackageId, intent: UserIntent) -> ToggleResult {
         // Get marked packages before
         let marked_before: HashSet<PackageId> = self.list().iter()
             .filter(|p| p.status.is_marked())
@@ -1077,7 +1675,7 @@ impl ManagerState {
             .collect();
 
         // Mark and compute plan
-        self.mark_install(id);
+        self.set_intent(id, intent);
         self.compute_plan();
         self.rebuild_list();
 
@@ -1152,55 +1750,24 @@ impl ManagerState {
             Some(n) => n,
             None => return Vec::new(),
         };
-        let target_base = target_name.split(':').next().unwrap_or(target_name);
-
-        let mut result = Vec::new();
-
-        // Check each user_intent package
-        let intent_ids: Vec<PackageId> = self.user_intent_ids().copied().collect();
-
-        for intent_id in intent_ids {
-            if let Some(intent_name) = cache.fullname_of(intent_id)
-                && self.package_depends_on(intent_name, target_base) {
-                    result.push(intent_id);
-            }
-        }
 
-        result
-    }
-
-    /// Check if package A (transitively) depends on package B
-    fn package_depends_on(&self, pkg_name: &str, target_base: &str) -> bool {
-        let cache = self.cache();
-        let mut visited = HashSet::new();
-        let mut to_check = vec![pkg_name.to_string()];
-
-        while let Some(current) = to_check.pop() {
-            if visited.contains(&current) {
-                continue;
-            }
-            visited.insert(current.clone());
-
-            let deps = cache.get_dependencies(&current);
-            for (dep_type, dep_name) in deps {
-                if dep_type != "Depends" && dep_type != "PreDepends" {
-                    continue;
-                }
-
-                if dep_name == target_base {
-                    return true;
-                }
-
-                // Add to check list for transitive deps
-                if let Some(&dep_id) = cache.fullname_to_id.get(&dep_name)
-                    .or_else(|| cache.fullname_to_id.get(&format!("{dep_name}:amd64")))
-                    && let Some(fullname) = cache.fullname_of(dep_id) {
-                        to_check.push(fullname.to_string());
-                }
-            }
-        }
+        // Only walk through packages the plan installs or upgrades: anything
+        // else is already satisfied and can't be what pulled the target in
+        let planned: Option<HashSet<&str>> = self.planned_changes().map(|changes| {
+            changes.iter()
+                .filter(|c| !matches!(c.action, ChangeAction::Remove | ChangeAction::Purge))
+                .filter_map(|c| cache.fullname_of(c.package))
+                .collect()
+        });
+        let follow = |name: &str| planned.as_ref().is_none_or(|p| p.contains(name));
 
-        false
+        self.user_intent_ids()
+            .copied()
+            .filter(|&intent_id| {
+                cache.fullname_of(intent_id)
+                    .is_some_and(|intent_name| deps::depends_on(cache, intent_name, target_name, follow))
+            })
+            .collect()
     }
 
     /// Get iterator over user_intent PackageIds
@@ -1252,49 +1819,16 @@ impl ManagerState {
         Ok(())
     }
 
-    /// Commit planned changes with caller-provided progress implementations
-    pub fn commit_with_progress(
-        &mut self,
-        acquire_progress: &mut rust_apt::progress::AcquireProgress,
-        install_progress: &mut rust_apt::progress::InstallProgress,
-    ) -> Result<()> {
-        *self = match std::mem::take(self) {
-            ManagerState::Clean(m) => ManagerState::Clean(m),
-            ManagerState::Dirty(m) => {
-                let planned = m.plan();
-                let clean = planned.commit_with_progress(acquire_progress, install_progress)?;
-                ManagerState::Clean(clean)
-            }
-            ManagerState::Planned(m) => {
-                let clean = m.commit_with_progress(acquire_progress, install_progress)?;
-                ManagerState::Clean(clean)
-            }
-            ManagerState::Transitioning => panic!("ManagerState::Transitioning should not be observed"),
-        };
-        Ok(())
-    }
-
-    /// Run `apt update` with caller-provided progress
-    pub fn update_with_progress(
-        &mut self,
-        acquire_progress: &mut rust_apt::progress::AcquireProgress,
-    ) -> Result<(), String> {
-        match self {
-            ManagerState::Clean(m) => m.update_with_progress(acquire_progress),
-            ManagerState::Dirty(m) => m.update_with_progress(acquire_progress),
-            ManagerState::Planned(m) => m.update_with_progress(acquire_progress),
-            ManagerState::Transitioning => panic!("Transitioning state observed"),
-        }
-    }
-
     /// Build a MarkPreview from the current Planned state's changes.
     /// Call this after marking a package and computing the plan.
     /// `previously_planned` contains PackageIds that were already in the plan
     /// before this mark — they are excluded from the "additional" lists.
+    /// `prior_marks` are the `user_marks()` from before the mark.
     pub fn build_mark_preview(
         &self,
         marked_pkg_id: PackageId,
         previously_planned: &HashSet<PackageId>,
+        prior_marks: &[PriorMark],
     ) -> Option<MarkPreview> {
         let changes = self.planned_changes()?;
         let cache = self.cache();
@@ -1308,12 +1842,14 @@ impl ManagerState {
         let mut additional_removes = Vec::new();
         let mut download_size = 0u64;
         let mut is_upgrade = false;
+        let mut is_downgrade = false;
 
         for change in changes {
             // Check if the marked package is an upgrade vs install
             if change.package == marked_pkg_id {
                 download_size += change.download_size;
                 is_upgrade = change.action == ChangeAction::Upgrade;
+                is_downgrade = change.action == ChangeAction::Downgrade;
                 continue;
             }
 
@@ -1332,16 +1868,19 @@ impl ManagerState {
             match change.action {
                 ChangeAction::Install => additional_installs.push(name),
                 ChangeAction::Upgrade => additional_upgrades.push(name),
-                ChangeAction::Remove => additional_removes.push(name),
+                ChangeAction::Remove | ChangeAction::Purge => additional_removes.push(name),
                 ChangeAction::Downgrade => additional_upgrades.push(name),
             }
         }
 
         Some(MarkPreview {
             package_name: marked_pkg_name,
+            intent: self.user_intent(marked_pkg_id),
             is_upgrade,
+            is_downgrade,
             is_marking: true,
             was_user_marked: false, // N/A for mark operations (package wasn't marked before)
+            restore: self.changed_marks(prior_marks, marked_pkg_id),
             additional_installs,
             additional_upgrades,
             additional_removes,
@@ -1367,13 +1906,15 @@ pub fn check_apt_lock() -> Option<String> {
         "/var/lib/apt/lists/lock",
     ];
 
-    for path in &lock_paths {
-        if let Ok(file) = File::open(path) {
+    for path in lock_paths {
+        let path = root::path(path);
+        if let Ok(file) = File::open(&path) {
             let fd = file.as_raw_fd();
             let ret = unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) };
             if ret != 0 {
                 return Some(format!(
-                    "Another package manager is running ({path}). Close it and try again."
+                    "Another package manager is running ({}). Close it and try again.",
+                    path.display()
                 ));
             }
             unsafe { libc::flock(fd, libc::LOCK_UN) };
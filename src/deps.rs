@@ -0,0 +1,506 @@
+//! Dependency resolution for cascade unmarking
+//!
+//! Answers "does package A (transitively) need package B?" the way APT would:
+//! virtual packages resolve to their providers, versioned dependencies only match
+//! versions (or versioned Provides) that satisfy them, and or-groups match any
+//! alternative. The logic works on a `DependencySource` so it can run against
+//! the APT cache as well as a synthetic package index.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// Comparison operator of a versioned dependency, e.g. the `>=` in `foo (>= 1.0)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    Lt, // <<
+    Le, // <=
+    Eq, // =
+    Ge, // >=
+    Gt, // >>
+}
+
+impl VersionOp {
+    /// Parse an operator as written in control files (or reported by APT)
+    pub fn parse(op: &str) -> Option<Self> {
+        match op {
+            "<<" => Some(Self::Lt),
+            "<=" | "<" => Some(Self::Le), // "<" is the obsolete spelling of "<="
+            "=" => Some(Self::Eq),
+            ">=" | ">" => Some(Self::Ge), // ">" is the obsolete spelling of ">="
+            ">>" => Some(Self::Gt),
+            _ => None,
+        }
+    }
+
+    /// Check whether a version satisfies `<op> wanted`, given how it
+    /// compares to `wanted`
+    pub fn holds(self, ord: Ordering) -> bool {
+        match self {
+            Self::Lt => ord == Ordering::Less,
+            Self::Le => ord != Ordering::Greater,
+            Self::Eq => ord == Ordering::Equal,
+            Self::Ge => ord != Ordering::Less,
+            Self::Gt => ord == Ordering::Greater,
+        }
+    }
+}
+
+/// One alternative of a dependency, e.g. `foo:any (>= 1.0)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepAlternative {
+    pub name: String,
+    pub arch: Option<String>, // Arch qualifier (`any`, `i386`, ...); None = same arch as the depender
+    pub constraint: Option<(VersionOp, String)>,
+}
+
+/// A dependency: satisfied when any alternative is (`a | b | c`)
+pub type OrGroup = Vec<DepAlternative>;
+
+/// A package that can satisfy a dependency on some name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Satisfier {
+    pub fullname: String, // Full name of the real package (e.g., "foo:amd64")
+    pub arch: String,
+    /// Version of the real package, or the provided version for a Provides.
+    /// None for an unversioned Provides, which never satisfies a versioned dependency.
+    pub version: Option<String>,
+}
+
+/// Where dependency information comes from (the APT cache, or a test index)
+pub trait DependencySource {
+    /// Architecture used when a package name carries no arch suffix
+    fn native_arch(&self) -> &str;
+
+    /// Depends and Pre-Depends of the version of `fullname` that would be installed
+    fn depends(&self, fullname: &str) -> Vec<OrGroup>;
+
+    /// Real packages named `name` (any architecture) and packages providing `name`
+    fn satisfiers(&self, name: &str) -> Vec<Satisfier>;
+
+    /// Order two versions the way the package system does
+    fn compare_versions(&self, a: &str, b: &str) -> Ordering;
+}
+
+/// Architecture part of a full package name, falling back to the native arch
+fn arch_of<'a>(source: &'a impl DependencySource, fullname: &'a str) -> &'a str {
+    fullname.rsplit_once(':').map_or(source.native_arch(), |(_, arch)| arch)
+}
+
+/// Packages that satisfy one dependency alternative of a package with arch `depender_arch`
+pub fn resolve_alternative(
+    source: &impl DependencySource,
+    alt: &DepAlternative,
+    depender_arch: &str,
+) -> Vec<String> {
+    let wanted_arch = alt.arch.as_deref().unwrap_or(depender_arch);
+
+    source.satisfiers(&alt.name)
+        .into_iter()
+        .filter(|s| wanted_arch == "any" || s.arch == wanted_arch || s.arch == "all")
+        .filter(|s| match (&alt.constraint, &s.version) {
+            (None, _) => true,
+            (Some((op, wanted)), Some(version)) => op.holds(source.compare_versions(version, wanted)),
+            (Some(_), None) => false,
+        })
+        .map(|s| s.fullname)
+        .collect()
+}
+
+/// Check if `from` (transitively) depends on `target`.
+///
+/// Only packages accepted by `follow` are descended into; callers pass the set
+/// of packages that are part of the current plan so that popular virtual
+/// packages don't pull in the whole archive.
+pub fn depends_on(
+    source: &impl DependencySource,
+    from: &str,
+    target: &str,
+    follow: impl Fn(&str) -> bool,
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut to_check = vec![from.to_string()];
+
+    while let Some(current) = to_check.pop() {
+        if !visited.insert(current.clone()) {
+            continue;
+        }
+
+        let arch = arch_of(source, &current).to_string();
+        for group in source.depends(&current) {
+            for alt in &group {
+                for satisfier in resolve_alternative(source, alt, &arch) {
+                    if satisfier == target {
+                        return true;
+                    }
+                    if follow(&satisfier) && !visited.contains(&satisfier) {
+                        to_check.push(satisfier);
+                    }
+                }
+            }
+        }
+    }
+
+    false
+}
+
+// ============================================================================
+// Control file relationship fields
+// ============================================================================
+
+/// Parse a relationship field as written in control files,
+/// e.g. `bar (>= 2) | baz, qux:any`
+pub fn parse_relations(field: &str) -> Result<Vec<OrGroup>, String> {
+    field.split(',')
+        .map(str::trim)
+        .filter(|group| !group.is_empty())
+        .map(|group| group.split('|').map(parse_alternative).collect())
+        .collect()
+}
+
+/// Parse one alternative, e.g. `foo:any (>= 1.0)`
+pub fn parse_alternative(alt: &str) -> Result<DepAlternative, String> {
+    let alt = alt.trim();
+    let (name_part, constraint) = match alt.split_once('(') {
+        Some((name, rest)) => {
+            let rest = rest.trim().strip_suffix(')')
+                .ok_or_else(|| format!("Unclosed version in `{alt}`"))?
+                .trim();
+            let (op, version) = rest.split_at(rest.find(|c| !"<>=".contains(c)).unwrap_or(rest.len()));
+            let op = VersionOp::parse(op).ok_or_else(|| format!("Unknown operator `{op}` in `{alt}`"))?;
+            (name.trim(), Some((op, version.trim().to_string())))
+        }
+        None => (alt, None),
+    };
+    if name_part.is_empty() {
+        return Err(format!("Missing package name in `{alt}`"));
+    }
+
+    let (name, arch) = match name_part.split_once(':') {
+        Some((name, arch)) => (name, Some(arch.to_string())),
+        None => (name_part, None),
+    };
+    Ok(DepAlternative { name: name.to_string(), arch, constraint })
+}
+
+// ============================================================================
+// Debian version comparison
+// ============================================================================
+
+/// Compare two Debian version strings (`[epoch:]upstream[-revision]`) the
+/// way dpkg does. `AptCache` compares with libapt instead; this serves the
+/// fake backend and code that runs without a cache.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (epoch_a, upstream_a, revision_a) = split_version(a);
+    let (epoch_b, upstream_b, revision_b) = split_version(b);
+
+    epoch_a.cmp(&epoch_b)
+        .then_with(|| compare_part(upstream_a, upstream_b))
+        .then_with(|| compare_part(revision_a, revision_b))
+}
+
+fn split_version(version: &str) -> (u64, &str, &str) {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+        None => (0, version),
+    };
+    match rest.rsplit_once('-') {
+        Some((upstream, revision)) => (epoch, upstream, revision),
+        None => (epoch, rest, ""),
+    }
+}
+
+/// Sort weight of a character in the non-digit parts: `~` sorts before
+/// everything (even the end of the string), letters before other symbols
+fn char_order(c: Option<char>) -> i32 {
+    match c {
+        None => 0,
+        Some('~') => -1,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+fn compare_part(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        // Non-digit prefix, compared character by character
+        loop {
+            let ca = a.peek().copied().filter(|c| !c.is_ascii_digit());
+            let cb = b.peek().copied().filter(|c| !c.is_ascii_digit());
+            if ca.is_none() && cb.is_none() {
+                break;
+            }
+            match char_order(ca).cmp(&char_order(cb)) {
+                Ordering::Equal => {
+                    a.next();
+                    b.next();
+                }
+                other => return other,
+            }
+        }
+
+        // Digit run, compared numerically
+        let mut num_a = String::new();
+        while let Some(c) = a.next_if(char::is_ascii_digit) {
+            num_a.push(c);
+        }
+        let mut num_b = String::new();
+        while let Some(c) = b.next_if(char::is_ascii_digit) {
+            num_b.push(c);
+        }
+        let num_a = num_a.trim_start_matches('0');
+        let num_b = num_b.trim_start_matches('0');
+        match num_a.len().cmp(&num_b.len()).then_with(|| num_a.cmp(num_b)) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+
+        if a.peek().is_none() && b.peek().is_none() {
+            return Ordering::Equal;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A package index written like a tiny Packages file
+    struct SyntheticIndex {
+        native_arch: String,
+        packages: HashMap<String, SyntheticPackage>,
+    }
+
+    struct SyntheticPackage {
+        name: String,
+        arch: String,
+        version: String,
+        depends: Vec<OrGroup>,
+        provides: Vec<(String, Option<String>)>,
+    }
+
+    impl SyntheticIndex {
+        fn new(native_arch: &str) -> Self {
+            Self { native_arch: native_arch.to_string(), packages: HashMap::new() }
+        }
+
+        /// Add a package from control-file style fields, e.g.
+        /// `add("foo:amd64", "1.0", "bar (>= 2) | baz, qux:any", "virt (= 1.0)")`.
+        /// Names without an arch suffix get the native arch.
+        fn add(mut self, fullname: &str, version: &str, depends: &str, provides: &str) -> Self {
+            let native_arch = self.native_arch.clone();
+            let (name, arch) = fullname.split_once(':').unwrap_or((fullname, &native_arch));
+            let provides = split_list(provides)
+                .map(|p| {
+                    let alt = parse_alternative(p).unwrap();
+                    (alt.name, alt.constraint.map(|(_, v)| v))
+                })
+                .collect();
+            let depends = parse_relations(depends).unwrap();
+            self.packages.insert(fullname.to_string(), SyntheticPackage {
+                name: name.to_string(),
+                arch: arch.to_string(),
+                version: version.to_string(),
+                depends,
+                provides,
+            });
+            self
+        }
+    }
+
+    fn split_list(list: &str) -> impl Iterator<Item = &str> {
+        list.split(',').map(str::trim).filter(|s| !s.is_empty())
+    }
+
+    impl DependencySource for SyntheticIndex {
+        fn native_arch(&self) -> &str {
+            &self.native_arch
+        }
+
+        fn depends(&self, fullname: &str) -> Vec<OrGroup> {
+            self.packages.get(fullname).map(|p| p.depends.clone()).unwrap_or_default()
+        }
+
+        fn compare_versions(&self, a: &str, b: &str) -> Ordering {
+            compare_versions(a, b)
+        }
+
+        fn satisfiers(&self, name: &str) -> Vec<Satisfier> {
+            let mut result = Vec::new();
+            for (fullname, pkg) in &self.packages {
+                if pkg.name == name {
+                    result.push(Satisfier {
+                        fullname: fullname.clone(),
+                        arch: pkg.arch.clone(),
+                        version: Some(pkg.version.clone()),
+                    });
+                }
+                for (provided, version) in &pkg.provides {
+                    if provided == name {
+                        result.push(Satisfier {
+                            fullname: fullname.clone(),
+                            arch: pkg.arch.clone(),
+                            version: version.clone(),
+                        });
+                    }
+                }
+            }
+            result
+        }
+    }
+
+    fn follow_all(_: &str) -> bool {
+        true
+    }
+
+    #[test]
+    fn direct_dependency() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "libfoo", "")
+            .add("libfoo:amd64", "2.0", "", "");
+
+        assert!(depends_on(&index, "app:amd64", "libfoo:amd64", follow_all));
+        assert!(!depends_on(&index, "libfoo:amd64", "app:amd64", follow_all));
+    }
+
+    #[test]
+    fn transitive_dependency() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "libfoo", "")
+            .add("libfoo:amd64", "2.0", "libbar", "")
+            .add("libbar:amd64", "3.0", "", "");
+
+        assert!(depends_on(&index, "app:amd64", "libbar:amd64", follow_all));
+        // Not descending into libfoo hides libbar
+        assert!(!depends_on(&index, "app:amd64", "libbar:amd64", |p| p != "libfoo:amd64"));
+    }
+
+    #[test]
+    fn dependency_cycle_terminates() {
+        let index = SyntheticIndex::new("amd64")
+            .add("a:amd64", "1", "b", "")
+            .add("b:amd64", "1", "a", "");
+
+        assert!(!depends_on(&index, "a:amd64", "c:amd64", follow_all));
+    }
+
+    #[test]
+    fn virtual_package_resolves_to_provider() {
+        // The nvidia case from TODO.md: the dependency names a virtual package
+        let index = SyntheticIndex::new("amd64")
+            .add("libnvidia-extra-590:amd64", "590.48.01", "nvidia-kernel-common-590-590.48.01", "")
+            .add("nvidia-kernel-common-590:amd64", "590.48.01", "", "nvidia-kernel-common-590-590.48.01");
+
+        assert!(depends_on(&index, "libnvidia-extra-590:amd64", "nvidia-kernel-common-590:amd64", follow_all));
+    }
+
+    #[test]
+    fn transitive_through_provider() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "virt", "")
+            .add("impl:amd64", "1.0", "libreal", "virt")
+            .add("libreal:amd64", "1.0", "", "");
+
+        assert!(depends_on(&index, "app:amd64", "libreal:amd64", follow_all));
+    }
+
+    #[test]
+    fn versioned_provides() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "virt (>= 2.0)", "")
+            .add("old:amd64", "1.0", "", "virt (= 1.5)")
+            .add("new:amd64", "1.0", "", "virt (= 2.1)")
+            .add("unversioned:amd64", "1.0", "", "virt");
+
+        assert!(depends_on(&index, "app:amd64", "new:amd64", follow_all));
+        assert!(!depends_on(&index, "app:amd64", "old:amd64", follow_all));
+        // An unversioned Provides never satisfies a versioned dependency
+        assert!(!depends_on(&index, "app:amd64", "unversioned:amd64", follow_all));
+    }
+
+    #[test]
+    fn versioned_dependency_on_real_package() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "libfoo (>= 2.0~rc1)", "")
+            .add("libfoo:amd64", "2.0~beta1", "", "");
+
+        assert!(!depends_on(&index, "app:amd64", "libfoo:amd64", follow_all));
+
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "libfoo (>= 2.0~rc1)", "")
+            .add("libfoo:amd64", "2.0", "", "");
+
+        assert!(depends_on(&index, "app:amd64", "libfoo:amd64", follow_all));
+    }
+
+    #[test]
+    fn or_group_matches_any_alternative() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "mta-a | mta-b | mail-transport-agent", "")
+            .add("mta-b:amd64", "1.0", "", "")
+            .add("postfix:amd64", "3.0", "", "mail-transport-agent");
+
+        assert!(depends_on(&index, "app:amd64", "mta-b:amd64", follow_all));
+        assert!(depends_on(&index, "app:amd64", "postfix:amd64", follow_all));
+    }
+
+    #[test]
+    fn architecture_qualifiers() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:amd64", "1.0", "libfoo, tool:any, lib32bar:i386", "")
+            .add("libfoo:amd64", "1.0", "", "")
+            .add("libfoo:i386", "1.0", "", "")
+            .add("tool:i386", "1.0", "", "")
+            .add("lib32bar:i386", "1.0", "", "")
+            .add("lib32bar:amd64", "1.0", "", "");
+
+        // Unqualified dependencies stay on the depender's architecture
+        assert!(depends_on(&index, "app:amd64", "libfoo:amd64", follow_all));
+        assert!(!depends_on(&index, "app:amd64", "libfoo:i386", follow_all));
+        // :any accepts any architecture, explicit qualifiers only that one
+        assert!(depends_on(&index, "app:amd64", "tool:i386", follow_all));
+        assert!(depends_on(&index, "app:amd64", "lib32bar:i386", follow_all));
+        assert!(!depends_on(&index, "app:amd64", "lib32bar:amd64", follow_all));
+    }
+
+    #[test]
+    fn arch_all_satisfies_any_architecture() {
+        let index = SyntheticIndex::new("amd64")
+            .add("app:i386", "1.0", "data", "")
+            .add("data:all", "1.0", "", "");
+
+        assert!(depends_on(&index, "app:i386", "data:all", follow_all));
+    }
+
+    #[test]
+    fn unqualified_name_uses_native_arch() {
+        let index = SyntheticIndex::new("arm64")
+            .add("app", "1.0", "libfoo", "")
+            .add("libfoo:arm64", "1.0", "", "")
+            .add("libfoo:amd64", "1.0", "", "");
+
+        assert!(depends_on(&index, "app", "libfoo:arm64", follow_all));
+        assert!(!depends_on(&index, "app", "libfoo:amd64", follow_all));
+    }
+
+    #[test]
+    fn debian_version_ordering() {
+        // Orderings dpkg --compare-versions agrees with: `~` before the end
+        // of the string, letters before non-letters, epochs before all else
+        let ordered = [
+            "0~", "0", "1.0~~", "1.0~~a", "1.0~", "1.0~rc1", "1.0", "1.0-1",
+            "1.0-1ubuntu1", "1.0-1+b1", "1.0-1.1", "1.0a", "1.0z", "1.0+b1", "1.0+dfsg",
+            "1.0.1", "1.2", "1.10", "1:0.1", "1:0.1-1", "2:0.1", "10:0",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(compare_versions(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+            assert_eq!(compare_versions(pair[1], pair[0]), Ordering::Greater, "{} > {}", pair[1], pair[0]);
+        }
+        assert_eq!(compare_versions("1.01", "1.1"), Ordering::Equal);
+        assert_eq!(compare_versions("0:1.0", "1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0-0", "1.0"), Ordering::Equal);
+    }
+}
@@ -0,0 +1,659 @@
+//! In-memory package backend for tests
+//!
+//! `FakeBackend` implements `Backend` over packages described by a fixture, so
+//! the typestate manager can be driven through whole user flows without
+//! libapt. A fixture is written like a dpkg status file, one stanza per
+//! package with blank lines in between:
+//!
+//! ```text
+//! Package: foo
+//! Installed: 1.0
+//! Candidate: 2.0
+//! Depends: bar (>= 2.0), baz
+//! ```
+//!
+//! `Package` may carry an arch (`libfoo:i386`); without one the package gets
+//! the native arch (amd64). A package without a `Candidate` stays on its
+//! installed version, `Versions` lists further versions that can be picked,
+//! and `Depends` and `Provides` apply to every version. `Section`,
+//! `Description`, `Size`, `Installed-Size`, `Auto-Installed`, `Hold` and
+//! `Config-Files` (removed but not purged) are optional. Lines starting with
+//! `#` are comments.
+//!
+//! The resolver is much simpler than APT's: marking a package for install
+//! also installs whatever its dependencies need that the system won't have,
+//! and resolving removes installed packages whose dependencies a removal
+//! broke.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use color_eyre::Result;
+
+use crate::apt::section_name;
+use crate::backend::{Backend, MarkedChange, PackageView};
+use crate::deps::{DepAlternative, DependencySource, OrGroup, Satisfier, compare_versions, parse_relations};
+use crate::filter::{Field, PackageFacts, Value};
+use crate::types::*;
+
+/// Pending change of a fake package
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Keep,
+    Install,
+    Delete { purge: bool },
+}
+
+struct FakePackage {
+    name: String,
+    arch: String,
+    installed: Option<String>,
+    candidate: Option<String>,
+    /// Every version, newest first
+    versions: Vec<String>,
+    depends: Vec<OrGroup>,
+    provides: Vec<(String, Option<String>)>,
+    section: String,
+    description: String,
+    size: u64,
+    installed_size: u64,
+    auto: bool,
+    held: bool,
+    config_files: bool,
+    mark: Mark,
+    /// Marked for install only to satisfy another package
+    marked_auto: bool,
+}
+
+impl FakePackage {
+    /// Version present once the marks are applied
+    fn version_after(&self) -> Option<&str> {
+        match self.mark {
+            Mark::Keep => self.installed.as_deref(),
+            Mark::Install => self.candidate.as_deref(),
+            Mark::Delete { .. } => None,
+        }
+    }
+
+    fn installed_version(&self) -> Option<&str> {
+        self.installed.as_deref()
+    }
+
+    fn candidate_version(&self) -> Option<&str> {
+        self.candidate.as_deref()
+    }
+
+    /// How the candidate compares to the installed version
+    fn candidate_order(&self) -> Option<Ordering> {
+        Some(compare_versions(self.candidate.as_deref()?, self.installed.as_deref()?))
+    }
+
+    fn is_upgradable(&self) -> bool {
+        self.candidate_order() == Some(Ordering::Greater)
+    }
+
+    fn is_residual_config(&self) -> bool {
+        self.installed.is_none() && self.config_files
+    }
+}
+
+/// Package backend kept in memory, loaded from a fixture
+pub struct FakeBackend {
+    native_arch: String,
+    /// Packages by full name
+    packages: BTreeMap<String, FakePackage>,
+    fullname_to_id: HashMap<String, PackageId>,
+    id_to_fullname: Vec<String>,
+    /// Candidates replaced by `set_candidate_version`
+    original_candidates: HashMap<PackageId, Option<String>>,
+}
+
+impl FakeBackend {
+    /// Load packages from a fixture (see the module docs for the format)
+    pub fn from_fixture(fixture: &str) -> Result<Self, String> {
+        let mut backend = Self {
+            native_arch: "amd64".to_string(),
+            packages: BTreeMap::new(),
+            fullname_to_id: HashMap::new(),
+            id_to_fullname: Vec::new(),
+            original_candidates: HashMap::new(),
+        };
+
+        let lines: Vec<&str> = fixture.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .collect();
+        for stanza in lines.split(|line| line.trim().is_empty()) {
+            if !stanza.is_empty() {
+                backend.add_stanza(stanza)?;
+            }
+        }
+        Ok(backend)
+    }
+
+    fn add_stanza(&mut self, lines: &[&str]) -> Result<(), String> {
+        let mut fields = HashMap::new();
+        for line in lines {
+            let (key, value) = line.split_once(':')
+                .ok_or_else(|| format!("Expected `Field: value`, got `{}`", line.trim()))?;
+            fields.insert(key.trim().to_lowercase(), value.trim());
+        }
+
+        let package = fields.remove("package").ok_or("Stanza without a Package field")?;
+        let (name, arch) = package.split_once(':').unwrap_or((package, &self.native_arch));
+        let fullname = format!("{name}:{arch}");
+        if self.packages.contains_key(&fullname) {
+            return Err(format!("{fullname} is listed twice"));
+        }
+
+        let flag = |value: Option<&str>| value == Some("yes");
+        let number = |value: Option<&str>| {
+            value.map_or(Ok(0), |v| v.parse().map_err(|_| format!("{fullname}: `{v}` is not a size")))
+        };
+        let installed = fields.remove("installed").map(str::to_string);
+        let candidate = fields.remove("candidate").map(str::to_string).or_else(|| installed.clone());
+
+        let mut versions: Vec<String> = fields.remove("versions").unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .chain(installed.clone())
+            .chain(candidate.clone())
+            .collect();
+        versions.sort_by(|a, b| compare_versions(b, a));
+        versions.dedup();
+
+        let depends = parse_relations(fields.remove("depends").unwrap_or_default())
+            .map_err(|e| format!("{fullname}: {e}"))?;
+        let provides = parse_relations(fields.remove("provides").unwrap_or_default())
+            .map_err(|e| format!("{fullname}: {e}"))?
+            .into_iter()
+            .flatten()
+            .map(|alt| (alt.name, alt.constraint.map(|(_, version)| version)))
+            .collect();
+
+        let pkg = FakePackage {
+            name: name.to_string(),
+            arch: arch.to_string(),
+            installed,
+            candidate,
+            versions,
+            depends,
+            provides,
+            section: fields.remove("section").unwrap_or("misc").to_string(),
+            description: fields.remove("description").unwrap_or_default().to_string(),
+            size: number(fields.remove("size"))?,
+            installed_size: number(fields.remove("installed-size"))?,
+            auto: flag(fields.remove("auto-installed")),
+            held: flag(fields.remove("hold")),
+            config_files: flag(fields.remove("config-files")),
+            mark: Mark::Keep,
+            marked_auto: false,
+        };
+        if let Some(field) = fields.keys().next() {
+            return Err(format!("{fullname}: unknown field `{field}`"));
+        }
+
+        self.id_for(&fullname);
+        self.packages.insert(fullname, pkg);
+        Ok(())
+    }
+
+    fn package(&self, id: PackageId) -> Option<&FakePackage> {
+        self.packages.get(self.id_to_fullname.get(id.index())?)
+    }
+
+    fn package_mut(&mut self, id: PackageId) -> Option<&mut FakePackage> {
+        self.packages.get_mut(self.id_to_fullname.get(id.index())?)
+    }
+
+    /// A package by full name or by name in the native arch
+    fn lookup(&self, name: &str) -> Option<&FakePackage> {
+        self.packages.get(name)
+            .or_else(|| self.packages.get(&format!("{name}:{}", self.native_arch)))
+    }
+
+    /// Mark a package and, recursively, what its dependencies need
+    fn install(&mut self, fullname: &str, auto: bool) {
+        let Some(pkg) = self.packages.get_mut(fullname) else {
+            return;
+        };
+        if pkg.mark == Mark::Install || pkg.candidate.is_none() {
+            return;
+        }
+        if pkg.installed == pkg.candidate {
+            pkg.mark = Mark::Keep;
+            return;
+        }
+        pkg.mark = Mark::Install;
+        pkg.marked_auto = auto && pkg.installed.is_none();
+
+        let arch = pkg.arch.clone();
+        let depends = pkg.depends.clone();
+        for group in &depends {
+            if self.satisfied(group, &arch) {
+                continue;
+            }
+            // Like APT, install the first alternative that can do it
+            if let Some(target) = group.iter().find_map(|alt| self.matching(alt, &arch, FakePackage::candidate_version)) {
+                self.install(&target, true);
+            }
+        }
+    }
+
+    /// Whether a dependency holds once the marks are applied
+    fn satisfied(&self, group: &OrGroup, depender_arch: &str) -> bool {
+        group.iter().any(|alt| self.matching(alt, depender_arch, FakePackage::version_after).is_some())
+    }
+
+    /// Whether a dependency holds on the installed system
+    fn satisfied_now(&self, group: &OrGroup, depender_arch: &str) -> bool {
+        group.iter().any(|alt| self.matching(alt, depender_arch, FakePackage::installed_version).is_some())
+    }
+
+    /// First package whose `version` satisfies `alt`, itself or through a Provides
+    fn matching(&self, alt: &DepAlternative, depender_arch: &str, version: fn(&FakePackage) -> Option<&str>) -> Option<String> {
+        let wanted_arch = alt.arch.as_deref().unwrap_or(depender_arch);
+        self.packages.iter()
+            .filter(|(_, p)| wanted_arch == "any" || p.arch == wanted_arch || p.arch == "all")
+            .find(|(_, p)| {
+                let Some(own) = version(p) else {
+                    return false;
+                };
+                let provided = p.provides.iter()
+                    .filter(|(name, _)| *name == alt.name)
+                    .map(|(_, version)| version.as_deref());
+                (p.name == alt.name).then_some(Some(own))
+                    .into_iter()
+                    .chain(provided)
+                    .any(|version| match (&alt.constraint, version) {
+                        (None, _) => true,
+                        (Some((op, wanted)), Some(version)) => op.holds(compare_versions(version, wanted)),
+                        (Some(_), None) => false,
+                    })
+            })
+            .map(|(fullname, _)| fullname.clone())
+    }
+
+    /// Installed packages that depend on `name`
+    fn installed_rdeps(&self, name: &str) -> usize {
+        self.packages.values()
+            .filter(|p| p.installed.is_some() && p.depends.iter().flatten().any(|alt| alt.name == name))
+            .count()
+    }
+}
+
+/// What filters and counts see of a fake package
+struct FakeView<'a> {
+    backend: &'a FakeBackend,
+    fullname: &'a str,
+    pkg: &'a FakePackage,
+}
+
+impl PackageFacts for FakeView<'_> {
+    fn value(&self, field: Field) -> Value {
+        let pkg = self.pkg;
+        let text = |value: Option<&String>| Value::Text(value.cloned().into_iter().collect());
+        match field {
+            Field::Name | Field::Source => Value::Text(vec![pkg.name.clone()]),
+            Field::Section => Value::Text(vec![section_name(&pkg.section).to_string(), pkg.section.clone()]),
+            Field::Arch => Value::Text(vec![pkg.arch.clone()]),
+            Field::Origin | Field::Archive | Field::Priority | Field::Maintainer => Value::Text(Vec::new()),
+            Field::InstalledVersion => text(pkg.installed.as_ref()),
+            Field::CandidateVersion => text(pkg.candidate.as_ref()),
+            Field::InstalledSize => Value::Number(pkg.installed_size),
+            Field::DownloadSize => Value::Number(pkg.size),
+            Field::Rdeps => Value::Number(self.backend.installed_rdeps(&pkg.name) as u64),
+            Field::Installed => Value::Flag(pkg.installed.is_some()),
+            Field::Upgradable => Value::Flag(pkg.is_upgradable()),
+            Field::Auto => Value::Flag(pkg.installed.is_some() && pkg.auto),
+            Field::Held => Value::Flag(pkg.held),
+            Field::Residual => Value::Flag(pkg.is_residual_config()),
+            Field::Essential | Field::Broken => Value::Flag(false),
+            Field::HasRdeps => Value::Flag(self.backend.installed_rdeps(&pkg.name) > 0),
+        }
+    }
+
+    fn compare_versions(&self, a: &str, b: &str) -> Ordering {
+        compare_versions(a, b)
+    }
+}
+
+impl PackageView for FakeView<'_> {
+    fn fullname(&self) -> String {
+        self.fullname.to_string()
+    }
+
+    fn name(&self) -> &str {
+        &self.pkg.name
+    }
+
+    fn is_installed(&self) -> bool {
+        self.pkg.installed.is_some()
+    }
+
+    fn is_upgradable(&self) -> bool {
+        self.pkg.is_upgradable()
+    }
+
+    fn is_marked(&self) -> bool {
+        self.pkg.mark != Mark::Keep
+    }
+
+    fn is_residual_config(&self) -> bool {
+        self.pkg.is_residual_config()
+    }
+
+    fn is_held(&self) -> bool {
+        self.pkg.held
+    }
+
+    /// Fixtures have no repositories
+    fn repos(&self) -> Vec<RepoFilter> {
+        Vec::new()
+    }
+
+    fn section(&self) -> Option<String> {
+        Some(section_name(&self.pkg.section).to_string())
+    }
+}
+
+impl DependencySource for FakeBackend {
+    fn native_arch(&self) -> &str {
+        &self.native_arch
+    }
+
+    fn depends(&self, fullname: &str) -> Vec<OrGroup> {
+        self.packages.get(fullname).map(|p| p.depends.clone()).unwrap_or_default()
+    }
+
+    fn satisfiers(&self, name: &str) -> Vec<Satisfier> {
+        let mut result = Vec::new();
+        for (fullname, pkg) in &self.packages {
+            let Some(version) = pkg.candidate.as_ref().or(pkg.installed.as_ref()) else {
+                continue;
+            };
+            if pkg.name == name {
+                result.push(Satisfier {
+                    fullname: fullname.clone(),
+                    arch: pkg.arch.clone(),
+                    version: Some(version.clone()),
+                });
+            }
+            for (provided, version) in &pkg.provides {
+                if provided == name {
+                    result.push(Satisfier {
+                        fullname: fullname.clone(),
+                        arch: pkg.arch.clone(),
+                        version: version.clone(),
+                    });
+                }
+            }
+        }
+        result
+    }
+
+    fn compare_versions(&self, a: &str, b: &str) -> Ordering {
+        compare_versions(a, b)
+    }
+}
+
+impl Backend for FakeBackend {
+    fn get_id(&self, fullname: &str) -> Option<PackageId> {
+        self.fullname_to_id.get(fullname).copied()
+    }
+
+    fn id_for(&mut self, fullname: &str) -> PackageId {
+        if let Some(&id) = self.fullname_to_id.get(fullname) {
+            return id;
+        }
+        let id = PackageId(self.id_to_fullname.len() as u32);
+        self.id_to_fullname.push(fullname.to_string());
+        self.fullname_to_id.insert(fullname.to_string(), id);
+        id
+    }
+
+    fn fullname_of(&self, id: PackageId) -> Option<&str> {
+        self.id_to_fullname.get(id.index()).map(String::as_str)
+    }
+
+    fn for_each_package(&self, upgradable_only: bool, visit: &mut dyn FnMut(&dyn PackageView)) {
+        for (fullname, pkg) in &self.packages {
+            if !upgradable_only || pkg.is_upgradable() {
+                visit(&FakeView { backend: self, fullname, pkg });
+            }
+        }
+    }
+
+    fn package_info(&self, fullname: &str) -> Option<PackageInfo> {
+        let pkg = self.packages.get(fullname)?;
+        let candidate = match &pkg.candidate {
+            Some(c) => c.clone(),
+            None if pkg.is_residual_config() => pkg.versions.first()?.clone(),
+            None => return None,
+        };
+
+        let status = if pkg.installed.is_some() {
+            if pkg.is_upgradable() {
+                PackageStatus::Upgradable
+            } else {
+                PackageStatus::Installed
+            }
+        } else if pkg.is_residual_config() {
+            PackageStatus::ResidualConfig
+        } else {
+            PackageStatus::NotInstalled
+        };
+
+        Some(PackageInfo {
+            id: self.get_id(fullname).unwrap_or(PackageId(u32::MAX)),
+            name: fullname.to_string(),
+            status,
+            section: pkg.section.clone(),
+            installed_version: pkg.installed.clone().unwrap_or_default(),
+            candidate_version: candidate,
+            installed_size: pkg.installed_size,
+            download_size: pkg.size,
+            description: pkg.description.clone(),
+            architecture: pkg.arch.clone(),
+        })
+    }
+
+    fn installed_version(&self, id: PackageId) -> Option<String> {
+        self.package(id)?.installed.clone()
+    }
+
+    fn candidate_version(&self, id: PackageId) -> Option<String> {
+        self.package(id)?.candidate.clone()
+    }
+
+    fn available_versions(&self, id: PackageId) -> Vec<AvailableVersion> {
+        let Some(pkg) = self.package(id) else {
+            return Vec::new();
+        };
+        let candidate = self.original_candidates.get(&id).cloned().unwrap_or_else(|| pkg.candidate.clone());
+
+        pkg.versions.iter()
+            .map(|version| AvailableVersion {
+                version: version.clone(),
+                origin: "fixture".to_string(),
+                archive: "stable".to_string(),
+                component: "main".to_string(),
+                priority: 500,
+                is_installed: pkg.installed.as_ref() == Some(version),
+                is_candidate: candidate.as_ref() == Some(version),
+                download_size: pkg.size,
+                installed_size: pkg.installed_size,
+            })
+            .collect()
+    }
+
+    fn clear_all_marks(&mut self) {
+        for pkg in self.packages.values_mut() {
+            pkg.mark = Mark::Keep;
+            pkg.marked_auto = false;
+        }
+    }
+
+    fn restore_candidates(&mut self) {
+        for (id, candidate) in std::mem::take(&mut self.original_candidates) {
+            if let Some(pkg) = self.package_mut(id) {
+                pkg.candidate = candidate;
+            }
+        }
+    }
+
+    fn set_candidate_version(&mut self, id: PackageId, version: &str) -> bool {
+        let Some(pkg) = self.package(id) else {
+            return false;
+        };
+        if !pkg.versions.iter().any(|v| v == version) {
+            return false;
+        }
+
+        let candidate = pkg.candidate.clone();
+        self.original_candidates.entry(id).or_insert(candidate);
+        if let Some(pkg) = self.package_mut(id) {
+            pkg.candidate = Some(version.to_string());
+        }
+        true
+    }
+
+    fn mark_install(&mut self, id: PackageId) {
+        if let Some(fullname) = self.fullname_of(id).map(str::to_string) {
+            self.install(&fullname, false);
+        }
+    }
+
+    fn mark_delete(&mut self, id: PackageId, purge: bool) {
+        if let Some(pkg) = self.package_mut(id)
+            && (pkg.installed.is_some() || (purge && pkg.config_files)) {
+                pkg.mark = Mark::Delete { purge };
+        }
+    }
+
+    fn mark_keep(&mut self, id: PackageId) {
+        if let Some(pkg) = self.package_mut(id) {
+            pkg.mark = Mark::Keep;
+            pkg.marked_auto = false;
+        }
+    }
+
+    fn set_hold(&mut self, id: PackageId, hold: bool) -> Result<(), String> {
+        let pkg = self.package_mut(id).ok_or_else(|| "Unknown package".to_string())?;
+        pkg.held = hold;
+        Ok(())
+    }
+
+    fn resolve(&mut self) -> Result<(), String> {
+        // Installed packages whose dependencies are going away go with them
+        // (ones that were broken to begin with are left alone)
+        loop {
+            let broken: Vec<String> = self.packages.iter()
+                .filter(|(_, p)| p.mark == Mark::Keep && p.installed.is_some())
+                .filter(|(_, p)| {
+                    p.depends.iter().any(|group| self.satisfied_now(group, &p.arch) && !self.satisfied(group, &p.arch))
+                })
+                .map(|(fullname, _)| fullname.clone())
+                .collect();
+            if broken.is_empty() {
+                break;
+            }
+            for fullname in broken {
+                if let Some(pkg) = self.packages.get_mut(&fullname) {
+                    pkg.mark = Mark::Delete { purge: false };
+                }
+            }
+        }
+
+        // Packages to install whose dependencies still can't be met
+        let mut errors = Vec::new();
+        for (fullname, pkg) in self.packages.iter().filter(|(_, p)| p.mark == Mark::Install) {
+            for group in pkg.depends.iter().filter(|group| !self.satisfied(group, &pkg.arch)) {
+                let names: Vec<&str> = group.iter().map(|alt| alt.name.as_str()).collect();
+                errors.push(format!("{} : Depends: {} but it is not installable", self.display_name(fullname), names.join(" | ")));
+            }
+        }
+
+        match errors.as_slice() {
+            [] => Ok(()),
+            [error] => Err(error.clone()),
+            [first, rest @ ..] => Err(format!("{first}; and {} more issue(s)", rest.len())),
+        }
+    }
+
+    fn changes(&self) -> Vec<MarkedChange> {
+        self.packages.iter()
+            .filter(|(_, p)| p.mark != Mark::Keep)
+            .map(|(fullname, p)| {
+                let install = p.mark == Mark::Install;
+                MarkedChange {
+                    fullname: fullname.clone(),
+                    is_installed: p.installed.is_some(),
+                    is_auto: p.auto,
+                    marked_install: install && p.installed.is_none(),
+                    marked_upgrade: install && p.candidate_order() == Some(Ordering::Greater),
+                    marked_downgrade: install && p.candidate_order() == Some(Ordering::Less),
+                    marked_delete: matches!(p.mark, Mark::Delete { .. }),
+                    marked_purge: p.mark == Mark::Delete { purge: true },
+                    candidate_sizes: p.candidate.as_ref().map(|_| (p.size, p.installed_size)),
+                    installed_size: p.installed.as_ref().map(|_| p.installed_size),
+                }
+            })
+            .collect()
+    }
+
+    fn get_dependencies(&self, name: &str) -> Vec<(String, String)> {
+        let Some(pkg) = self.lookup(name) else {
+            return Vec::new();
+        };
+        let mut deps: Vec<(String, String)> = pkg.depends.iter()
+            .flatten()
+            .map(|alt| ("Depends".to_string(), alt.name.clone()))
+            .collect();
+        deps.sort();
+        deps
+    }
+
+    fn get_reverse_dependencies(&self, name: &str) -> Vec<(String, String)> {
+        let Some(target) = self.lookup(name) else {
+            return Vec::new();
+        };
+        let mut rdeps: Vec<(String, String)> = self.packages.values()
+            .filter(|p| p.depends.iter().flatten().any(|alt| alt.name == target.name))
+            .map(|p| ("Depends".to_string(), p.name.clone()))
+            .collect();
+        rdeps.sort();
+        rdeps
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.clear_all_marks();
+        self.restore_candidates();
+        Ok(())
+    }
+
+    /// Apply the marks to the installed state, the way dpkg would
+    fn commit(&mut self) -> Result<()> {
+        for pkg in self.packages.values_mut() {
+            match pkg.mark {
+                Mark::Keep => {}
+                Mark::Install => {
+                    if pkg.installed.is_none() {
+                        pkg.auto = pkg.marked_auto;
+                    }
+                    pkg.installed = pkg.candidate.clone();
+                    pkg.config_files = false;
+                }
+                Mark::Delete { purge } => {
+                    pkg.installed = None;
+                    pkg.config_files = !purge;
+                    pkg.auto = false;
+                }
+            }
+        }
+        self.clear_all_marks();
+        self.restore_candidates();
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! Package file lists
+//!
+//! Lists the files of a package and answers "which package owns this path".
+//! Installed packages are looked up in dpkg's `*.list` files; packages that
+//! aren't installed are found through the Contents indexes apt-file style
+//! sources download into the APT lists directory, when there are any.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use flate2::read::GzDecoder;
+
+use crate::root;
+
+/// Where dpkg keeps the file list of each installed package (under `root::path`)
+pub(crate) const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+
+/// APT's lists directory (Dir::State::lists, which follows `--root`)
+pub(crate) fn apt_lists_dir() -> PathBuf {
+    PathBuf::from(rust_apt::config::Config::new().dir("Dir::State::lists", "/var/lib/apt/lists/"))
+}
+
+/// Rank of a path that matches the query exactly (partial matches get 0)
+const EXACT_PATH_RANK: f64 = -1.0;
+
+/// A file shipped by a package
+#[derive(Debug, Clone)]
+pub struct PackageFile {
+    pub path: String,
+    /// Size on disk; None when the package isn't installed
+    pub size: Option<u64>,
+    pub conffile: bool,
+}
+
+/// Files of an installed package from its dpkg file list, without the
+/// directories it shares with other packages. None if there is no list.
+pub fn installed_files(name: &str, arch: &str) -> Option<Vec<PackageFile>> {
+    let base = name.split(':').next().unwrap_or(name);
+    // Multi-Arch: same packages have per-architecture lists
+    let read = |ext: &str| {
+        std::fs::read_to_string(root::path(&format!("{DPKG_INFO_DIR}/{base}:{arch}.{ext}")))
+            .or_else(|_| std::fs::read_to_string(root::path(&format!("{DPKG_INFO_DIR}/{base}.{ext}"))))
+    };
+    let list = read("list").ok()?;
+
+    // Lines are a path, or flags then a path (`remove-on-upgrade /etc/foo`)
+    let conffiles: Vec<String> = read("conffiles").unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.find('/').map(|i| line[i..].to_string()))
+        .collect();
+
+    let files = list.lines()
+        .filter_map(|path| {
+            let meta = std::fs::symlink_metadata(root::path(path)).ok();
+            if meta.as_ref().is_some_and(std::fs::Metadata::is_dir) || path == "/." {
+                return None;
+            }
+            Some(PackageFile {
+                path: path.to_string(),
+                size: Some(meta.map_or(0, |m| m.len())),
+                conffile: conffiles.iter().any(|c| c == path),
+            })
+        })
+        .collect();
+    Some(files)
+}
+
+/// Files of a package according to the Contents indexes (empty if there
+/// are none). Reads every index, so this takes a while.
+pub fn contents_files(name: &str) -> Vec<PackageFile> {
+    let base = name.split(':').next().unwrap_or(name);
+    let mut files = Vec::new();
+    scan_contents(&[base], |path, packages| {
+        if packages.split(',').any(|p| p.rsplit('/').next() == Some(base)) {
+            files.push(PackageFile { path: format!("/{path}"), size: None, conffile: false });
+        }
+    });
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files.dedup_by(|a, b| a.path == b.path);
+    files
+}
+
+/// Runs a slow lookup (`contents_files`, `find_owners`) on a worker thread,
+/// one at a time. Requests superseded before the worker gets to them are
+/// skipped, and only the result of the latest request is handed out.
+pub struct FileLookup<T> {
+    requests: Sender<String>,
+    results: Receiver<(String, T)>,
+    latest: Option<String>,
+}
+
+impl<T: Send + 'static> FileLookup<T> {
+    pub fn new(lookup: impl Fn(&str) -> T + Send + 'static) -> Self {
+        let (requests, worker_requests) = mpsc::channel::<String>();
+        let (worker_results, results) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(mut key) = worker_requests.recv() {
+                while let Ok(newer) = worker_requests.try_recv() {
+                    key = newer;
+                }
+                let result = lookup(&key);
+                if worker_results.send((key, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results, latest: None }
+    }
+
+    /// Look up `key`, superseding any earlier request
+    pub fn request(&mut self, key: String) {
+        self.requests.send(key.clone()).ok();
+        self.latest = Some(key);
+    }
+
+    /// Drop the result of the pending request, if any
+    pub fn cancel(&mut self) {
+        self.latest = None;
+    }
+
+    /// The key still being looked up
+    pub fn pending(&self) -> Option<&str> {
+        self.latest.as_deref()
+    }
+
+    /// The result of the latest request, once it's ready
+    pub fn poll(&mut self) -> Option<(String, T)> {
+        while let Ok((key, result)) = self.results.try_recv() {
+            if self.latest.as_ref() == Some(&key) {
+                self.latest = None;
+                return Some((key, result));
+            }
+        }
+        None
+    }
+}
+
+/// Whether a search query is a file path rather than a text query
+pub fn is_path_query(query: &str) -> bool {
+    query.trim_start().starts_with('/')
+}
+
+/// Find the packages owning a path containing `query`, keyed by base package
+/// name with a rank (lower is better): packages with the exact path come
+/// first.
+pub fn find_owners(query: &str) -> HashMap<String, f64> {
+    let query = query.trim();
+    let mut owners = HashMap::new();
+    if query.is_empty() {
+        return owners;
+    }
+
+    let mut queries = vec![query.to_string()];
+    queries.extend(merged_usr_alias(query));
+
+    search_dpkg_lists(&queries, &mut owners);
+    let relative: Vec<&str> = queries.iter().map(|q| q.trim_start_matches('/')).collect();
+    search_contents(&relative, &mut owners);
+    owners
+}
+
+/// On merged-/usr systems /bin, /sbin and /lib* are links into /usr, but
+/// packages record whichever path they ship. Give the other spelling.
+fn merged_usr_alias(path: &str) -> Option<String> {
+    const MERGED: [&str; 6] = ["bin", "sbin", "lib", "lib32", "lib64", "libx32"];
+    let is_merged = |rest: &str| {
+        let dir = rest.split('/').next().unwrap_or(rest);
+        MERGED.contains(&dir)
+    };
+
+    match path.strip_prefix("/usr/") {
+        Some(rest) if is_merged(rest) => Some(format!("/{rest}")),
+        Some(_) => None,
+        None => path.strip_prefix('/').filter(|rest| is_merged(rest)).map(|rest| format!("/usr/{rest}")),
+    }
+}
+
+fn add_owner(owners: &mut HashMap<String, f64>, name: &str, rank: f64) {
+    let name = name.split(':').next().unwrap_or(name);
+    let entry = owners.entry(name.to_string()).or_insert(rank);
+    *entry = entry.min(rank);
+}
+
+/// Search the file lists of installed packages (`<pkg>[:<arch>].list`)
+fn search_dpkg_lists(queries: &[String], owners: &mut HashMap<String, f64>) {
+    let Ok(entries) = std::fs::read_dir(root::path(DPKG_INFO_DIR)) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".list")) else {
+            continue;
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let mut rank = None;
+        for line in content.lines().filter(|line| queries.iter().any(|q| line.contains(q.as_str()))) {
+            if queries.iter().any(|q| line == q) {
+                rank = Some(EXACT_PATH_RANK);
+                break;
+            }
+            rank = Some(0.0);
+        }
+        if let Some(rank) = rank {
+            add_owner(owners, name, rank);
+        }
+    }
+}
+
+/// Search the Contents indexes for packages owning a matching path
+fn search_contents(queries: &[&str], owners: &mut HashMap<String, f64>) {
+    scan_contents(queries, |file, packages| {
+        let rank = if queries.contains(&file) { EXACT_PATH_RANK } else { 0.0 };
+        for package in packages.split(',') {
+            let name = package.rsplit('/').next().unwrap_or(package);
+            add_owner(owners, name, rank);
+        }
+    });
+}
+
+/// Call `f(path, packages)` for the lines of every Contents index in the APT
+/// lists directory that contain one of `needles`. Lines are
+/// `<path without leading slash>  <section>/<pkg>[,<section>/<pkg>...]`.
+fn scan_contents(needles: &[&str], mut f: impl FnMut(&str, &str)) {
+    let Ok(entries) = std::fs::read_dir(apt_lists_dir()) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let Some(reader) = open_contents(&entry.path()) else {
+            continue;
+        };
+
+        for line in reader.lines().map_while(Result::ok) {
+            if !needles.iter().any(|n| line.contains(n)) {
+                continue;
+            }
+            if let Some((file, packages)) = line.trim_end().rsplit_once([' ', '\t']) {
+                f(file.trim_end(), packages);
+            }
+        }
+    }
+}
+
+/// Open a Contents index (`..._Contents-<arch>[.lz4|.gz]`), decompressing it.
+/// Anything else, including installer (udeb) Contents, gives None.
+fn open_contents(path: &Path) -> Option<BufReader<Box<dyn Read>>> {
+    let name = path.file_name()?.to_str()?;
+    let (_, suffix) = name.split_once("_Contents-")?;
+    if suffix.starts_with("udeb") {
+        return None;
+    }
+
+    let file = File::open(path).ok()?;
+    let reader: Box<dyn Read> = match suffix.rsplit_once('.') {
+        Some((_, "lz4")) => Box::new(lz4_flex::frame::FrameDecoder::new(file)),
+        Some((_, "gz")) => Box::new(GzDecoder::new(file)),
+        Some(_) => return None,
+        None => Box::new(file),
+    };
+    Some(BufReader::new(reader))
+}
@@ -0,0 +1,560 @@
+//! Custom filter expressions
+//!
+//! ```text
+//! installed_size > 100M && section == "games"
+//! auto && !has_rdeps
+//! origin =~ "ppa" || (upgradable && candidate_version >= "2.0")
+//! ```
+//!
+//! - Flags (`installed`, `auto`, ...) are used bare and negated with `!`
+//! - Text fields compare with `==`, `!=`, `=~` (contains) and `!~`, ignoring case
+//! - Versions compare with `==`, `!=`, `<`, `<=`, `>`, `>=` in Debian order, and `=~`
+//! - Sizes are bytes and compare numerically; `K`, `M` and `G` suffixes are powers of 1024
+//! - `&&` binds tighter than `||`; parentheses group
+//!
+//! Named filters live in the `[filters]` section of config.toml and show up
+//! in the filter pane next to the built-in categories.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::query::ParseError;
+
+/// Something a filter can test about a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    // Text
+    Name,
+    Section,
+    Arch,
+    Origin,
+    Archive,
+    Priority,
+    Maintainer,
+    Source,
+    // Versions
+    InstalledVersion,
+    CandidateVersion,
+    // Sizes and counts
+    InstalledSize,
+    DownloadSize,
+    Rdeps,
+    // Flags
+    Installed,
+    Upgradable,
+    Auto,
+    Held,
+    Residual,
+    Essential,
+    Broken,
+    HasRdeps,
+}
+
+/// How a field's values compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Version,
+    Number,
+    Flag,
+}
+
+const FIELDS: [(&str, Field); 21] = [
+    ("name", Field::Name),
+    ("section", Field::Section),
+    ("arch", Field::Arch),
+    ("origin", Field::Origin),
+    ("archive", Field::Archive),
+    ("priority", Field::Priority),
+    ("maintainer", Field::Maintainer),
+    ("source", Field::Source),
+    ("installed_version", Field::InstalledVersion),
+    ("candidate_version", Field::CandidateVersion),
+    ("installed_size", Field::InstalledSize),
+    ("download_size", Field::DownloadSize),
+    ("rdeps", Field::Rdeps),
+    ("installed", Field::Installed),
+    ("upgradable", Field::Upgradable),
+    ("auto", Field::Auto),
+    ("held", Field::Held),
+    ("residual", Field::Residual),
+    ("essential", Field::Essential),
+    ("broken", Field::Broken),
+    ("has_rdeps", Field::HasRdeps),
+];
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        FIELDS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+    }
+
+    pub fn name(&self) -> &'static str {
+        FIELDS.iter().find(|(_, f)| f == self).map_or("?", |(n, _)| n)
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Name | Self::Section | Self::Arch | Self::Origin | Self::Archive
+            | Self::Priority | Self::Maintainer | Self::Source => Kind::Text,
+            Self::InstalledVersion | Self::CandidateVersion => Kind::Version,
+            Self::InstalledSize | Self::DownloadSize | Self::Rdeps => Kind::Number,
+            Self::Installed | Self::Upgradable | Self::Auto | Self::Held | Self::Residual
+            | Self::Essential | Self::Broken | Self::HasRdeps => Kind::Flag,
+        }
+    }
+}
+
+/// A field's value for one package
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Text fields can hold several values (a package can come from several
+    /// origins); a comparison holds if any of them matches
+    Text(Vec<String>),
+    Number(u64),
+    Flag(bool),
+}
+
+/// Where filters get package data from. Values are asked for lazily, so a
+/// filter only pays for the fields it uses.
+pub trait PackageFacts {
+    fn value(&self, field: Field) -> Value;
+
+    /// Order two versions the way the package system does
+    fn compare_versions(&self, a: &str, b: &str) -> Ordering;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    NotContains,
+}
+
+impl Op {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Contains => "=~",
+            Self::NotContains => "!~",
+        }
+    }
+
+    fn holds(&self, ord: Ordering) -> bool {
+        match self {
+            Self::Eq => ord == Ordering::Equal,
+            Self::Ne => ord != Ordering::Equal,
+            Self::Lt => ord == Ordering::Less,
+            Self::Le => ord != Ordering::Greater,
+            Self::Gt => ord == Ordering::Greater,
+            Self::Ge => ord != Ordering::Less,
+            Self::Contains | Self::NotContains => false,
+        }
+    }
+}
+
+/// Parsed filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Flag(Field),
+    Text { field: Field, op: Op, value: String },
+    Number { field: Field, op: Op, value: u64 },
+    Not(Box<Filter>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+impl Filter {
+    /// Parse a filter expression
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0, end: input.chars().count() };
+        let filter = parser.parse_or()?;
+        if let Some((token, column)) = parser.tokens.get(parser.pos) {
+            return Err(error(format!("Unexpected {token}"), *column));
+        }
+        Ok(filter)
+    }
+
+    /// Parse the named filters of the `[filters]` config section. Returns
+    /// warnings for expressions that don't parse.
+    pub fn from_config(config: &BTreeMap<String, String>) -> (Vec<(String, Self)>, Vec<String>) {
+        let mut filters = Vec::new();
+        let mut warnings = Vec::new();
+        for (name, expression) in config {
+            match Self::parse(expression) {
+                Ok(filter) => filters.push((name.clone(), filter)),
+                Err(e) => warnings.push(format!("Filter `{name}`: {e} (ignored)")),
+            }
+        }
+        (filters, warnings)
+    }
+
+    pub fn matches(&self, pkg: &(impl PackageFacts + ?Sized)) -> bool {
+        match self {
+            Self::Flag(field) => pkg.value(*field) == Value::Flag(true),
+            Self::Text { field, op, value } => {
+                let Value::Text(texts) = pkg.value(*field) else {
+                    return false;
+                };
+                let wanted = value.to_lowercase();
+                let test = |text: &String| {
+                    // Versions are case sensitive and ordered by the package system
+                    if field.kind() == Kind::Version && !matches!(op, Op::Contains | Op::NotContains) {
+                        return !text.is_empty() && op.holds(pkg.compare_versions(text, value));
+                    }
+                    let text = text.to_lowercase();
+                    match op {
+                        Op::Contains => text.contains(&wanted),
+                        Op::NotContains => !text.contains(&wanted),
+                        _ => op.holds(text.cmp(&wanted)),
+                    }
+                };
+                // Negative operators hold only if no value matches
+                match op {
+                    Op::Ne | Op::NotContains => texts.iter().all(test),
+                    _ => texts.iter().any(test),
+                }
+            }
+            Self::Number { field, op, value } => match pkg.value(*field) {
+                Value::Number(n) => op.holds(n.cmp(value)),
+                _ => false,
+            },
+            Self::Not(inner) => !inner.matches(pkg),
+            Self::And(items) => items.iter().all(|f| f.matches(pkg)),
+            Self::Or(items) => items.iter().any(|f| f.matches(pkg)),
+        }
+    }
+}
+
+fn error(message: impl Into<String>, column: usize) -> ParseError {
+    ParseError { message: message.into(), column }
+}
+
+// ============================================================================
+// Lexer
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(u64),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ident(name) => write!(f, "`{name}`"),
+            Self::Str(text) => write!(f, "\"{text}\""),
+            Self::Number(n) => write!(f, "`{n}`"),
+            Self::Op(op) => write!(f, "`{}`", op.symbol()),
+            Self::And => write!(f, "`&&`"),
+            Self::Or => write!(f, "`||`"),
+            Self::Not => write!(f, "`!`"),
+            Self::Open => write!(f, "`(`"),
+            Self::Close => write!(f, "`)`"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '&' if next == Some('&') => (Token::And, 2),
+            '|' if next == Some('|') => (Token::Or, 2),
+            '=' if next == Some('=') => (Token::Op(Op::Eq), 2),
+            '=' if next == Some('~') => (Token::Op(Op::Contains), 2),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '!' if next == Some('~') => (Token::Op(Op::NotContains), 2),
+            '!' => (Token::Not, 1),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '>' => (Token::Op(Op::Gt), 1),
+            '"' => {
+                let close = (i + 1..chars.len())
+                    .find(|&j| chars[j] == '"')
+                    .ok_or_else(|| error("Unterminated quote", start))?;
+                let text: String = chars[i + 1..close].iter().collect();
+                (Token::Str(text), close + 1 - i)
+            }
+            c if c.is_ascii_digit() => {
+                let end = (i..chars.len()).find(|&j| !chars[j].is_ascii_alphanumeric() && chars[j] != '.').unwrap_or(chars.len());
+                let word: String = chars[i..end].iter().collect();
+                let value = parse_size(&word).ok_or_else(|| error(format!("Invalid number `{word}`"), start))?;
+                (Token::Number(value), end - i)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let end = (i..chars.len()).find(|&j| !chars[j].is_alphanumeric() && chars[j] != '_').unwrap_or(chars.len());
+                (Token::Ident(chars[i..end].iter().collect()), end - i)
+            }
+            c => return Err(error(format!("Unexpected `{c}`"), start)),
+        };
+        tokens.push((token, start));
+        i += len;
+    }
+
+    Ok(tokens)
+}
+
+/// A byte count with an optional K/M/G suffix (and optional B/iB)
+fn parse_size(word: &str) -> Option<u64> {
+    let lower = word.to_ascii_lowercase();
+    let unit_start = lower.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(unit_start);
+    let multiplier: u64 = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        _ => return None,
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * multiplier as f64) as u64)
+}
+
+// ============================================================================
+// Parser
+// ============================================================================
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize, // Column just past the input, for errors at the end
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, c)| *c)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ParseError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| error("Unexpected end of filter", self.end))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// or := and (|| and)*
+    fn parse_or(&mut self) -> Result<Filter, ParseError> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            items.push(self.parse_and()?);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Filter::Or(items) })
+    }
+
+    /// and := unary (&& unary)*
+    fn parse_and(&mut self) -> Result<Filter, ParseError> {
+        let mut items = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            items.push(self.parse_unary()?);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Filter::And(items) })
+    }
+
+    /// unary := !unary | ( or ) | comparison
+    fn parse_unary(&mut self) -> Result<Filter, ParseError> {
+        let (token, column) = self.next()?;
+        match token {
+            Token::Not => Ok(Filter::Not(Box::new(self.parse_unary()?))),
+            Token::Open => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(error("Missing `)`", self.column()));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::Ident(name) => self.parse_comparison(&name, column),
+            other => Err(error(format!("Expected a field, found {other}"), column)),
+        }
+    }
+
+    /// comparison := flag | field op value
+    fn parse_comparison(&mut self, name: &str, column: usize) -> Result<Filter, ParseError> {
+        let field = Field::parse(name).ok_or_else(|| error(format!("Unknown field `{name}`"), column))?;
+
+        let Some(Token::Op(op)) = self.peek().cloned() else {
+            return match field.kind() {
+                Kind::Flag => Ok(Filter::Flag(field)),
+                _ => Err(error(format!("`{name}` needs a comparison (e.g. `{name} == ...`)"), self.column())),
+            };
+        };
+        let op_column = self.column();
+        self.pos += 1;
+
+        let (value, value_column) = self.next()?;
+        match (field.kind(), value) {
+            (Kind::Flag, _) => Err(error(format!("`{name}` is a flag; use `{name}` or `!{name}`"), op_column)),
+            (Kind::Number, Token::Number(n)) if !matches!(op, Op::Contains | Op::NotContains) => {
+                Ok(Filter::Number { field, op, value: n })
+            }
+            (Kind::Number, Token::Number(_)) => Err(error(format!("`{}` doesn't apply to sizes", op.symbol()), op_column)),
+            (Kind::Number, other) => Err(error(format!("`{name}` compares to a number, found {other}"), value_column)),
+            (Kind::Text, _) if matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge) => {
+                Err(error(format!("`{}` doesn't apply to text", op.symbol()), op_column))
+            }
+            (Kind::Text | Kind::Version, Token::Str(value) | Token::Ident(value)) => Ok(Filter::Text { field, op, value }),
+            (Kind::Version, Token::Number(_)) => Err(error("Quote version numbers (e.g. \"2.0\")", value_column)),
+            (_, other) => Err(error(format!("Expected a value, found {other}"), value_column)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::fake::FakeBackend;
+
+    const PACKAGES: &str = "\
+Package: tetris
+Installed: 1.0
+Candidate: 1.2~rc1
+Section: contrib/games
+Installed-Size: 2097152
+
+Package: chess
+Installed: 2:0.9
+Section: games
+Installed-Size: 52428800
+Auto-Installed: yes
+
+Package: editor
+Candidate: 1.10
+Section: editors
+Size: 1536
+";
+
+    /// Names of the fixture packages a filter matches, sorted
+    fn matching(expression: &str) -> Vec<String> {
+        let backend = FakeBackend::from_fixture(PACKAGES).unwrap();
+        let filter = Filter::parse(expression).unwrap();
+        let mut names = Vec::new();
+        backend.for_each_package(false, &mut |pkg| {
+            if filter.matches(pkg) {
+                names.push(pkg.name().to_string());
+            }
+        });
+        names.sort();
+        names
+    }
+
+    fn parse_error(expression: &str) -> (String, usize) {
+        let e = Filter::parse(expression).unwrap_err();
+        (e.message, e.column)
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_column() {
+        assert_eq!(parse_error("installed &&"), ("Unexpected end of filter".to_string(), 12));
+        assert_eq!(parse_error("bogus"), ("Unknown field `bogus`".to_string(), 0));
+        assert_eq!(parse_error("name == \"vim"), ("Unterminated quote".to_string(), 8));
+        assert_eq!(parse_error("(installed"), ("Missing `)`".to_string(), 10));
+        assert_eq!(parse_error("installed_size > 10X"), ("Invalid number `10X`".to_string(), 17));
+        assert_eq!(parse_error("section < \"games\""), ("`<` doesn't apply to text".to_string(), 8));
+        assert_eq!(parse_error("auto == \"yes\""), ("`auto` is a flag; use `auto` or `!auto`".to_string(), 5));
+        assert_eq!(parse_error("candidate_version > 2"), ("Quote version numbers (e.g. \"2.0\")".to_string(), 20));
+        assert_eq!(parse_error("installed installed"), ("Unexpected `installed`".to_string(), 10));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let a = Filter::Flag(Field::Auto);
+        let b = Filter::Flag(Field::Held);
+        let c = Filter::Flag(Field::Installed);
+        assert_eq!(
+            Filter::parse("auto || held && installed").unwrap(),
+            Filter::Or(vec![a.clone(), Filter::And(vec![b.clone(), c.clone()])]),
+        );
+        assert_eq!(
+            Filter::parse("(auto || held) && installed").unwrap(),
+            Filter::And(vec![Filter::Or(vec![a.clone(), b]), c.clone()]),
+        );
+        // `!` applies to the nearest operand only
+        assert_eq!(
+            Filter::parse("!auto && installed").unwrap(),
+            Filter::And(vec![Filter::Not(Box::new(a)), c]),
+        );
+    }
+
+    #[test]
+    fn flags_and_negation() {
+        assert_eq!(matching("installed"), ["chess", "tetris"]);
+        assert_eq!(matching("installed && !auto"), ["tetris"]);
+        assert_eq!(matching("!installed || auto"), ["chess", "editor"]);
+        assert_eq!(matching("!(installed && auto)"), ["editor", "tetris"]);
+    }
+
+    #[test]
+    fn size_suffixes_are_powers_of_1024() {
+        assert_eq!(parse_size("100"), Some(100));
+        assert_eq!(parse_size("1.5K"), Some(1536));
+        assert_eq!(parse_size("2M"), Some(2 << 20));
+        assert_eq!(parse_size("1GiB"), Some(1 << 30));
+        assert_eq!(parse_size("3kb"), Some(3 << 10));
+        assert_eq!(parse_size("4T"), None);
+
+        assert_eq!(matching("installed_size >= 2M"), ["chess", "tetris"]);
+        assert_eq!(matching("installed_size > 2M"), ["chess"]);
+        assert_eq!(matching("download_size == 1.5K"), ["editor"]);
+    }
+
+    #[test]
+    fn negative_operators_need_every_section_value_to_differ() {
+        // tetris is in both "games" and "contrib/games"
+        assert_eq!(matching("section == \"games\""), ["chess", "tetris"]);
+        assert_eq!(matching("section == \"contrib/games\""), ["tetris"]);
+        assert_eq!(matching("section != \"contrib/games\""), ["chess", "editor"]);
+        assert_eq!(matching("section != \"games\""), ["editor"]);
+        assert_eq!(matching("section =~ \"contrib\""), ["tetris"]);
+        assert_eq!(matching("section !~ \"GAME\""), ["editor"]);
+    }
+
+    #[test]
+    fn versions_compare_in_debian_order() {
+        // 1.10 > 1.9 numerically, ~ sorts before the release, epochs win
+        assert_eq!(matching("candidate_version > \"1.9\""), ["chess", "editor"]);
+        assert_eq!(matching("candidate_version < \"1.2\""), ["tetris"]);
+        assert_eq!(matching("installed_version >= \"1.0\""), ["chess", "tetris"]);
+        assert_eq!(matching("installed_version > \"5.0\""), ["chess"]);
+        // Packages without the version never match an ordering
+        assert_eq!(matching("installed_version < \"9\""), ["tetris"]);
+        assert_eq!(matching("candidate_version =~ \"rc\""), ["tetris"]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Versioned JSON output
+//!
+//! What `--json` prints in the synh8 CLI and debug_cli. Every document is an
+//! object with a `schema` number next to its payload:
+//!
+//! ```text
+//! {"schema": 1, "packages": [{"name": "vim:amd64", "status": "installed", ...}]}
+//! ```
+//!
+//! `SCHEMA_VERSION` goes up when a field is removed, renamed or changes
+//! meaning. New fields can appear without a bump, so consumers should ignore
+//! fields they don't know. Package ids are never serialized because they only
+//! live as long as one cache; packages are named by their full name
+//! (`name:arch`).
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::core::ManagerState;
+use crate::simulate::Simulation;
+use crate::types::*;
+
+/// Version of the document layout
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<'a, T> {
+    schema: u32,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+/// Serialize a payload as a pretty-printed, versioned document
+pub fn to_string<T: Serialize>(body: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&Document { schema: SCHEMA_VERSION, body })
+}
+
+/// `{"packages": [...]}`
+#[derive(Serialize)]
+pub struct PackageList<'a> {
+    pub packages: &'a [PackageInfo],
+}
+
+/// One package with everything `show` prints
+#[derive(Serialize)]
+pub struct PackageShow<'a> {
+    pub package: &'a PackageInfo,
+    pub details: &'a PackageDetails,
+    pub versions: &'a [AvailableVersion],
+    pub dependencies: Vec<Dependency>,
+}
+
+/// `{"package": ..., "dependencies": [...]}`
+#[derive(Serialize)]
+pub struct Dependencies<'a> {
+    pub package: &'a str,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// A dependency of a package (`kind` is e.g. "Depends" or "Recommends")
+#[derive(Serialize)]
+pub struct Dependency {
+    pub kind: String,
+    pub name: String,
+}
+
+impl Dependency {
+    /// Convert the `(type, name)` pairs of `get_dependencies()`
+    pub fn from_pairs(pairs: Vec<(String, String)>) -> Vec<Self> {
+        pairs.into_iter().map(|(kind, name)| Self { kind, name }).collect()
+    }
+}
+
+/// Nothing marked, nothing planned
+static NOTHING_PLANNED: Planned = Planned {
+    changes: Vec::new(),
+    download_size: 0,
+    install_size_change: 0,
+    errors: Vec::new(),
+};
+
+/// The current plan: its changes by name, then totals and errors
+#[derive(Serialize)]
+pub struct Plan<'a> {
+    pub changes: Vec<Change<'a>>,
+    #[serde(flatten)]
+    pub totals: &'a Planned,
+}
+
+/// A planned change with the package name and versions
+#[derive(Serialize)]
+pub struct Change<'a> {
+    pub name: String,
+    #[serde(flatten)]
+    pub change: &'a PlannedChange,
+    /// Installed version, if any
+    pub old_version: Option<String>,
+    /// Version after the change; None for removals
+    pub new_version: Option<String>,
+}
+
+impl<'a> Plan<'a> {
+    pub fn new(core: &'a ManagerState) -> Self {
+        let totals = core.planned().unwrap_or(&NOTHING_PLANNED);
+        let cache = core.cache();
+
+        let changes = totals.changes.iter()
+            .map(|change| {
+                let pkg = cache.get_by_id(change.package);
+                let removal = matches!(change.action, ChangeAction::Remove | ChangeAction::Purge);
+                Change {
+                    name: cache.fullname_of(change.package).unwrap_or_default().to_string(),
+                    change,
+                    old_version: pkg.as_ref().and_then(rust_apt::Package::installed).map(|v| v.version().to_string()),
+                    new_version: pkg.as_ref()
+                        .and_then(rust_apt::Package::candidate)
+                        .filter(|_| !removal)
+                        .map(|v| v.version().to_string()),
+                }
+            })
+            .collect();
+
+        Self { changes, totals }
+    }
+}
+
+/// Marked packages and the plan that resolves them
+#[derive(Serialize)]
+pub struct Status<'a> {
+    pub marked: &'a [PackageInfo],
+    pub upgradable_count: usize,
+    pub plan: Plan<'a>,
+}
+
+/// `apply --dry-run`: the plan and its simulation
+#[derive(Serialize)]
+pub struct DryRun<'a> {
+    pub plan: Plan<'a>,
+    pub simulation: &'a Simulation,
+}
+
+/// Outcome of `apply`: the plan, whether it was applied and what apt and
+/// dpkg printed
+#[derive(Serialize)]
+pub struct Applied {
+    pub plan: serde_json::Value,
+    pub applied: bool,
+    pub error: Option<String>,
+    pub output: Vec<String>,
+}
+
+/// Outcome of toggling one package's mark
+#[derive(Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Toggle {
+    Marked { preview: Preview },
+    Unmarked { package: String, also_unmarked: Vec<String> },
+    NoChange { package: String },
+}
+
+/// What marking a package brought into the plan, like `MarkPreview` but
+/// with full names
+#[derive(Serialize)]
+pub struct Preview {
+    pub package_name: String,
+    pub intent: UserIntent,
+    pub is_upgrade: bool,
+    pub is_downgrade: bool,
+    pub is_marking: bool,
+    pub was_user_marked: bool,
+    pub additional_installs: Vec<String>,
+    pub additional_upgrades: Vec<String>,
+    pub additional_removes: Vec<String>,
+    pub download_size: u64,
+}
+
+impl Preview {
+    /// Preview of marking `package`; `previously_planned` as for
+    /// `ManagerState::build_mark_preview`
+    pub fn new(core: &ManagerState, package: PackageId, previously_planned: &HashSet<PackageId>) -> Self {
+        let preview = core.build_mark_preview(package, previously_planned, &[]).unwrap_or_default();
+        let cache = core.cache();
+        let name_of = |id: PackageId| cache.fullname_of(id).unwrap_or("(unknown)").to_string();
+
+        let mut additional_installs = Vec::new();
+        let mut additional_upgrades = Vec::new();
+        let mut additional_removes = Vec::new();
+        let added = core.planned_changes().unwrap_or_default().iter()
+            .filter(|c| c.package != package && !previously_planned.contains(&c.package));
+        for change in added {
+            match change.action {
+                ChangeAction::Install => additional_installs.push(name_of(change.package)),
+                ChangeAction::Upgrade | ChangeAction::Downgrade => additional_upgrades.push(name_of(change.package)),
+                ChangeAction::Remove | ChangeAction::Purge => additional_removes.push(name_of(change.package)),
+            }
+        }
+
+        Self {
+            package_name: name_of(package),
+            intent: preview.intent,
+            is_upgrade: preview.is_upgrade,
+            is_downgrade: preview.is_downgrade,
+            is_marking: preview.is_marking,
+            was_user_marked: preview.was_user_marked,
+            additional_installs,
+            additional_upgrades,
+            additional_removes,
+            download_size: preview.download_size,
+        }
+    }
+}
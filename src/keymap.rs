@@ -0,0 +1,549 @@
+//! Key bindings
+//!
+//! Input is dispatched through a table of named actions per `AppState`.
+//! Every state has default bindings, and the `[keys.<context>]` sections of
+//! config.toml replace the keys of individual actions:
+//!
+//! ```toml
+//! [keys.listing]
+//! install = "+"
+//! toggle = ["Space", "Enter"]
+//! changelog = []            # unbind
+//! ```
+//!
+//! The same table drives the help overlay and the key hints, so what is shown
+//! is always what is bound.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::types::AppState;
+
+/// `[keys]` section of config.toml: context name -> action name -> keys
+pub type KeysConfig = BTreeMap<String, BTreeMap<String, KeySpec>>;
+
+/// One key or a list of keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpec {
+    fn keys(&self) -> &[String] {
+        match self {
+            Self::One(key) => std::slice::from_ref(key),
+            Self::Many(keys) => keys,
+        }
+    }
+}
+
+// ============================================================================
+// Actions
+// ============================================================================
+
+/// Something a key can do. What an action means depends on the state it is
+/// bound in (e.g. `confirm` applies a mark preview or picks a version).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Cancel,
+    Confirm,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
+    FocusNext,
+    FocusPrev,
+    Search,
+    DeleteChar,
+    Toggle,
+    Install,
+    Remove,
+    Purge,
+    Hold,
+    Versions,
+    VisualMode,
+    PrevTab,
+    NextTab,
+    Changelog,
+    ToggleHistory,
+    Settings,
+    ShowChanges,
+    Apply,
+    PurgeResidual,
+    UpgradeAll,
+    UnmarkAll,
+    Update,
+    Refresh,
+    Help,
+}
+
+impl Action {
+    /// Name used in config.toml
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Cancel => "cancel",
+            Self::Confirm => "confirm",
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::PageUp => "page_up",
+            Self::PageDown => "page_down",
+            Self::First => "first",
+            Self::Last => "last",
+            Self::FocusNext => "focus_next",
+            Self::FocusPrev => "focus_prev",
+            Self::Search => "search",
+            Self::DeleteChar => "delete_char",
+            Self::Toggle => "toggle",
+            Self::Install => "install",
+            Self::Remove => "remove",
+            Self::Purge => "purge",
+            Self::Hold => "hold",
+            Self::Versions => "versions",
+            Self::VisualMode => "visual_mode",
+            Self::PrevTab => "prev_tab",
+            Self::NextTab => "next_tab",
+            Self::Changelog => "changelog",
+            Self::ToggleHistory => "toggle_history",
+            Self::Settings => "settings",
+            Self::ShowChanges => "show_changes",
+            Self::Apply => "apply",
+            Self::PurgeResidual => "purge_residual",
+            Self::UpgradeAll => "upgrade_all",
+            Self::UnmarkAll => "unmark_all",
+            Self::Update => "update",
+            Self::Refresh => "refresh",
+            Self::Help => "help",
+        }
+    }
+}
+
+/// Default bindings per state with their descriptions, in the order they
+/// are listed in the help overlay
+fn default_bindings(state: AppState) -> &'static [(Action, &'static [&'static str], &'static str)] {
+    const SCROLL: [(Action, &[&str], &str); 4] = [
+        (Action::Up, &["Up", "k"], "Scroll up"),
+        (Action::Down, &["Down", "j"], "Scroll down"),
+        (Action::PageUp, &["PageUp"], "Scroll a page up"),
+        (Action::PageDown, &["PageDown"], "Scroll a page down"),
+    ];
+    const HELP: (Action, &[&str], &str) = (Action::Help, &["?", "F1"], "Show this help");
+
+    match state {
+        AppState::Listing => &[
+            (Action::Up, &["Up", "k"], "Move up"),
+            (Action::Down, &["Down", "j"], "Move down"),
+            (Action::PageUp, &["PageUp"], "Page up"),
+            (Action::PageDown, &["PageDown"], "Page down"),
+            (Action::First, &["Home", "g"], "First package"),
+            (Action::Last, &["End", "G"], "Last package"),
+            (Action::FocusNext, &["Tab"], "Focus next pane"),
+            (Action::FocusPrev, &["Shift+Tab"], "Focus previous pane"),
+            (Action::PrevTab, &["Left", "h", "d"], "Previous details tab"),
+            (Action::NextTab, &["Right", "l"], "Next details tab"),
+            (Action::Search, &["/"], "Search (filter files when the Files tab is focused)"),
+            (Action::Cancel, &["Esc"], "Leave visual mode / clear search"),
+            (Action::Toggle, &["Space"], "Mark or unmark (visual mode: marked rows)"),
+            (Action::Install, &["+"], "Mark for install/upgrade"),
+            (Action::Remove, &["-"], "Mark for removal"),
+            (Action::Purge, &["_"], "Mark for purge (removes config files)"),
+            (Action::Hold, &["="], "Hold or release at the installed version"),
+            (Action::Versions, &["V"], "Pick a version to install"),
+            (Action::VisualMode, &["v"], "Visual mode (select a range)"),
+            (Action::UpgradeAll, &["x"], "Mark all upgrades"),
+            (Action::UnmarkAll, &["N"], "Unmark everything"),
+            (Action::ShowChanges, &["u"], "Review and apply changes"),
+            (Action::Changelog, &["c"], "Changelog"),
+            (Action::Settings, &["s"], "Settings"),
+            (Action::Update, &["U"], "Update package lists (apt update)"),
+            (Action::Refresh, &["r"], "Reload the package cache"),
+            HELP,
+            (Action::Quit, &["q", "Ctrl+c"], "Quit"),
+        ],
+        AppState::Searching => &[
+            (Action::Confirm, &["Enter"], "Keep the results"),
+            (Action::Cancel, &["Esc"], "Cancel the search"),
+            (Action::DeleteChar, &["Backspace"], "Delete the last character"),
+            (Action::Help, &["F1"], "Show this help"),
+        ],
+        AppState::FilteringFiles => &[
+            (Action::Confirm, &["Enter"], "Keep the filter"),
+            (Action::Cancel, &["Esc"], "Clear the filter"),
+            (Action::DeleteChar, &["Backspace"], "Delete the last character"),
+            (Action::Help, &["F1"], "Show this help"),
+        ],
+        AppState::ShowingMarkConfirm => &[
+            (Action::Confirm, &["y", "Enter", "Space"], "Mark with the listed changes"),
+            (Action::Cancel, &["n", "Esc"], "Cancel"),
+            SCROLL[0], SCROLL[1], SCROLL[2], SCROLL[3],
+            HELP,
+        ],
+        AppState::ShowingChanges => &[
+            (Action::Apply, &["y", "Enter"], "Apply the changes"),
+            (Action::Cancel, &["n", "Esc"], "Back to the package list"),
+            (Action::NextTab, &["Tab", "Right", "l"], "Switch between the changes and the dry run"),
+            (Action::PrevTab, &["Shift+Tab", "Left", "h"], "Switch between the changes and the dry run"),
+            (Action::Changelog, &["c"], "Changelogs of all upgrades"),
+            (Action::PurgeResidual, &["P"], "Purge residual config"),
+            SCROLL[0], SCROLL[1], SCROLL[2], SCROLL[3],
+            HELP,
+        ],
+        AppState::ShowingChangelog => &[
+            (Action::ToggleHistory, &["f"], "Full history / installed-to-candidate delta"),
+            (Action::Cancel, &["Esc", "q"], "Close (cancels loading)"),
+            SCROLL[0], SCROLL[1], SCROLL[2], SCROLL[3],
+            HELP,
+        ],
+        AppState::ShowingVersions => &[
+            (Action::Up, &["Up", "k"], "Move up"),
+            (Action::Down, &["Down", "j"], "Move down"),
+            (Action::Confirm, &["Enter", "Space"], "Install this version"),
+            (Action::Cancel, &["Esc", "q"], "Close"),
+            HELP,
+        ],
+        AppState::ShowingSettings => &[
+            (Action::Up, &["Up", "k"], "Move up"),
+            (Action::Down, &["Down", "j"], "Move down"),
+            (Action::Toggle, &["Enter", "Space"], "Toggle or cycle the setting"),
+            (Action::Cancel, &["Esc", "q"], "Close"),
+            HELP,
+        ],
+        AppState::ShowingHelp => &[
+            SCROLL[0], SCROLL[1], SCROLL[2], SCROLL[3],
+            (Action::Cancel, &["Esc", "q", "?", "F1"], "Close help"),
+        ],
+        AppState::ConfirmExit => &[
+            (Action::Confirm, &["y", "Enter"], "Quit and discard the changes"),
+            (Action::Cancel, &["n", "Esc"], "Keep working"),
+            HELP,
+        ],
+        AppState::Upgrading => &[],
+        AppState::Done => &[
+            SCROLL[0], SCROLL[1], SCROLL[2], SCROLL[3],
+            (Action::Refresh, &["r"], "Back to the package list"),
+            HELP,
+            (Action::Quit, &["q"], "Quit"),
+        ],
+    }
+}
+
+/// Config names of the key contexts
+const CONTEXTS: [(&str, AppState); 12] = [
+    ("listing", AppState::Listing),
+    ("searching", AppState::Searching),
+    ("file_filter", AppState::FilteringFiles),
+    ("mark_confirm", AppState::ShowingMarkConfirm),
+    ("changes", AppState::ShowingChanges),
+    ("changelog", AppState::ShowingChangelog),
+    ("versions", AppState::ShowingVersions),
+    ("settings", AppState::ShowingSettings),
+    ("help", AppState::ShowingHelp),
+    ("confirm_exit", AppState::ConfirmExit),
+    ("upgrading", AppState::Upgrading),
+    ("done", AppState::Done),
+];
+
+// ============================================================================
+// Keys
+// ============================================================================
+
+/// A key with its Ctrl/Alt modifiers. Shift is folded into the key itself
+/// (`G`, `+`, `Shift+Tab`) so bindings match whatever the terminal reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn from_event(event: &KeyEvent) -> Self {
+        let code = match event.code {
+            KeyCode::Tab if event.modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            code => code,
+        };
+        Self::normalized(code, event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT))
+    }
+
+    /// Terminals report Ctrl+letter in lowercase whatever the case typed, so
+    /// "Ctrl+C" means the same key as "Ctrl+c"
+    fn normalized(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Parse "q", "G", "+", "Space", "PageDown", "F1", "Ctrl+c", "Shift+Tab", ...
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // The key itself may be '+', so only split before the last character
+        let (prefix, key) = match text.char_indices().last() {
+            Some((last, _)) if last > 0 => match text[..last].rfind('+') {
+                Some(split) => (&text[..split], &text[split + 1..]),
+                None => ("", text),
+            },
+            _ => ("", text),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut shift = false;
+        for modifier in prefix.split('+').filter(|m| !m.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+                "alt" | "meta" => modifiers |= KeyModifiers::ALT,
+                "shift" => shift = true,
+                _ => return Err(format!("unknown modifier `{modifier}` in `{text}`")),
+            }
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if shift => KeyCode::Char(c.to_ascii_uppercase()),
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "tab" if shift => KeyCode::BackTab,
+                "space" => KeyCode::Char(' '),
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{text}`")),
+                },
+            },
+        };
+
+        Ok(Self::normalized(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+// ============================================================================
+// Keymap
+// ============================================================================
+
+/// The keys bound to one action
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub action: Action,
+    pub keys: Vec<KeyBinding>,
+    pub description: &'static str,
+}
+
+impl Binding {
+    /// Keys as shown to the user, e.g. "y/Enter"
+    pub fn keys_label(&self) -> String {
+        self.keys.iter().map(ToString::to_string).collect::<Vec<_>>().join("/")
+    }
+}
+
+/// Bindings for every state, defaults merged with config overrides
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<AppState, Vec<Binding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_config(&KeysConfig::default()).0
+    }
+}
+
+impl Keymap {
+    /// Build the keymap from config. Returns warnings for unknown contexts,
+    /// actions and keys, and for keys bound to more than one action.
+    pub fn from_config(config: &KeysConfig) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+
+        for context in config.keys() {
+            if !CONTEXTS.iter().any(|(name, _)| name == context) {
+                warnings.push(format!("Unknown key context [keys.{context}] (ignored)"));
+            }
+        }
+
+        let mut bindings = HashMap::new();
+        for (context, state) in CONTEXTS {
+            let mut state_bindings: Vec<Binding> = default_bindings(state)
+                .iter()
+                .map(|(action, keys, description)| Binding {
+                    action: *action,
+                    keys: keys.iter()
+                        .map(|key| KeyBinding::parse(key).expect("default key bindings are valid"))
+                        .collect(),
+                    description,
+                })
+                .collect();
+            let mut overridden = vec![false; state_bindings.len()];
+
+            for (name, spec) in config.get(context).into_iter().flatten() {
+                let Some(index) = state_bindings.iter().position(|b| b.action.name() == name) else {
+                    warnings.push(format!("Unknown action `{name}` in [keys.{context}] (ignored)"));
+                    continue;
+                };
+                let mut keys = Vec::new();
+                for key in spec.keys() {
+                    match KeyBinding::parse(key) {
+                        Ok(key) => keys.push(key),
+                        Err(e) => warnings.push(format!("[keys.{context}] {name}: {e} (ignored)")),
+                    }
+                }
+                state_bindings[index].keys = keys;
+                overridden[index] = true;
+            }
+
+            resolve_conflicts(&mut state_bindings, &overridden, context, &mut warnings);
+            bindings.insert(state, state_bindings);
+        }
+
+        (Self { bindings }, warnings)
+    }
+
+    /// The action bound to a key in the given state
+    pub fn action(&self, state: AppState, event: &KeyEvent) -> Option<Action> {
+        let key = KeyBinding::from_event(event);
+        self.bindings(state).iter()
+            .find(|b| b.keys.contains(&key))
+            .map(|b| b.action)
+    }
+
+    /// Config name of a state's bindings (`[keys.<context>]`)
+    pub fn context_name(state: AppState) -> &'static str {
+        CONTEXTS.iter().find(|(_, s)| *s == state).map_or("", |(name, _)| name)
+    }
+
+    /// Bindings of a state in display order
+    pub fn bindings(&self, state: AppState) -> &[Binding] {
+        self.bindings.get(&state).map_or(&[], Vec::as_slice)
+    }
+
+    /// First key of an action for use in messages, or the action name if unbound
+    pub fn hint(&self, state: AppState, action: Action) -> String {
+        self.bindings(state).iter()
+            .find(|b| b.action == action)
+            .and_then(|b| b.keys.first())
+            .map_or_else(|| action.name().to_string(), ToString::to_string)
+    }
+}
+
+/// A key bound to several actions goes to the first one set in config, or
+/// the first in table order if none was; the others lose it
+fn resolve_conflicts(bindings: &mut [Binding], overridden: &[bool], context: &str, warnings: &mut Vec<String>) {
+    let order: Vec<usize> = (0..bindings.len())
+        .filter(|&i| overridden[i])
+        .chain((0..bindings.len()).filter(|&i| !overridden[i]))
+        .collect();
+
+    let mut owner: Vec<(KeyBinding, Action)> = Vec::new();
+    for i in order {
+        let action = bindings[i].action;
+        bindings[i].keys.retain(|key| match owner.iter().find(|(k, _)| k == key) {
+            Some((_, winner)) => {
+                warnings.push(format!(
+                    "Key `{key}` is bound to both `{}` and `{}` in [keys.{context}] (using `{}`)",
+                    winner.name(), action.name(), winner.name(),
+                ));
+                false
+            }
+            None => {
+                owner.push((*key, action));
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding { code, modifiers }
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        assert_eq!(KeyBinding::parse("+"), Ok(key(KeyCode::Char('+'), KeyModifiers::NONE)));
+        assert_eq!(KeyBinding::parse("Ctrl++"), Ok(key(KeyCode::Char('+'), KeyModifiers::CONTROL)));
+        assert_eq!(KeyBinding::parse("Shift+Tab"), Ok(key(KeyCode::BackTab, KeyModifiers::NONE)));
+        assert_eq!(KeyBinding::parse("F1"), Ok(key(KeyCode::F(1), KeyModifiers::NONE)));
+        assert_eq!(KeyBinding::parse("Alt+Space"), Ok(key(KeyCode::Char(' '), KeyModifiers::ALT)));
+        assert_eq!(KeyBinding::parse("Hyper+x"), Err("unknown modifier `Hyper` in `Hyper+x`".to_string()));
+        assert_eq!(KeyBinding::parse("F25"), Err("unknown key `F25`".to_string()));
+    }
+
+    #[test]
+    fn ctrl_letters_match_what_the_terminal_sends() {
+        let event = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(KeyBinding::parse("Ctrl+C"), Ok(KeyBinding::from_event(&event)));
+        assert_eq!(KeyBinding::parse("Ctrl+c"), Ok(KeyBinding::from_event(&event)));
+        // Without Ctrl the case is the key
+        assert_ne!(KeyBinding::parse("G"), KeyBinding::parse("g"));
+    }
+
+    #[test]
+    fn an_override_beats_the_default_and_duplicates_warn() {
+        let config: KeysConfig = toml::from_str(r#"
+            [listing]
+            install = "u"
+            hold = ["u", "H"]
+            nonsense = "z"
+        "#).unwrap();
+        let (keymap, warnings) = Keymap::from_config(&config);
+
+        let press = |c| keymap.action(AppState::Listing, &KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        // `u` was show_changes; both overrides want it and install comes first
+        assert_eq!(press('u'), Some(Action::Install));
+        assert_eq!(press('H'), Some(Action::Hold));
+        assert_eq!(press('+'), None);
+        assert_eq!(keymap.hint(AppState::Listing, Action::ShowChanges), Action::ShowChanges.name());
+
+        assert_eq!(warnings, [
+            "Unknown action `nonsense` in [keys.listing] (ignored)",
+            "Key `u` is bound to both `install` and `hold` in [keys.listing] (using `install`)",
+            "Key `u` is bound to both `install` and `show_changes` in [keys.listing] (using `install`)",
+        ]);
+    }
+}
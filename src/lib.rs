@@ -3,7 +3,20 @@
 //! This library exposes the core modules for use by the debug CLI and tests.
 
 pub mod apt;
+pub mod backend;
+pub mod changelog;
+pub mod config;
 pub mod core;
+pub mod deps;
+pub mod fake;
+pub mod files;
+pub mod filter;
+pub mod json;
+pub mod keymap;
 pub mod progress;
+pub mod query;
+pub mod root;
 pub mod search;
+pub mod simulate;
+pub mod theme;
 pub mod types;
@@ -1,10 +1,11 @@
 mod app;
+mod cli;
 mod ui;
 
 use std::io;
 
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
@@ -13,13 +14,37 @@ use ratatui::prelude::*;
 
 use app::App;
 use synh8::core::is_root;
+use synh8::keymap::Action;
 use synh8::types::*;
 use ui::ui;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    if !is_root() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let root = match cli::parse(&args) {
+        Ok(cli::Invocation::Tui { root }) => root,
+        Ok(cli::Invocation::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(cli::Invocation::Version) => {
+            println!("synh8 {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Ok(cli::Invocation::Run { command, json, root }) => {
+            enter_root(root.as_ref());
+            std::process::exit(cli::run(command, json)?)
+        }
+        Err(e) => {
+            eprintln!("synh8: {e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    enter_root(root.as_ref());
+    // An alternate root may well belong to the user
+    if root.is_none() && !is_root() {
         eprintln!("synh8 must be run as root. Try: sudo {}", std::env::args().next().unwrap_or_else(|| "synh8".into()));
         std::process::exit(1);
     }
@@ -31,6 +56,8 @@ fn main() -> Result<()> {
     let mut app = App::new()?;
 
     loop {
+        app.poll_changelog();
+        app.poll_files();
         terminal.draw(|f| ui(f, &mut app))?;
 
         if event::poll(std::time::Duration::from_millis(100))?
@@ -39,29 +66,45 @@ fn main() -> Result<()> {
                     continue;
                 }
 
+                let Some(action) = app.keymap.action(app.state, &key) else {
+                    // Unbound characters are typed into the search query or file filter
+                    if let KeyCode::Char(c) = key.code {
+                        match app.state {
+                            AppState::Searching => {
+                                app.core.search_query_push(c);
+                                app.execute_search();
+                            }
+                            AppState::FilteringFiles => app.file_filter_push(c),
+                            _ => {}
+                        }
+                    }
+                    continue;
+                };
+
+                if action == Action::Help {
+                    app.show_help();
+                    continue;
+                }
+
                 match app.state {
-                    AppState::Listing => match key.code {
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    AppState::Listing => match action {
+                        Action::Quit => {
                             if app.has_pending_changes() {
                                 app.state = AppState::ConfirmExit;
                             } else {
                                 break;
                             }
                         }
-                        KeyCode::Char('q') => {
-                            if app.has_pending_changes() {
-                                app.state = AppState::ConfirmExit;
+                        Action::FocusNext => app.cycle_focus(),
+                        Action::FocusPrev => app.cycle_focus_back(),
+                        Action::Search => {
+                            if app.ui.focused_pane == FocusedPane::Details && app.details.tab == DetailsTab::Files {
+                                app.start_file_filter();
                             } else {
-                                break;
+                                app.start_search();
                             }
                         }
-                        KeyCode::Tab if key.modifiers.contains(KeyModifiers::SHIFT) => {
-                            app.cycle_focus_back();
-                        }
-                        KeyCode::Tab => app.cycle_focus(),
-                        KeyCode::BackTab => app.cycle_focus_back(),
-                        KeyCode::Char('/') => app.start_search(),
-                        KeyCode::Esc => {
+                        Action::Cancel => {
                             if app.ui.visual_mode {
                                 // Cancel visual mode
                                 app.cancel_visual_mode();
@@ -72,7 +115,7 @@ fn main() -> Result<()> {
                                 app.update_status_message();
                             }
                         }
-                        KeyCode::Up | KeyCode::Char('k') => match app.ui.focused_pane {
+                        Action::Up => match app.ui.focused_pane {
                             FocusedPane::Filters => app.move_filter_selection(-1),
                             FocusedPane::Packages => {
                                 app.move_package_selection(-1);
@@ -82,7 +125,7 @@ fn main() -> Result<()> {
                                 app.details.scroll = app.details.scroll.saturating_sub(1);
                             }
                         },
-                        KeyCode::Down | KeyCode::Char('j') => match app.ui.focused_pane {
+                        Action::Down => match app.ui.focused_pane {
                             FocusedPane::Filters => app.move_filter_selection(1),
                             FocusedPane::Packages => {
                                 app.move_package_selection(1);
@@ -92,141 +135,161 @@ fn main() -> Result<()> {
                                 app.details.scroll = app.details.scroll.saturating_add(1);
                             }
                         },
-                        KeyCode::PageDown => {
+                        Action::PageDown => {
                             app.move_package_selection(10);
                             app.update_visual_selection();
                         }
-                        KeyCode::PageUp => {
+                        Action::PageUp => {
                             app.move_package_selection(-10);
                             app.update_visual_selection();
                         }
-                        KeyCode::Home | KeyCode::Char('g') => {
+                        Action::First => {
                             app.select_first_package();
                             app.update_visual_selection();
                         }
-                        KeyCode::End | KeyCode::Char('G') => {
+                        Action::Last => {
                             app.select_last_package();
                             app.update_visual_selection();
                         }
-                        KeyCode::Char(' ') => {
+                        Action::Toggle => {
                             if app.ui.visual_mode {
                                 app.toggle_multi_select();
                             } else {
                                 app.toggle_current();
                             }
                         }
-                        KeyCode::Char('v') => app.start_visual_mode(),
-                        KeyCode::Char('d') | KeyCode::Left | KeyCode::Char('h') => {
-                            app.prev_details_tab();
-                        }
-                        KeyCode::Right | KeyCode::Char('l') => app.next_details_tab(),
-                        KeyCode::Char('c') => app.show_changelog(),
-                        KeyCode::Char('s') => app.show_settings(),
-                        KeyCode::Char('u') => app.show_changes_preview(),
-                        KeyCode::Char('x') => app.mark_all_upgrades(),
-                        KeyCode::Char('N') => app.unmark_all(),
-                        KeyCode::Char('U') => {
+                        Action::Install => app.install_current(),
+                        Action::Remove => app.remove_current(false),
+                        Action::Purge => app.remove_current(true),
+                        Action::Hold => app.toggle_hold(),
+                        Action::Versions => app.show_versions(),
+                        Action::VisualMode => app.start_visual_mode(),
+                        Action::PrevTab => app.prev_details_tab(),
+                        Action::NextTab => app.next_details_tab(),
+                        Action::Changelog => app.show_changelog(),
+                        Action::Settings => app.show_settings(),
+                        Action::ShowChanges => app.show_changes_preview(),
+                        Action::UpgradeAll => app.mark_all_upgrades(),
+                        Action::UnmarkAll => app.unmark_all(),
+                        Action::Update => {
                             // apt update with live progress
                             app.update_packages_live()?;
                             terminal.clear()?;
                         }
-                        KeyCode::Char('r') => {
+                        Action::Refresh => {
                             if let Err(e) = app.refresh_cache() {
                                 app.status_message = format!("Refresh failed: {e}");
                             }
                         }
                         _ => {}
                     },
-                    AppState::Searching => match key.code {
-                        KeyCode::Esc => app.cancel_search(),
-                        KeyCode::Enter => app.confirm_search(),
-                        KeyCode::Backspace => {
+                    AppState::Searching => match action {
+                        Action::Cancel => app.cancel_search(),
+                        Action::Confirm => app.confirm_search(),
+                        Action::DeleteChar => {
                             app.core.search_query_pop();
                             app.execute_search();
                         }
-                        KeyCode::Char(c) => {
-                            app.core.search_query_push(c);
-                            app.execute_search();
-                        }
                         _ => {}
                     },
-                    AppState::ShowingMarkConfirm => match key.code {
-                        KeyCode::Char('y') | KeyCode::Enter | KeyCode::Char(' ') => {
-                            app.confirm_mark();
-                        }
-                        KeyCode::Char('n') | KeyCode::Esc => app.cancel_mark(),
-                        KeyCode::Up | KeyCode::Char('k') => app.scroll_mark_confirm(-1),
-                        KeyCode::Down | KeyCode::Char('j') => app.scroll_mark_confirm(1),
-                        KeyCode::PageUp => app.scroll_mark_confirm(-10),
-                        KeyCode::PageDown => app.scroll_mark_confirm(10),
+                    AppState::FilteringFiles => match action {
+                        Action::Cancel => app.cancel_file_filter(),
+                        Action::Confirm => app.state = AppState::Listing,
+                        Action::DeleteChar => app.file_filter_pop(),
+                        _ => {}
+                    },
+                    AppState::ShowingMarkConfirm => match action {
+                        Action::Confirm => app.confirm_mark(),
+                        Action::Cancel => app.cancel_mark(),
+                        Action::Up => app.scroll_mark_confirm(-1),
+                        Action::Down => app.scroll_mark_confirm(1),
+                        Action::PageUp => app.scroll_mark_confirm(-10),
+                        Action::PageDown => app.scroll_mark_confirm(10),
                         _ => {}
                     },
-                    AppState::ShowingChanges => match key.code {
-                        KeyCode::Char('y') | KeyCode::Enter => {
+                    AppState::ShowingChanges => match action {
+                        Action::Apply if app.has_pending_changes() => {
                             // Clear the confirmation dialog before showing progress
                             terminal.clear()?;
                             app.commit_changes_live()?;
                             terminal.clear()?;
                         }
-                        KeyCode::Char('n') | KeyCode::Esc => {
+                        Action::Cancel => {
                             app.state = AppState::Listing;
                         }
-                        KeyCode::Up | KeyCode::Char('k') => app.scroll_changes(-1),
-                        KeyCode::Down | KeyCode::Char('j') => app.scroll_changes(1),
-                        KeyCode::PageUp => app.scroll_changes(-10),
-                        KeyCode::PageDown => app.scroll_changes(10),
+                        Action::NextTab | Action::PrevTab => app.switch_changes_tab(),
+                        Action::PurgeResidual => app.purge_residual_configs(),
+                        Action::Changelog => app.show_upgrade_changelog(),
+                        Action::Up => app.scroll_changes(-1),
+                        Action::Down => app.scroll_changes(1),
+                        Action::PageUp => app.scroll_changes(-10),
+                        Action::PageDown => app.scroll_changes(10),
+                        _ => {}
+                    },
+                    AppState::ShowingChangelog => match action {
+                        Action::Cancel => app.close_changelog(),
+                        Action::ToggleHistory => app.toggle_changelog_history(),
+                        Action::Up => app.scroll_changelog(-1),
+                        Action::Down => app.scroll_changelog(1),
+                        Action::PageUp => app.scroll_changelog(-10),
+                        Action::PageDown => app.scroll_changelog(10),
                         _ => {}
                     },
-                    AppState::ShowingChangelog => match key.code {
-                        KeyCode::Esc | KeyCode::Char('q') => {
+                    AppState::ShowingVersions => match action {
+                        Action::Cancel => {
                             app.state = AppState::Listing;
                         }
-                        KeyCode::Up | KeyCode::Char('k') => app.scroll_changelog(-1),
-                        KeyCode::Down | KeyCode::Char('j') => app.scroll_changelog(1),
-                        KeyCode::PageUp => app.scroll_changelog(-10),
-                        KeyCode::PageDown => app.scroll_changelog(10),
+                        Action::Confirm => app.confirm_version(),
+                        Action::Up => app.move_version_selection(-1),
+                        Action::Down => app.move_version_selection(1),
                         _ => {}
                     },
-                    AppState::ConfirmExit => match key.code {
-                        KeyCode::Char('y') | KeyCode::Enter => break,
-                        KeyCode::Char('n') | KeyCode::Esc => {
+                    AppState::ConfirmExit => match action {
+                        Action::Confirm => break,
+                        Action::Cancel => {
                             app.state = AppState::Listing;
                         }
                         _ => {}
                     },
-                    AppState::ShowingSettings => match key.code {
-                        KeyCode::Esc | KeyCode::Char('q') => {
+                    AppState::ShowingSettings => match action {
+                        Action::Cancel => {
                             app.state = AppState::Listing;
                             app.apply_current_filter();
                         }
-                        KeyCode::Up | KeyCode::Char('k') => {
+                        Action::Up => {
                             if app.settings_selection > 0 {
                                 app.settings_selection -= 1;
                             }
                         }
-                        KeyCode::Down | KeyCode::Char('j') => {
+                        Action::Down => {
                             if app.settings_selection < App::settings_item_count() - 1 {
                                 app.settings_selection += 1;
                             }
                         }
-                        KeyCode::Enter | KeyCode::Char(' ') => {
-                            app.toggle_setting();
-                        }
+                        Action::Toggle => app.toggle_setting(),
+                        _ => {}
+                    },
+                    AppState::ShowingHelp => match action {
+                        Action::Cancel => app.close_help(),
+                        Action::Up => app.scroll_help(-1),
+                        Action::Down => app.scroll_help(1),
+                        Action::PageUp => app.scroll_help(-10),
+                        Action::PageDown => app.scroll_help(10),
                         _ => {}
                     },
                     AppState::Upgrading => {}
-                    AppState::Done => match key.code {
-                        KeyCode::Char('q') => break,
-                        KeyCode::Char('r') => {
+                    AppState::Done => match action {
+                        Action::Quit => break,
+                        Action::Refresh => {
                             app.state = AppState::Listing;
                             if let Err(e) = app.refresh_cache() {
                                 app.status_message = format!("Refresh failed: {e}");
                             }
                         }
-                        KeyCode::Up | KeyCode::Char('k') => app.scroll_output(-1),
-                        KeyCode::Down | KeyCode::Char('j') => app.scroll_output(1),
-                        KeyCode::PageUp => app.scroll_output(-10),
-                        KeyCode::PageDown => app.scroll_output(10),
+                        Action::Up => app.scroll_output(-1),
+                        Action::Down => app.scroll_output(1),
+                        Action::PageUp => app.scroll_output(-10),
+                        Action::PageDown => app.scroll_output(10),
                         _ => {}
                     },
                 }
@@ -238,3 +301,12 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Point APT and dpkg at `--root DIR`, if given; exits when DIR is unusable
+fn enter_root(root: Option<&cli::RootArgs>) {
+    if let Some(root) = root
+        && let Err(e) = synh8::root::set(&root.dir, root.chrootless_scripts) {
+            eprintln!("synh8: {e}");
+            std::process::exit(2);
+        }
+}
@@ -16,6 +16,7 @@ use ratatui::widgets::{Block, Borders, Clear, Gauge, Paragraph, Wrap};
 use ratatui::Terminal;
 use rust_apt::raw::{AcqTextStatus, ItemDesc, PkgAcquire};
 
+use crate::theme::Theme;
 use crate::types::PackageInfo;
 
 // ============================================================================
@@ -102,10 +103,11 @@ pub struct ProgressState {
     pub errors: Vec<String>,
     /// Title shown in the modal border
     pub title: String,
+    theme: Theme,
 }
 
 impl ProgressState {
-    pub fn new(title: &str) -> std::io::Result<Self> {
+    pub fn new(title: &str, theme: Theme) -> std::io::Result<Self> {
         let tty = std::fs::OpenOptions::new()
             .write(true)
             .open("/dev/tty")?;
@@ -123,6 +125,7 @@ impl ProgressState {
             install_action: String::new(),
             errors: Vec::new(),
             title: title.to_string(),
+            theme,
         })
     }
 
@@ -138,6 +141,7 @@ impl ProgressState {
         let install_action = &self.install_action;
         let errors = &self.errors;
         let title = &self.title;
+        let theme = &self.theme;
 
         drop(self.terminal.draw(|frame| {
             render_progress_modal(
@@ -152,6 +156,7 @@ impl ProgressState {
                 install_action,
                 errors,
                 title,
+                theme,
             );
         }));
     }
@@ -270,6 +275,7 @@ fn render_progress_modal(
     install_action: &str,
     errors: &[String],
     title: &str,
+    theme: &Theme,
 ) {
     let area = frame.area();
 
@@ -292,9 +298,9 @@ fn render_progress_modal(
     frame.render_widget(Clear, modal_area);
 
     let border_color = match phase {
-        ProgressPhase::Downloading => Color::Cyan,
-        ProgressPhase::Installing => Color::Green,
-        ProgressPhase::Done => Color::Green,
+        ProgressPhase::Downloading => theme.accent,
+        ProgressPhase::Installing => theme.success,
+        ProgressPhase::Done => theme.success,
     };
     let block = Block::default()
         .title(format!(" {title} "))
@@ -330,16 +336,16 @@ fn render_progress_modal(
                 String::new()
             };
             let status = Line::from(vec![
-                Span::styled("Downloading... ", Style::default().fg(Color::Cyan)),
-                Span::styled(format!("{percent:.0}%"), Style::default().fg(Color::White).bold()),
-                Span::styled(speed_str, Style::default().fg(Color::DarkGray)),
+                Span::styled("Downloading... ", Style::default().fg(theme.accent)),
+                Span::styled(format!("{percent:.0}%"), Style::default().fg(theme.text).bold()),
+                Span::styled(speed_str, Style::default().fg(theme.muted)),
             ]);
             frame.render_widget(Paragraph::new(status), chunks[1]);
 
             // Gauge
             let ratio = (percent / 100.0).clamp(0.0, 1.0);
             let gauge = Gauge::default()
-                .gauge_style(Style::default().fg(Color::Cyan).bg(Color::DarkGray))
+                .gauge_style(Style::default().fg(theme.accent).bg(theme.gauge_bg))
                 .ratio(ratio);
             frame.render_widget(gauge, chunks[3]);
 
@@ -350,17 +356,17 @@ fn render_progress_modal(
                     PackageInfo::size_str(current_bytes),
                     PackageInfo::size_str(total_bytes),
                 ),
-                Style::default().fg(Color::DarkGray),
+                Style::default().fg(theme.muted),
             ));
             frame.render_widget(Paragraph::new(detail), chunks[5]);
         }
         ProgressPhase::Installing => {
             // Status: "Installing...  Step 14 / 38"
             let status = Line::from(vec![
-                Span::styled("Installing... ", Style::default().fg(Color::Green)),
+                Span::styled("Installing... ", Style::default().fg(theme.success)),
                 Span::styled(
                     format!("Step {install_steps_done} / {install_total_steps}"),
-                    Style::default().fg(Color::White).bold(),
+                    Style::default().fg(theme.text).bold(),
                 ),
             ]);
             frame.render_widget(Paragraph::new(status), chunks[1]);
@@ -372,26 +378,26 @@ fn render_progress_modal(
                 0.0
             };
             let gauge = Gauge::default()
-                .gauge_style(Style::default().fg(Color::Green).bg(Color::DarkGray))
+                .gauge_style(Style::default().fg(theme.success).bg(theme.gauge_bg))
                 .ratio(ratio);
             frame.render_widget(gauge, chunks[3]);
 
             // Detail: current action
             let detail = Line::from(Span::styled(
                 install_action,
-                Style::default().fg(Color::DarkGray),
+                Style::default().fg(theme.muted),
             ));
             frame.render_widget(Paragraph::new(detail), chunks[5]);
         }
         ProgressPhase::Done => {
             let status = Line::from(Span::styled(
                 "Complete.",
-                Style::default().fg(Color::Green).bold(),
+                Style::default().fg(theme.success).bold(),
             ));
             frame.render_widget(Paragraph::new(status), chunks[1]);
 
             let gauge = Gauge::default()
-                .gauge_style(Style::default().fg(Color::Green).bg(Color::DarkGray))
+                .gauge_style(Style::default().fg(theme.success).bg(theme.gauge_bg))
                 .ratio(1.0);
             frame.render_widget(gauge, chunks[3]);
         }
@@ -404,7 +410,7 @@ fn render_progress_modal(
             .rev()
             .take(4)
             .rev()
-            .map(|e| Line::from(Span::styled(e.as_str(), Style::default().fg(Color::Red))))
+            .map(|e| Line::from(Span::styled(e.as_str(), Style::default().fg(theme.danger))))
             .collect();
         let error_para = Paragraph::new(error_lines)
             .wrap(Wrap { trim: false });
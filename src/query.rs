@@ -0,0 +1,389 @@
+//! Search query language
+//!
+//! ```text
+//! vim -doc section:editors "text editor" OR (name:neovim arch:amd64)
+//! ```
+//!
+//! - Bare words match the name, descriptions, section, source package and
+//!   Provides by prefix; `"..."` matches a phrase
+//! - `name:`, `desc:`, `homepage:`, `source:` and `provides:` restrict a word or
+//!   phrase to one text field
+//! - `section:`, `arch:`, `origin:` and `maintainer:` filter on package metadata,
+//!   ignoring case. A section matches whole or after its component
+//!   (`section:libs` matches `universe/libs`), an arch matches exactly, and
+//!   origin and maintainer match any part (`origin:ppa`, `maintainer:debian`)
+//! - Terms are ANDed; `OR` (or `|`) binds looser, `-` negates a term or group,
+//!   and parentheses group
+//!
+//! A query starting with `/` is a file path instead, looked up by `files.rs`.
+//!
+//! Queries are parsed into an `Expr` tree; `search.rs` turns that into SQL with
+//! bound parameters, so user input never reaches FTS5 or SQL unescaped.
+
+use std::fmt;
+
+/// A field qualifier (`field:value`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Desc,
+    Section,
+    Arch,
+    Origin,
+    Maintainer,
+    Homepage,
+    Source,
+    Provides,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "name" => Self::Name,
+            "desc" | "description" => Self::Desc,
+            "section" => Self::Section,
+            "arch" => Self::Arch,
+            "origin" => Self::Origin,
+            "maintainer" => Self::Maintainer,
+            "homepage" => Self::Homepage,
+            "source" => Self::Source,
+            "provides" => Self::Provides,
+            _ => return None,
+        })
+    }
+
+    /// Whether the field is matched by full-text search rather than as metadata
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Name | Self::Desc | Self::Homepage | Self::Source | Self::Provides)
+    }
+}
+
+/// Parsed query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// A word (matched by prefix) or a quoted phrase, optionally field-qualified
+    Term { field: Option<Field>, text: String, phrase: bool },
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+/// Why a query could not be parsed, with the character column it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.column + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// ============================================================================
+// Lexer
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Term { field: Option<Field>, text: String, phrase: bool },
+    Minus,
+    Or,
+    Open,
+    Close,
+}
+
+fn error(message: impl Into<String>, column: usize) -> ParseError {
+    ParseError { message: message.into(), column }
+}
+
+/// Split a query into tokens, each with its starting column
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push((Token::Open, start));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::Close, start));
+                i += 1;
+            }
+            '|' => {
+                tokens.push((Token::Or, start));
+                i += 1;
+            }
+            '-' => {
+                tokens.push((Token::Minus, start));
+                i += 1;
+            }
+            '"' => {
+                let (text, end) = read_phrase(&chars, i)?;
+                tokens.push((Token::Term { field: None, text, phrase: true }, start));
+                i = end;
+            }
+            _ => {
+                let end = (i..chars.len())
+                    .find(|&j| chars[j].is_whitespace() || matches!(chars[j], '(' | ')' | '"' | '|'))
+                    .unwrap_or(chars.len());
+                let word: String = chars[i..end].iter().collect();
+                i = end;
+
+                if word == "OR" {
+                    tokens.push((Token::Or, start));
+                    continue;
+                }
+
+                let Some((name, value)) = word.split_once(':') else {
+                    tokens.push((Token::Term { field: None, text: word, phrase: false }, start));
+                    continue;
+                };
+
+                let field = Field::parse(&name.to_ascii_lowercase()).ok_or_else(|| error(
+                    format!("Unknown field `{name}:` (use name, desc, section, arch, origin, maintainer, homepage, source or provides)"),
+                    start,
+                ))?;
+
+                if !value.is_empty() {
+                    tokens.push((Token::Term { field: Some(field), text: value.to_string(), phrase: false }, start));
+                } else if chars.get(i) == Some(&'"') {
+                    let (text, end) = read_phrase(&chars, i)?;
+                    tokens.push((Token::Term { field: Some(field), text, phrase: true }, start));
+                    i = end;
+                } else {
+                    return Err(error(format!("Missing value after `{name}:`"), start));
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read a quoted phrase starting at the opening quote. Returns the text and
+/// the index just past the closing quote.
+fn read_phrase(chars: &[char], open: usize) -> Result<(String, usize), ParseError> {
+    let close = (open + 1..chars.len())
+        .find(|&j| chars[j] == '"')
+        .ok_or_else(|| error("Unterminated quote", open))?;
+    let text: String = chars[open + 1..close].iter().collect();
+    if text.trim().is_empty() {
+        return Err(error("Empty phrase", open));
+    }
+    Ok((text, close + 1))
+}
+
+// ============================================================================
+// Parser
+// ============================================================================
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize, // Column just past the input, for errors at the end
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, c)| *c)
+    }
+
+    /// or := and (OR and)*
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            items.push(self.parse_and()?);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Expr::Or(items) })
+    }
+
+    /// and := unary+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut items = Vec::new();
+        while !matches!(self.peek(), None | Some(Token::Or | Token::Close)) {
+            items.push(self.parse_unary()?);
+        }
+
+        match items.len() {
+            0 => Err(match self.peek() {
+                Some(Token::Close) => error("Unexpected `)`", self.column()),
+                _ if self.pos > 0 || self.peek().is_some() => error("`OR` needs a term on both sides", self.column()),
+                _ => error("Empty query", self.column()),
+            }),
+            1 => Ok(items.remove(0)),
+            _ => Ok(Expr::And(items)),
+        }
+    }
+
+    /// unary := -unary | ( or ) | term
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let column = self.column();
+        let Some((token, _)) = self.tokens.get(self.pos).cloned() else {
+            return Err(error("Expected a term", column));
+        };
+        self.pos += 1;
+
+        match token {
+            Token::Minus => {
+                if matches!(self.peek(), None | Some(Token::Or | Token::Close)) {
+                    return Err(error("Nothing to negate after `-`", column));
+                }
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Token::Open => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(error("Missing `)`", column));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::Term { field, text, phrase } => Ok(Expr::Term { field, text, phrase }),
+            Token::Or | Token::Close => Err(error("Expected a term", column)),
+        }
+    }
+}
+
+/// Parse a search query
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0, end: input.chars().count() };
+    let expr = parser.parse_or()?;
+    if parser.peek().is_some() {
+        return Err(error("Unexpected `)`", parser.column()));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    /// Text terms that are not negated, for ranking and highlighting
+    pub fn positive_terms(&self) -> Vec<(Option<Field>, &str, bool)> {
+        let mut terms = Vec::new();
+        self.collect_positive(&mut terms);
+        terms
+    }
+
+    fn collect_positive<'a>(&'a self, terms: &mut Vec<(Option<Field>, &'a str, bool)>) {
+        match self {
+            Self::Term { field, text, phrase } => {
+                if field.is_none_or(|f| f.is_text()) {
+                    terms.push((*field, text, *phrase));
+                }
+            }
+            Self::Not(_) => {}
+            Self::And(items) | Self::Or(items) => {
+                for item in items {
+                    item.collect_positive(terms);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(field: Option<Field>, text: &str) -> Expr {
+        Expr::Term { field, text: text.to_string(), phrase: false }
+    }
+
+    fn phrase(field: Option<Field>, text: &str) -> Expr {
+        Expr::Term { field, text: text.to_string(), phrase: true }
+    }
+
+    fn parse_error(input: &str) -> (String, usize) {
+        let e = parse(input).unwrap_err();
+        (e.message, e.column)
+    }
+
+    #[test]
+    fn field_prefixes() {
+        assert_eq!(parse("name:vim").unwrap(), word(Some(Field::Name), "vim"));
+        assert_eq!(parse("Section:libs").unwrap(), word(Some(Field::Section), "libs"));
+        assert_eq!(parse("description:editor").unwrap(), word(Some(Field::Desc), "editor"));
+        // Only the first colon separates the field
+        assert_eq!(parse("origin:ppa:foo").unwrap(), word(Some(Field::Origin), "ppa:foo"));
+        assert_eq!(
+            parse("arch:amd64 -section:games").unwrap(),
+            Expr::And(vec![
+                word(Some(Field::Arch), "amd64"),
+                Expr::Not(Box::new(word(Some(Field::Section), "games"))),
+            ]),
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(parse("\"text editor\"").unwrap(), phrase(None, "text editor"));
+        assert_eq!(parse("desc:\"text editor\"").unwrap(), phrase(Some(Field::Desc), "text editor"));
+        // A quote ends a bare word
+        assert_eq!(
+            parse("vim\"text editor\"").unwrap(),
+            Expr::And(vec![word(None, "vim"), phrase(None, "text editor")]),
+        );
+        assert_eq!(parse_error("\"text editor"), ("Unterminated quote".to_string(), 0));
+        assert_eq!(parse_error("vim \"  \""), ("Empty phrase".to_string(), 4));
+        assert_eq!(parse_error("name:"), ("Missing value after `name:`".to_string(), 0));
+    }
+
+    #[test]
+    fn or_binds_looser_than_and() {
+        assert_eq!(
+            parse("a b OR c | (d -e)").unwrap(),
+            Expr::Or(vec![
+                Expr::And(vec![word(None, "a"), word(None, "b")]),
+                word(None, "c"),
+                Expr::And(vec![word(None, "d"), Expr::Not(Box::new(word(None, "e")))]),
+            ]),
+        );
+        // Only upper-case OR is an operator
+        assert_eq!(parse("a or").unwrap(), Expr::And(vec![word(None, "a"), word(None, "or")]));
+    }
+
+    #[test]
+    fn malformed_queries_are_rejected() {
+        assert_eq!(parse_error(""), ("Empty query".to_string(), 0));
+        assert_eq!(parse_error("a OR"), ("`OR` needs a term on both sides".to_string(), 4));
+        assert_eq!(parse_error("(a"), ("Missing `)`".to_string(), 0));
+        assert_eq!(parse_error("a)"), ("Unexpected `)`".to_string(), 1));
+        assert_eq!(parse_error("a -"), ("Nothing to negate after `-`".to_string(), 2));
+        // FTS5 column filters are not fields
+        assert!(parse_error("summary:vim").0.starts_with("Unknown field `summary:`"));
+        assert_eq!(parse_error("{name}:vim").1, 0);
+    }
+
+    #[test]
+    fn fts_syntax_stays_literal() {
+        // FTS5 operators and specials are plain words; search.rs quotes them
+        assert_eq!(
+            parse("NEAR foo* ^bar AND NOT").unwrap(),
+            Expr::And(["NEAR", "foo*", "^bar", "AND", "NOT"].iter().map(|t| word(None, t)).collect()),
+        );
+        assert_eq!(parse("\"a \"\"").unwrap_err().message, "Unterminated quote");
+        assert_eq!(parse("name:\"x OR y\"").unwrap(), phrase(Some(Field::Name), "x OR y"));
+    }
+
+    #[test]
+    fn positive_terms_skip_negations_and_metadata() {
+        let expr = parse("vim -doc section:editors name:\"neo vim\"").unwrap();
+        assert_eq!(expr.positive_terms(), [
+            (None, "vim", false),
+            (Some(Field::Name), "neo vim", true),
+        ]);
+    }
+}
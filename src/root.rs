@@ -0,0 +1,128 @@
+//! Alternate root directory
+//!
+//! `--root DIR` makes synh8 manage the system installed under DIR (a chroot,
+//! a container image, a synthetic test tree) instead of the host. `set` points
+//! APT's Dir, Dir::State::status and the sources at the tree before the cache
+//! is opened, and passes `--root` on to dpkg. The paths synh8 reads itself
+//! (dpkg's info directory, lock files) go through `path`, the APT lists are
+//! found through APT's own configuration, and the tools it runs get the same
+//! settings from `apt_options`.
+//!
+//! dpkg chroots into the tree to run maintainer scripts, which needs root.
+//! `--no-chroot-scripts` runs them on the host instead, for trees a user
+//! owns.
+//!
+//! Without `--root` everything is the host's, as before.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::core::is_root;
+
+/// The root directory and whether maintainer scripts skip the chroot
+struct Root {
+    dir: PathBuf,
+    chrootless_scripts: bool,
+}
+
+static ROOT: OnceLock<Root> = OnceLock::new();
+
+/// Use `dir` as the root for the rest of the process. Must be called before
+/// the first `AptCache` is opened.
+pub fn set(dir: &Path, chrootless_scripts: bool) -> Result<(), String> {
+    let dir = dir.canonicalize()
+        .map_err(|e| format!("--root {}: {e}", dir.display()))?;
+    if !dir.is_dir() {
+        return Err(format!("--root {}: not a directory", dir.display()));
+    }
+    ROOT.set(Root { dir, chrootless_scripts })
+        .map_err(|_| "The root directory is already set".to_string())?;
+
+    let config = rust_apt::config::Config::new();
+    for (key, value) in apt_settings() {
+        config.set(key, &value);
+    }
+    config.set_vector("Dpkg::Options", &dpkg_options());
+
+    // APT took its architectures from the host's dpkg. The tree's dpkg
+    // records its own, native first, once it has more than one.
+    let archs = tree_architectures();
+    if let Some(native) = archs.first() {
+        config.set("APT::Architecture", native);
+        config.set_vector("APT::Architectures", &archs);
+    }
+    Ok(())
+}
+
+/// Architectures listed in the tree's /var/lib/dpkg/arch
+fn tree_architectures() -> Vec<String> {
+    if get().is_none() {
+        return Vec::new();
+    }
+    std::fs::read_to_string(path("/var/lib/dpkg/arch"))
+        .map(|content| content.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// The alternate root, if one was set
+pub fn get() -> Option<&'static Path> {
+    ROOT.get().map(|root| root.dir.as_path())
+}
+
+/// An absolute path of the managed system, e.g. `/var/lib/dpkg/status`,
+/// as seen from this process
+pub fn path(path: &str) -> PathBuf {
+    match get() {
+        Some(root) => root.join(path.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// `-o` arguments giving apt-get and apt-mark the same view as the cache
+pub fn apt_options() -> Vec<String> {
+    let mut args = Vec::new();
+    for (key, value) in apt_settings() {
+        args.extend(["-o".to_string(), format!("{key}={value}")]);
+    }
+    for option in dpkg_options() {
+        args.extend(["-o".to_string(), format!("Dpkg::Options::={option}")]);
+    }
+    let archs = tree_architectures();
+    if let Some(native) = archs.first() {
+        args.extend(["-o".to_string(), format!("APT::Architecture={native}")]);
+        args.extend(["-o".to_string(), format!("APT::Architectures={}", archs.join(","))]);
+    }
+    args
+}
+
+/// Options dpkg needs to work on the root. Callers that replace
+/// `Dpkg::Options` must include these.
+pub fn dpkg_options() -> Vec<String> {
+    let Some(root) = ROOT.get() else {
+        return Vec::new();
+    };
+    let mut options = vec![format!("--root={}", root.dir.display())];
+    if root.chrootless_scripts {
+        options.push("--force-script-chrootless".to_string());
+        if !is_root() {
+            options.push("--force-not-root".to_string());
+        }
+    }
+    options
+}
+
+/// APT configuration for the root. Dir::State::status and the sources are
+/// set explicitly so host configuration that names them absolutely can't
+/// leak through.
+fn apt_settings() -> Vec<(&'static str, String)> {
+    let Some(root) = get() else {
+        return Vec::new();
+    };
+    let root = root.display();
+    vec![
+        ("Dir", format!("{root}/")),
+        ("Dir::State::status", format!("{root}/var/lib/dpkg/status")),
+        ("Dir::Etc::sourcelist", format!("{root}/etc/apt/sources.list")),
+        ("Dir::Etc::sourceparts", format!("{root}/etc/apt/sources.list.d")),
+    ]
+}
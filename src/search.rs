@@ -1,13 +1,37 @@
 //! Full-text search using SQLite FTS5
+//!
+//! Queries are parsed by `query.rs`; this module stores the packages and
+//! evaluates parsed queries against them.
+//!
+//! The index lives in /var/cache/synh8 so it survives restarts; a `--root`
+//! tree gets its own subdirectory there, keyed by its path. It records a
+//! fingerprint of the APT lists and the dpkg status file; while those are
+//! unchanged it is reused as is, and otherwise only packages whose versions
+//! or origins changed are re-indexed.
 
-use std::collections::HashSet;
-use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use color_eyre::Result;
 use rust_apt::cache::PackageSort;
-use rusqlite::{Connection, params};
+use rust_apt::{Package, Version};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
 
-use crate::apt::AptCache;
+use crate::apt::{AptCache, package_details};
+use crate::files::apt_lists_dir;
+use crate::query::{Expr, Field};
+use crate::root;
+
+/// Directory holding the persistent index, on the host even under `--root`
+const INDEX_DIR: &str = "/var/cache/synh8";
+const INDEX_FILE: &str = "search.db";
+
+/// Bump when the tables or what gets indexed change; older indexes are rebuilt
+const SCHEMA_VERSION: &str = "2";
+
+/// File whose changes can change the indexed packages, besides the APT lists
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
 
 /// SQLite FTS5 search index for packages
 pub struct SearchIndex {
@@ -15,78 +39,382 @@ pub struct SearchIndex {
 }
 
 impl SearchIndex {
+    /// An index that only lives as long as this process
     pub fn new() -> Result<Self> {
-        let conn = Connection::open_in_memory()?;
-        conn.execute(
-            "CREATE VIRTUAL TABLE IF NOT EXISTS packages USING fts5(name, description)",
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// Open the index in /var/cache/synh8, falling back to memory if it
+    /// can't be created or read
+    pub fn open() -> Result<Self> {
+        let dir = index_dir();
+        let on_disk = std::fs::create_dir_all(&dir)
+            .map_err(Into::into)
+            .and_then(|()| Ok(Connection::open(dir.join(INDEX_FILE))?))
+            .and_then(Self::init);
+        match on_disk {
+            Ok(index) => Ok(index),
+            Err(_) => Self::new(),
+        }
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute("CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)", [])?;
+
+        let index = Self { conn };
+        if index.meta("schema")?.as_deref() != Some(SCHEMA_VERSION) {
+            index.conn.execute_batch(
+                "DROP TABLE IF EXISTS packages;
+                 DROP TABLE IF EXISTS stamps;
+                 DELETE FROM meta;",
+            )?;
+        }
+
+        // Column order must match RANK_WEIGHTS. The UNINDEXED columns are
+        // only matched by query predicates.
+        index.conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS packages USING fts5(
+                name, summary, description, section, maintainer, homepage, source, provides,
+                arch UNINDEXED, origin UNINDEXED
+            )",
+            [],
+        )?;
+        // What each package was indexed from, to spot the ones that changed.
+        // Keyed by full name: every architecture of a package has its own row.
+        index.conn.execute(
+            "CREATE TABLE IF NOT EXISTS stamps (fullname TEXT PRIMARY KEY, stamp TEXT NOT NULL, row INTEGER NOT NULL)",
             [],
         )?;
-        Ok(Self { conn })
+        index.set_meta("schema", SCHEMA_VERSION)?;
+        Ok(index)
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.conn
+            .query_row("SELECT value FROM meta WHERE key = ?", [key], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute("INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)", [key, value])?;
+        Ok(())
     }
 
-    /// Build the search index from the APT cache
-    pub fn build(&mut self, apt: &AptCache) -> Result<(usize, Duration)> {
+    /// Bring the index up to date with the APT cache. Returns how many
+    /// packages were (re)indexed or dropped, and how long it took.
+    pub fn sync(&mut self, apt: &AptCache) -> Result<(usize, Duration)> {
         let start = Instant::now();
+        let fingerprint = fingerprint();
+        if self.meta("fingerprint")?.as_deref() == Some(fingerprint.as_str()) {
+            return Ok((0, start.elapsed()));
+        }
+
+        let mut stored: HashMap<String, (String, i64)> = HashMap::new();
+        {
+            let mut stmt = self.conn.prepare("SELECT fullname, stamp, row FROM stamps")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?;
+            stored.extend(rows.flatten());
+        }
+
         let mut count = 0;
 
         // Use transaction for much faster bulk inserts
-        self.conn.execute("BEGIN TRANSACTION", [])?;
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO packages
+                    (name, summary, description, section, maintainer, homepage, source, provides, arch, origin)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            let mut delete = tx.prepare("DELETE FROM packages WHERE rowid = ?")?;
+            let mut set_stamp = tx.prepare("INSERT OR REPLACE INTO stamps (fullname, stamp, row) VALUES (?, ?, ?)")?;
 
-        // Clear existing data
-        self.conn.execute("DELETE FROM packages", [])?;
+            for pkg in apt.packages(&PackageSort::default()) {
+                let name = pkg.name();
+                let fullname = pkg.fullname(false);
+                let version = pkg.candidate().or_else(|| pkg.installed());
+                let origins: Vec<String> = version.as_ref()
+                    .map(|v| v.package_files().filter_map(|pf| pf.origin().map(str::to_string)).collect())
+                    .unwrap_or_default();
+                let stamp = package_stamp(&pkg, &origins);
 
-        // Insert all packages
-        let mut stmt = self.conn.prepare("INSERT INTO packages (name, description) VALUES (?, ?)")?;
+                match stored.remove(&fullname) {
+                    Some((old, _)) if old == stamp => continue,
+                    Some((_, row)) => {
+                        delete.execute([row])?;
+                    }
+                    None => {}
+                }
 
-        for pkg in apt.packages(&PackageSort::default()) {
-            let name = pkg.name();
-            let desc = pkg.candidate()
-                .and_then(|c| c.summary())
-                .unwrap_or_default();
-            stmt.execute(params![name, desc])?;
-            count += 1;
-        }
+                let summary = version.as_ref()
+                    .and_then(Version::summary)
+                    .unwrap_or_default();
+                let section = version.as_ref()
+                    .and_then(|v| v.section().ok().map(str::to_string))
+                    .unwrap_or_default();
+                let arch = version.as_ref().map_or(pkg.arch(), |v| v.arch()).to_string();
+                let details = package_details(&pkg);
+                insert.execute(params![
+                    name,
+                    summary,
+                    details.long_description,
+                    section,
+                    details.maintainer,
+                    details.homepage,
+                    details.source,
+                    details.provides.join(" "),
+                    arch,
+                    origins.join("\n"),
+                ])?;
+                set_stamp.execute(params![fullname, stamp, tx.last_insert_rowid()])?;
+                count += 1;
+            }
 
-        self.conn.execute("COMMIT", [])?;
+            // Whatever is left disappeared from the cache
+            let mut unstamp = tx.prepare("DELETE FROM stamps WHERE fullname = ?")?;
+            for (fullname, (_, row)) in &stored {
+                delete.execute([row])?;
+                unstamp.execute([fullname])?;
+                count += 1;
+            }
+        }
+        tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('fingerprint', ?)", [&fingerprint])?;
+        tx.commit()?;
 
         Ok((count, start.elapsed()))
     }
 
-    /// Search for packages matching the query
-    pub fn search(&self, query: &str) -> Result<HashSet<String>> {
-        let mut results = HashSet::new();
+    /// Find the packages matching a parsed query, with their rank (lower is better).
+    /// Ranks are the bm25 score of the query's text terms, with exact and then
+    /// prefix name matches moved ahead of everything else.
+    pub fn search(&self, query: &Expr) -> Result<HashMap<String, f64>> {
+        let mut params = Vec::new();
+        let condition = to_sql(query, &mut params);
+
+        let mut stmt = self.conn.prepare(&format!("SELECT DISTINCT name FROM packages WHERE {condition}"))?;
+        let rows = stmt.query_map(params_from_iter(&params), |row| row.get::<_, String>(0))?;
+        let mut ranks: HashMap<String, f64> = rows.flatten().map(|name| (name, 0.0)).collect();
 
-        if query.is_empty() {
-            return Ok(results);
+        let terms = query.positive_terms();
+        if terms.is_empty() {
+            return Ok(ranks);
         }
 
-        // Escape special FTS5 characters and add prefix matching
-        let fts_query = query
-            .split_whitespace()
-            .map(|word| {
-                let cleaned: String = word.chars()
-                    .filter(|c| !matches!(c, '"' | '*' | '^' | '(' | ')' | '{' | '}' | ':' | '+'))
-                    .collect();
-                if cleaned.is_empty() {
-                    String::new()
-                } else {
-                    format!("{cleaned}*")
-                }
-            })
-            .filter(|s| !s.is_empty())
+        // Score every row matching any text term; rows that only matched
+        // through metadata predicates keep 0
+        let rank_query = terms.iter()
+            .map(|(field, text, phrase)| format!("{} : {}", fts_columns(*field), fts_string(text, *phrase)))
             .collect::<Vec<_>>()
-            .join(" ");
+            .join(" OR ");
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT name, bm25(packages, {RANK_WEIGHTS}) FROM packages WHERE packages MATCH ?"
+        ))?;
+        let rows = stmt.query_map([&rank_query], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?;
+        for (name, score) in rows.flatten() {
+            if let Some(rank) = ranks.get_mut(&name) {
+                *rank = rank.min(score);
+            }
+        }
 
-        let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT name FROM packages WHERE packages MATCH ?"
-        )?;
+        let name_terms: Vec<String> = terms.iter()
+            .filter(|(field, _, _)| matches!(field, None | Some(Field::Name)))
+            .map(|(_, text, _)| text.to_lowercase())
+            .collect();
+        for (name, rank) in &mut ranks {
+            if name_terms.iter().any(|t| t == name) {
+                *rank -= EXACT_NAME_BOOST;
+            } else if name_terms.iter().any(|t| name.starts_with(t.as_str())) {
+                *rank -= PREFIX_NAME_BOOST;
+            }
+        }
+
+        Ok(ranks)
+    }
+}
+
+/// What a package's indexed row was built from. Package names are per
+/// architecture in APT, so the version and its origins identify the content.
+fn package_stamp(pkg: &Package, origins: &[String]) -> String {
+    let candidate = pkg.candidate().map(|v| v.version().to_string()).unwrap_or_default();
+    let installed = pkg.installed().map(|v| v.version().to_string()).unwrap_or_default();
+    format!("{candidate}|{installed}|{}", origins.join(","))
+}
 
-        let rows = stmt.query_map([&fts_query], |row| row.get::<_, String>(0))?;
+/// Where the index of the managed system lives: INDEX_DIR for the host,
+/// a subdirectory of it for each `--root` tree
+fn index_dir() -> PathBuf {
+    match root::get() {
+        Some(dir) => Path::new(INDEX_DIR).join("roots").join(hash(&dir.to_string_lossy())),
+        None => PathBuf::from(INDEX_DIR),
+    }
+}
+
+/// Size and modification time of every APT list and the dpkg status file,
+/// hashed into a short string
+fn fingerprint() -> String {
+    let mut entries: Vec<String> = std::fs::read_dir(apt_lists_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| file_stamp(&entry.path()))
+        .collect();
+    entries.sort();
+    entries.push(file_stamp(&root::path(DPKG_STATUS)));
+    hash(&entries.join("\n"))
+}
 
-        for name in rows.flatten() {
-            results.insert(name);
+/// FNV-1a hash of `text` as 16 hex digits
+fn hash(text: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+fn file_stamp(path: &Path) -> String {
+    let (len, mtime) = std::fs::metadata(path)
+        .map(|m| {
+            let mtime = m.modified().ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            (m.len(), mtime)
+        })
+        .unwrap_or_default();
+    format!("{} {len} {mtime}", path.display())
+}
+
+/// Rank bonuses for name matches. bm25 scores are small negative numbers,
+/// so these put exact matches first and prefix matches second.
+const EXACT_NAME_BOOST: f64 = 1000.0;
+const PREFIX_NAME_BOOST: f64 = 100.0;
+
+/// bm25 weights of the text columns, in table order: name, summary,
+/// description, section, maintainer, homepage, source, provides
+const RANK_WEIGHTS: &str = "10.0, 4.0, 1.0, 2.0, 0.5, 0.5, 5.0, 3.0";
+
+/// FTS5 column filter for a text term. Unqualified words skip maintainer and
+/// homepage, which would match on e-mail and URL parts.
+fn fts_columns(field: Option<Field>) -> &'static str {
+    match field {
+        Some(Field::Name) => "name",
+        Some(Field::Desc) => "{summary description}",
+        Some(Field::Homepage) => "homepage",
+        Some(Field::Source) => "source",
+        Some(Field::Provides) => "provides",
+        _ => "{name summary description section source provides}",
+    }
+}
+
+/// Translate a query into an SQL condition. Every piece of user text becomes
+/// a bound parameter.
+fn to_sql(expr: &Expr, params: &mut Vec<String>) -> String {
+    match expr {
+        Expr::Term { field, text, phrase } => match field {
+            None | Some(Field::Name | Field::Desc | Field::Homepage | Field::Source | Field::Provides) => {
+                params.push(format!("{} : {}", fts_columns(*field), fts_string(text, *phrase)));
+                "rowid IN (SELECT rowid FROM packages WHERE packages MATCH ?)".to_string()
+            }
+            Some(Field::Section) => {
+                let value = text.to_lowercase();
+                params.push(value.clone());
+                params.push(format!("%/{}", escape_like(&value)));
+                "(lower(section) = ? OR lower(section) LIKE ? ESCAPE '\\')".to_string()
+            }
+            Some(Field::Arch) => {
+                params.push(text.to_lowercase());
+                "arch = ?".to_string()
+            }
+            Some(Field::Origin) => {
+                params.push(format!("%{}%", escape_like(text)));
+                "origin LIKE ? ESCAPE '\\'".to_string()
+            }
+            Some(Field::Maintainer) => {
+                params.push(format!("%{}%", escape_like(text)));
+                "maintainer LIKE ? ESCAPE '\\'".to_string()
+            }
+        },
+        Expr::Not(inner) => format!("NOT ({})", to_sql(inner, params)),
+        Expr::And(items) => join_sql(items, " AND ", params),
+        Expr::Or(items) => join_sql(items, " OR ", params),
+    }
+}
+
+fn join_sql(items: &[Expr], separator: &str, params: &mut Vec<String>) -> String {
+    let parts: Vec<String> = items.iter().map(|item| to_sql(item, params)).collect();
+    format!("({})", parts.join(separator))
+}
+
+/// An FTS5 string: a phrase, or a word matched by prefix
+fn fts_string(text: &str, phrase: bool) -> String {
+    let quoted = format!("\"{}\"", text.replace('"', "\"\""));
+    if phrase { quoted } else { format!("{quoted}*") }
+}
+
+/// Escape LIKE wildcards (with `\` as the escape character)
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query;
+
+    /// An in-memory index holding a few hand-written rows
+    fn index() -> SearchIndex {
+        let index = SearchIndex::new().unwrap();
+        for (name, summary, section, arch, origin) in [
+            ("vim", "Vi IMproved - enhanced vi editor", "editors", "amd64", "Debian"),
+            ("neovim", "heavily refactored vim fork", "universe/editors", "amd64", "Ubuntu"),
+            ("foo_bar", "100% \"quoted\" summary", "libs", "i386", "my_ppa"),
+        ] {
+            index.conn.execute(
+                "INSERT INTO packages (name, summary, description, section, maintainer, homepage, source, provides, arch, origin)
+                 VALUES (?, ?, '', ?, '', '', ?, '', ?, ?)",
+                params![name, summary, section, name, arch, origin],
+            ).unwrap();
         }
+        index
+    }
+
+    fn search(index: &SearchIndex, input: &str) -> Vec<String> {
+        let mut names: Vec<String> = index.search(&query::parse(input).unwrap()).unwrap().into_keys().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn fts_strings_are_quoted() {
+        assert_eq!(fts_string("vim", false), "\"vim\"*");
+        assert_eq!(fts_string("text editor", true), "\"text editor\"");
+        assert_eq!(fts_string("a\"b", false), "\"a\"\"b\"*");
+        assert_eq!(escape_like("100%_a\\b"), "100\\%\\_a\\\\b");
+    }
+
+    #[test]
+    fn fts_syntax_in_queries_reaches_sqlite_escaped() {
+        let index = index();
+        for input in ["NEAR", "foo*", "^vim", "AND", "NOT vim", "name:\"x\"\"y\"", "col:vim", "\"vi IMproved\"", "{name}"] {
+            if let Ok(expr) = query::parse(input) {
+                index.search(&expr).unwrap_or_else(|e| panic!("`{input}` failed: {e}"));
+            }
+        }
+        assert_eq!(search(&index, "vim"), ["neovim", "vim"]);
+        assert_eq!(search(&index, "\"enhanced vi\""), ["vim"]);
+        assert_eq!(search(&index, "\"quoted\""), ["foo_bar"]);
+    }
 
-        Ok(results)
+    #[test]
+    fn metadata_predicates_escape_like_wildcards() {
+        let index = index();
+        assert_eq!(search(&index, "section:editors"), ["neovim", "vim"]);
+        assert_eq!(search(&index, "arch:i386"), ["foo_bar"]);
+        assert_eq!(search(&index, "origin:y_p"), ["foo_bar"]);
+        // `_` and `%` are literal, not LIKE wildcards
+        assert!(search(&index, "origin:m_ppa").is_empty());
+        assert!(search(&index, "origin:%").is_empty());
     }
 }
@@ -0,0 +1,268 @@
+//! Dry-run simulation of a planned transaction
+//!
+//! Hands the resolved changeset to `apt-get -s`, which orders it the way the
+//! real commit would and prints the steps without touching the system:
+//!
+//! ```text
+//! Inst libfoo [1.0-1] (1.1-1 Debian:12/stable [amd64]) [foo ]
+//! Conf libfoo (1.1-1 Debian:12/stable [amd64])
+//! Remv bar [2.0-3]
+//! ```
+//!
+//! Every package is pinned to its planned version so APT has nothing left to
+//! decide. The bracketed list at the end of an `Inst` line names packages
+//! that are broken until a later step configures them.
+//!
+//! `apt-get -s` doesn't run triggers, so they are predicted from dpkg's
+//! records: a package whose installed files lie under a path another package
+//! has an `interest` in, or that `activate`s a named trigger, will run that
+//! trigger. Packages that aren't installed yet have no records and don't
+//! contribute.
+
+use std::collections::{BTreeSet, HashSet};
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::apt::AptCache;
+use crate::files::{self, DPKG_INFO_DIR};
+use crate::root;
+use crate::types::*;
+
+/// What a simulated step does, after apt-get's `Inst`, `Conf`, `Remv` and `Purg`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    Unpack,
+    Configure,
+    Remove,
+    Purge,
+}
+
+impl StepKind {
+    /// apt-get's tag for the step
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::Unpack => "Inst",
+            Self::Configure => "Conf",
+            Self::Remove => "Remv",
+            Self::Purge => "Purg",
+        }
+    }
+}
+
+/// One step of the simulated transaction, in dpkg order
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    pub kind: StepKind,
+    pub package: String,
+    /// Versions and origin as apt-get prints them (`[1.0] (1.1 Debian:12/stable [amd64])`)
+    pub detail: String,
+    /// Packages left broken by this step until a later one fixes them
+    pub breaks: Vec<String>,
+}
+
+/// A trigger the transaction is expected to run
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Trigger {
+    /// Trigger name or the path the package is interested in
+    pub name: String,
+    /// Package whose trigger runs
+    pub package: String,
+}
+
+/// Outcome of a dry run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Simulation {
+    pub steps: Vec<Step>,
+    /// Unmet dependencies apt-get reports for the changeset
+    pub broken: Vec<String>,
+    pub triggers: Vec<Trigger>,
+    /// apt-get's `E:` lines
+    pub errors: Vec<String>,
+}
+
+/// Simulate a planned changeset
+pub fn simulate(cache: &AptCache, changes: &[PlannedChange]) -> Result<Simulation, String> {
+    if changes.is_empty() {
+        return Ok(Simulation::default());
+    }
+
+    let mut targets = Vec::new();
+    let mut purges = HashSet::new();
+    for change in changes {
+        let Some(pkg) = cache.get_by_id(change.package) else {
+            continue;
+        };
+        let name = pkg.fullname(false);
+        match change.action {
+            ChangeAction::Install | ChangeAction::Upgrade | ChangeAction::Downgrade => {
+                if let Some(version) = pkg.candidate() {
+                    targets.push(format!("{name}={}", version.version()));
+                }
+            }
+            ChangeAction::Remove => targets.push(format!("{name}-")),
+            ChangeAction::Purge => {
+                // apt-get only purges everything or nothing; relabel the steps instead
+                purges.insert(cache.display_name(&name).to_string());
+                targets.push(format!("{name}-"));
+            }
+        }
+    }
+
+    let output = Command::new("apt-get")
+        .args(["-s", "-q", "--allow-downgrades", "-o", "APT::Install-Recommends=false"])
+        .args(root::apt_options())
+        .arg("install")
+        .args(&targets)
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| format!("Failed to run apt-get: {e}"))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut simulation = parse_output(&stdout, &stderr);
+    for step in &mut simulation.steps {
+        if step.kind == StepKind::Remove && purges.contains(&step.package) {
+            step.kind = StepKind::Purge;
+        }
+    }
+    if !output.status.success() && simulation.errors.is_empty() {
+        simulation.errors.push(format!("apt-get exited with {}", output.status));
+    }
+    simulation.triggers = predict_triggers(cache, changes);
+    Ok(simulation)
+}
+
+/// Collect steps, unmet dependencies and errors from apt-get's output
+fn parse_output(stdout: &str, stderr: &str) -> Simulation {
+    let mut simulation = Simulation::default();
+    let mut in_unmet = false;
+
+    for line in stdout.lines().chain(stderr.lines()) {
+        if let Some(step) = parse_step(line) {
+            simulation.steps.push(step);
+        } else if line.contains("have unmet dependencies") {
+            in_unmet = true;
+            continue;
+        } else if let Some(error) = line.strip_prefix("E: ") {
+            simulation.errors.push(error.to_string());
+        } else if in_unmet && line.starts_with(' ') {
+            // ` foo : Depends: bar` continues with `       Recommends: baz`
+            let line = line.trim();
+            let owner = simulation.broken.last()
+                .filter(|_| !line.contains(" : "))
+                .and_then(|prev| prev.split_once(" : "))
+                .map(|(owner, _)| owner.to_string());
+            simulation.broken.push(match owner {
+                Some(owner) => format!("{owner} : {line}"),
+                None => line.to_string(),
+            });
+            continue;
+        }
+        in_unmet = false;
+    }
+    simulation
+}
+
+/// Parse an `Inst`, `Conf`, `Remv` or `Purg` line
+fn parse_step(line: &str) -> Option<Step> {
+    let (tag, rest) = line.split_once(' ')?;
+    let kind = match tag {
+        "Inst" => StepKind::Unpack,
+        "Conf" => StepKind::Configure,
+        "Remv" => StepKind::Remove,
+        "Purg" => StepKind::Purge,
+        _ => return None,
+    };
+    let (package, detail) = rest.split_once(' ').unwrap_or((rest, ""));
+
+    // `... (1.1 Debian:12/stable [amd64]) [foo bar ]`
+    let (detail, breaks) = match detail.rsplit_once(") [") {
+        Some((head, broken)) if broken.ends_with(']') => (
+            format!("{head})"),
+            broken.trim_end_matches(']').split_whitespace().map(str::to_string).collect(),
+        ),
+        _ => (detail.to_string(), Vec::new()),
+    };
+    Some(Step { kind, package: package.to_string(), detail, breaks })
+}
+
+/// Triggers that the changed packages' installed files or explicit
+/// activations will set off
+fn predict_triggers(cache: &AptCache, changes: &[PlannedChange]) -> Vec<Trigger> {
+    let interests = trigger_interests();
+    let mut triggers = BTreeSet::new();
+
+    for change in changes {
+        let Some(fullname) = cache.fullname_of(change.package) else {
+            continue;
+        };
+        let (name, arch) = fullname.split_once(':').unwrap_or((fullname, ""));
+
+        // Named triggers the package activates itself (`activate-noawait ldconfig`)
+        for (directive, trigger) in read_triggers(name, arch) {
+            if directive.starts_with("activate") {
+                for (interest, package) in interests.iter().filter(|(i, _)| *i == trigger) {
+                    triggers.insert(Trigger { name: interest.clone(), package: package.clone() });
+                }
+            }
+        }
+
+        // File triggers on the paths it ships
+        let Some(files) = files::installed_files(name, arch) else {
+            continue;
+        };
+        for (interest, package) in interests.iter().filter(|(i, _)| i.starts_with('/')) {
+            let under = |path: &str| path.strip_prefix(interest.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+            if files.iter().any(|f| under(&f.path)) {
+                triggers.insert(Trigger { name: interest.clone(), package: package.clone() });
+            }
+        }
+    }
+    triggers.into_iter().collect()
+}
+
+/// `(trigger, package)` for every `interest` of an installed package
+fn trigger_interests() -> Vec<(String, String)> {
+    let Ok(entries) = std::fs::read_dir(root::path(DPKG_INFO_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut interests = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(package) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".triggers")) else {
+            continue;
+        };
+        let package = package.split(':').next().unwrap_or(package);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for (directive, trigger) in parse_triggers(&content) {
+            if directive.starts_with("interest") {
+                interests.push((trigger, package.to_string()));
+            }
+        }
+    }
+    interests
+}
+
+/// Directives of a package's dpkg triggers file
+fn read_triggers(name: &str, arch: &str) -> Vec<(String, String)> {
+    std::fs::read_to_string(root::path(&format!("{DPKG_INFO_DIR}/{name}:{arch}.triggers")))
+        .or_else(|_| std::fs::read_to_string(root::path(&format!("{DPKG_INFO_DIR}/{name}.triggers"))))
+        .map(|content| parse_triggers(&content))
+        .unwrap_or_default()
+}
+
+/// `interest /usr/share/man` -> ("interest", "/usr/share/man"), skipping comments
+fn parse_triggers(content: &str) -> Vec<(String, String)> {
+    content.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            Some((words.next()?.to_string(), words.next()?.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,235 @@
+//! Color themes
+//!
+//! Renderers never name colors directly; they ask the active `Theme` for a
+//! role (accent, warning, muted, ...). Themes are picked and overridden from
+//! the `[theme]` section of config.toml, and NO_COLOR forces monochrome.
+
+use std::collections::BTreeMap;
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+use crate::types::PackageStatus;
+
+/// Built-in themes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    Monochrome,
+}
+
+impl ThemeName {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+            Self::HighContrast => "High contrast",
+            Self::Monochrome => "Monochrome",
+        }
+    }
+
+    pub fn all() -> &'static [ThemeName] {
+        &[Self::Dark, Self::Light, Self::HighContrast, Self::Monochrome]
+    }
+}
+
+/// `[theme]` section of config.toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub name: ThemeName,
+    /// Per-role overrides, e.g. `muted = "gray"` or `accent = "#5f87af"`
+    pub colors: BTreeMap<String, String>,
+}
+
+/// Colors for every UI role
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub text: Color,         // Emphasized values (percentages, selected names)
+    pub accent: Color,       // Labels, headers, focused borders, dependency changes
+    pub warning: Color,      // Upgradable packages, active tabs, section headings
+    pub success: Color,      // Installs, upgrades, completed operations
+    pub danger: Color,       // Removals, errors, exit confirmation
+    pub special: Color,      // Residual config, auto-removals, mark confirmation
+    pub downgrade: Color,
+    pub held: Color,
+    pub broken: Color,
+    pub muted: Color,        // Hints, help line, unfocused borders, installed packages
+    pub dim: Color,          // Not-installed packages
+    pub title_fg: Color,     // Title bar
+    pub title_bg: Color,
+    pub selection_bg: Color, // Cursor row in lists and tables
+    pub multi_select_bg: Color,
+    pub gauge_bg: Color,
+    /// Extra modifier for the cursor row (reverse video where colors can't show it)
+    pub selection_modifier: Modifier,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin(ThemeName::Dark)
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Self {
+                text: Color::White,
+                accent: Color::Cyan,
+                warning: Color::Yellow,
+                success: Color::Green,
+                danger: Color::Red,
+                special: Color::Magenta,
+                downgrade: Color::LightYellow,
+                held: Color::Blue,
+                broken: Color::LightRed,
+                muted: Color::DarkGray,
+                dim: Color::Gray,
+                title_fg: Color::White,
+                title_bg: Color::Blue,
+                selection_bg: Color::DarkGray,
+                multi_select_bg: Color::Blue,
+                gauge_bg: Color::DarkGray,
+                selection_modifier: Modifier::empty(),
+            },
+            // Darker hues that stay readable on white/cream backgrounds
+            ThemeName::Light => Self {
+                text: Color::Black,
+                accent: Color::Indexed(25),
+                warning: Color::Indexed(130),
+                success: Color::Indexed(28),
+                danger: Color::Indexed(160),
+                special: Color::Indexed(90),
+                downgrade: Color::Indexed(136),
+                held: Color::Indexed(19),
+                broken: Color::Indexed(124),
+                muted: Color::Indexed(240),
+                dim: Color::Indexed(244),
+                title_fg: Color::White,
+                title_bg: Color::Indexed(25),
+                selection_bg: Color::Indexed(252),
+                multi_select_bg: Color::Indexed(153),
+                gauge_bg: Color::Indexed(252),
+                selection_modifier: Modifier::empty(),
+            },
+            ThemeName::HighContrast => Self {
+                text: Color::White,
+                accent: Color::LightCyan,
+                warning: Color::LightYellow,
+                success: Color::LightGreen,
+                danger: Color::LightRed,
+                special: Color::LightMagenta,
+                downgrade: Color::LightYellow,
+                held: Color::LightBlue,
+                broken: Color::LightRed,
+                muted: Color::White,
+                dim: Color::Gray,
+                title_fg: Color::Black,
+                title_bg: Color::White,
+                selection_bg: Color::Reset,
+                multi_select_bg: Color::Blue,
+                gauge_bg: Color::Black,
+                selection_modifier: Modifier::REVERSED,
+            },
+            ThemeName::Monochrome => Self {
+                text: Color::Reset,
+                accent: Color::Reset,
+                warning: Color::Reset,
+                success: Color::Reset,
+                danger: Color::Reset,
+                special: Color::Reset,
+                downgrade: Color::Reset,
+                held: Color::Reset,
+                broken: Color::Reset,
+                muted: Color::Reset,
+                dim: Color::Reset,
+                title_fg: Color::Reset,
+                title_bg: Color::Reset,
+                selection_bg: Color::Reset,
+                multi_select_bg: Color::Reset,
+                gauge_bg: Color::Reset,
+                selection_modifier: Modifier::REVERSED,
+            },
+        }
+    }
+
+    /// Build the theme from config. NO_COLOR (any non-empty value) wins over
+    /// everything. Returns warnings for unknown roles and unparsable colors.
+    pub fn from_config(config: &ThemeConfig) -> (Self, Vec<String>) {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return (Self::builtin(ThemeName::Monochrome), Vec::new());
+        }
+
+        let mut theme = Self::builtin(config.name);
+        let mut warnings = Vec::new();
+        for (role, value) in &config.colors {
+            let Ok(color) = value.parse::<Color>() else {
+                warnings.push(format!("Invalid color `{value}` for theme.colors.{role} (ignored)"));
+                continue;
+            };
+            let Some(slot) = theme.role_mut(role) else {
+                warnings.push(format!("Unknown theme color `{role}` (ignored)"));
+                continue;
+            };
+            *slot = color;
+        }
+        (theme, warnings)
+    }
+
+    fn role_mut(&mut self, role: &str) -> Option<&mut Color> {
+        Some(match role {
+            "text" => &mut self.text,
+            "accent" => &mut self.accent,
+            "warning" => &mut self.warning,
+            "success" => &mut self.success,
+            "danger" => &mut self.danger,
+            "special" => &mut self.special,
+            "downgrade" => &mut self.downgrade,
+            "held" => &mut self.held,
+            "broken" => &mut self.broken,
+            "muted" => &mut self.muted,
+            "dim" => &mut self.dim,
+            "title_fg" => &mut self.title_fg,
+            "title_bg" => &mut self.title_bg,
+            "selection_bg" => &mut self.selection_bg,
+            "multi_select_bg" => &mut self.multi_select_bg,
+            "gauge_bg" => &mut self.gauge_bg,
+            _ => return None,
+        })
+    }
+
+    /// Color of a package status symbol
+    pub fn status(&self, status: PackageStatus) -> Color {
+        match status {
+            PackageStatus::Upgradable => self.warning,
+            PackageStatus::MarkedForUpgrade | PackageStatus::MarkedForInstall => self.success,
+            PackageStatus::MarkedForDowngrade => self.downgrade,
+            PackageStatus::MarkedForRemove | PackageStatus::MarkedForPurge => self.danger,
+            PackageStatus::ResidualConfig => self.special,
+            PackageStatus::Keep => self.held,
+            PackageStatus::Installed => self.muted,
+            PackageStatus::NotInstalled => self.dim,
+            PackageStatus::Broken => self.broken,
+        }
+    }
+
+    /// Rows picked in visual mode
+    pub fn multi_select(&self) -> Style {
+        let style = Style::default().bg(self.multi_select_bg);
+        if self.multi_select_bg == Color::Reset {
+            style.add_modifier(Modifier::UNDERLINED)
+        } else {
+            style
+        }
+    }
+
+    /// Cursor row in lists and tables
+    pub fn selection(&self) -> Style {
+        Style::default().bg(self.selection_bg).add_modifier(self.selection_modifier)
+    }
+}
@@ -1,6 +1,12 @@
 //! Common types used throughout the application
 
+use std::collections::BTreeMap;
+
 use ratatui::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::keymap::KeysConfig;
+use crate::theme::ThemeConfig;
 
 // ============================================================================
 // Core API Types (Typestate Pattern)
@@ -18,7 +24,8 @@ impl PackageId {
 }
 
 /// What the user explicitly wants for a package
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum UserIntent {
     /// No user action - follow default behavior
     #[default]
@@ -27,12 +34,15 @@ pub enum UserIntent {
     Install,
     /// User explicitly wants this removed
     Remove,
+    /// User explicitly wants this removed along with its configuration files
+    Purge,
     /// User explicitly wants to keep current version (prevent auto-changes)
     Hold,
 }
 
 /// Why a package is changing
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ChangeReason {
     /// User explicitly requested this
     UserRequested,
@@ -43,18 +53,23 @@ pub enum ChangeReason {
 }
 
 /// Type of change to a package
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ChangeAction {
     Install,
     Upgrade,
     Remove,
+    /// Remove including configuration files
+    Purge,
     Downgrade,
 }
 
 /// A computed change from the plan
 /// A planned change to a package. Name is derived from PackageId, not stored.
-#[derive(Clone, Debug)]
+/// Serialized without the id; `json::Plan` adds the name.
+#[derive(Clone, Debug, Serialize)]
 pub struct PlannedChange {
+    #[serde(skip)]
     pub package: PackageId,
     pub action: ChangeAction,
     pub reason: ChangeReason,
@@ -72,8 +87,11 @@ pub struct Clean;
 /// Dirty state - has user marks but no computed plan
 pub struct Dirty;
 
-/// Planned state - dependencies resolved, changeset computed
+/// Planned state - dependencies resolved, changeset computed.
+/// Serializes the totals and errors; `json::Plan` lists the named changes.
+#[derive(Serialize)]
 pub struct Planned {
+    #[serde(skip)]
     pub changes: Vec<PlannedChange>,
     pub download_size: u64,
     pub install_size_change: i64,
@@ -96,18 +114,23 @@ impl MarkableState for Dirty {}
 // ============================================================================
 
 /// Package status - no distinction between user-marked and dependency
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PackageStatus {
     // Base states (not marked)
     Installed,        // · Package is installed, no changes pending
     NotInstalled,     //   Package is not installed, no changes pending
     Upgradable,       // ↑ Package can be upgraded (yellow)
+    ResidualConfig,   // c Package was removed but its configuration files remain (dpkg "rc")
     // Marked states (all marked packages look identical)
     MarkedForInstall, // + Package will be installed
     MarkedForUpgrade, // ↑ Package will be upgraded (green)
+    MarkedForDowngrade, // ↓ Package will be downgraded to a user-picked version
     MarkedForRemove,  // - Package will be removed
+    MarkedForPurge,   // _ Package will be removed along with its configuration files
     // Other
-    Keep,             // = Package kept at current version
+    #[serde(rename = "held")]
+    Keep,             // = Package held at current version
     Broken,           // ✗ Package is broken
 }
 
@@ -115,8 +138,11 @@ impl PackageStatus {
     pub fn symbol(&self) -> &'static str {
         match self {
             Self::Upgradable | Self::MarkedForUpgrade => "↑",
+            Self::MarkedForDowngrade => "↓",
             Self::MarkedForInstall => "+",
             Self::MarkedForRemove => "-",
+            Self::MarkedForPurge => "_",
+            Self::ResidualConfig => "c",
             Self::Keep => "=",
             Self::Installed => "·",
             Self::NotInstalled => " ",
@@ -124,16 +150,19 @@ impl PackageStatus {
         }
     }
 
-    pub fn color(&self) -> Color {
+    pub fn label(&self) -> &'static str {
         match self {
-            Self::Upgradable => Color::Yellow,
-            Self::MarkedForUpgrade => Color::Green,
-            Self::MarkedForInstall => Color::Green,
-            Self::MarkedForRemove => Color::Red,
-            Self::Keep => Color::Blue,
-            Self::Installed => Color::DarkGray,
-            Self::NotInstalled => Color::Gray,
-            Self::Broken => Color::LightRed,
+            Self::Installed => "Installed",
+            Self::NotInstalled => "Not installed",
+            Self::Upgradable => "Upgradable",
+            Self::ResidualConfig => "Residual config",
+            Self::MarkedForInstall => "Marked for install",
+            Self::MarkedForUpgrade => "Marked for upgrade",
+            Self::MarkedForDowngrade => "Marked for downgrade",
+            Self::MarkedForRemove => "Marked for removal",
+            Self::MarkedForPurge => "Marked for purge",
+            Self::Keep => "Held",
+            Self::Broken => "Broken",
         }
     }
 
@@ -142,18 +171,23 @@ impl PackageStatus {
         matches!(self,
             Self::MarkedForInstall |
             Self::MarkedForUpgrade |
-            Self::MarkedForRemove
+            Self::MarkedForDowngrade |
+            Self::MarkedForRemove |
+            Self::MarkedForPurge
         )
     }
 }
 
 /// Filter categories (left panel)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FilterCategory {
     Upgradable,
     MarkedChanges,
     Installed,
     NotInstalled,
+    ResidualConfig,
+    Held,
     All,
 }
 
@@ -164,6 +198,8 @@ impl FilterCategory {
             Self::MarkedChanges => "Marked Changes",
             Self::Installed => "Installed",
             Self::NotInstalled => "Not Installed",
+            Self::ResidualConfig => "Residual Config",
+            Self::Held => "Held",
             Self::All => "All Packages",
         }
     }
@@ -174,15 +210,67 @@ impl FilterCategory {
             Self::MarkedChanges,
             Self::Installed,
             Self::NotInstalled,
+            Self::ResidualConfig,
+            Self::Held,
             Self::All,
         ]
     }
 }
 
+/// A node of the repository tree in the filter pane
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RepoFilter {
+    /// Packages with a version from this origin, optionally narrowed to one
+    /// of its archives and then to a component of that archive
+    Origin { origin: String, archive: Option<String>, component: Option<String> },
+    /// Installed packages whose installed version no configured repository
+    /// offers (local .debs, or dropped from the archive)
+    NotConfigured,
+}
+
+impl RepoFilter {
+    /// Label of the tree node, without its parents
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Origin { component: Some(component), .. } => component,
+            Self::Origin { archive: Some(archive), .. } => archive,
+            Self::Origin { origin, .. } => origin,
+            Self::NotConfigured => "Local/obsolete",
+        }
+    }
+
+    /// Nesting level: 0 for origins, 1 for archives, 2 for components
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::Origin { component: Some(_), .. } => 2,
+            Self::Origin { archive: Some(_), .. } => 1,
+            Self::Origin { .. } | Self::NotConfigured => 0,
+        }
+    }
+
+    /// The node this one is nested in
+    pub fn parent(&self) -> Option<RepoFilter> {
+        match self {
+            Self::Origin { origin, archive: Some(archive), component: Some(_) } => Some(Self::Origin {
+                origin: origin.clone(),
+                archive: Some(archive.clone()),
+                component: None,
+            }),
+            Self::Origin { origin, archive: Some(_), component: None } => Some(Self::Origin {
+                origin: origin.clone(),
+                archive: None,
+                component: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
 /// Displayed package info (extracted from rust-apt Package).
 /// The package is identified by `id` (PackageId). Name is derived, not stored separately.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PackageInfo {
+    #[serde(skip)]
     pub id: PackageId,        // Stable handle for this package - the ONLY identifier
     pub name: String,         // Full name including arch (e.g., "libfoo:i386") - for display/sort
     pub status: PackageStatus,
@@ -195,6 +283,17 @@ pub struct PackageInfo {
     pub architecture: String,
 }
 
+/// Package record fields beyond `PackageInfo`, read on demand
+/// (for the details pane and the search index)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PackageDetails {
+    pub long_description: String, // Without the summary line; paragraphs separated by blank lines
+    pub maintainer: String,
+    pub homepage: String,
+    pub source: String,
+    pub provides: Vec<String>,
+}
+
 impl PackageInfo {
     pub fn size_str(bytes: u64) -> String {
         if bytes == 0 {
@@ -224,6 +323,20 @@ impl PackageInfo {
     }
 }
 
+/// One version of a package as offered by the version picker
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableVersion {
+    pub version: String,
+    pub origin: String,    // e.g., "Ubuntu", "LP-PPA-foo", or "local" for dpkg-only versions
+    pub archive: String,   // e.g., "jammy-updates", or "now" for dpkg-only versions
+    pub component: String, // e.g., "main"
+    pub priority: i32,     // Pin priority as reported by `apt policy`
+    pub is_installed: bool,
+    pub is_candidate: bool, // APT's policy candidate (ignoring user overrides)
+    pub download_size: u64,
+    pub installed_size: u64,
+}
+
 /// Which pane has focus
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FocusedPane {
@@ -233,34 +346,64 @@ pub enum FocusedPane {
 }
 
 /// Which tab is shown in details pane
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DetailsTab {
     Info,
     Dependencies,
     ReverseDeps,
+    Files,
+}
+
+/// Which tab is shown in the changes modal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChangesTab {
+    #[default]
+    Changes,
+    Simulation, // `apt-get -s` dry run of the plan
 }
 
 /// Application state machine
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AppState {
     Listing,
     Searching,          // User is typing a search query
+    FilteringFiles,     // User is typing a filter for the Files tab
     ShowingMarkConfirm, // Popup showing additional changes when marking a package
     ShowingChanges,     // Final confirmation before applying all changes
     ShowingChangelog,   // Viewing package changelog
+    ShowingVersions,    // Picking a specific version to install/downgrade to
     ShowingSettings,    // Settings/preferences view
+    ShowingHelp,        // Key binding overlay on top of the previous state
     ConfirmExit,        // Confirm exit with pending changes
     Upgrading,
     Done,
 }
 
 /// Sort options
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SortBy {
     Name,
     Section,
     InstalledVersion,
     CandidateVersion,
+    Relevance, // Search rank; used automatically while a query is set
+}
+
+impl DetailsTab {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Info => "Info",
+            Self::Dependencies => "Dependencies",
+            Self::ReverseDeps => "Reverse deps",
+            Self::Files => "Files",
+        }
+    }
+
+    pub fn all() -> &'static [DetailsTab] {
+        &[Self::Info, Self::Dependencies, Self::ReverseDeps, Self::Files]
+    }
 }
 
 impl SortBy {
@@ -270,15 +413,17 @@ impl SortBy {
             Self::Section => "Section",
             Self::InstalledVersion => "Installed version",
             Self::CandidateVersion => "Candidate version",
+            Self::Relevance => "Relevance",
         }
     }
 
+    /// Orders the user can pick (Relevance follows the search query instead)
     pub fn all() -> &'static [SortBy] {
         &[Self::Name, Self::Section, Self::InstalledVersion, Self::CandidateVersion]
     }
 }
 
-/// User settings (not persisted yet)
+/// User settings (persisted to config.toml, see `config.rs`)
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub show_status_column: bool,
@@ -289,6 +434,11 @@ pub struct Settings {
     pub show_download_size_column: bool,
     pub sort_by: SortBy,
     pub sort_ascending: bool,
+    pub default_filter: FilterCategory, // Filter selected at startup
+    pub details_tab: DetailsTab,        // Details tab shown at startup
+    pub theme: ThemeConfig,
+    pub keys: KeysConfig, // Key overrides; only editable in config.toml
+    pub filters: BTreeMap<String, String>, // Custom filters by name; only editable in config.toml
 }
 
 impl Default for Settings {
@@ -302,6 +452,11 @@ impl Default for Settings {
             show_download_size_column: false,
             sort_by: SortBy::CandidateVersion,
             sort_ascending: true,
+            default_filter: FilterCategory::Upgradable,
+            details_tab: DetailsTab::Info,
+            theme: ThemeConfig::default(),
+            keys: KeysConfig::default(),
+            filters: BTreeMap::new(),
         }
     }
 }
@@ -325,13 +480,26 @@ pub enum ToggleResult {
     },
 }
 
-/// Additional changes required when marking a single package
+/// A package's user mark as it was before a toggle, put back if the toggle
+/// is cancelled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorMark {
+    pub package: PackageId,
+    pub intent: UserIntent,
+    pub version: Option<String>, // Picked version, if any
+}
+
+/// Additional changes required when marking a single package (display names;
+/// `json::Preview` is the full-name form)
 #[derive(Debug, Default, Clone)]
 pub struct MarkPreview {
     pub package_name: String,
+    pub intent: UserIntent, // For mark: Install, Remove or Purge; for unmark: the intent dropped
     pub is_upgrade: bool, // true = package is being upgraded, false = new install
+    pub is_downgrade: bool, // true = package is being downgraded to a picked version
     pub is_marking: bool, // true = marking for install, false = unmarking
     pub was_user_marked: bool, // For unmark: was the original package user-marked (vs dependency)?
+    pub restore: Vec<PriorMark>, // Marks the toggle changed, restored on cancel
     pub additional_installs: Vec<String>,
     pub additional_upgrades: Vec<String>,
     pub additional_removes: Vec<String>,
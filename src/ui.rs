@@ -3,13 +3,16 @@
 use ratatui::prelude::*;
 use ratatui::widgets::{
     Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Scrollbar,
-    ScrollbarOrientation, ScrollbarState, Table, Wrap,
+    ScrollbarOrientation, ScrollbarState, Table, TableState, Wrap,
 };
 
-use crate::app::App;
+use crate::app::{App, FilterRow};
+use synh8::files;
+use synh8::keymap::{Action, Keymap};
 use synh8::types::*;
 
 pub fn ui(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme.clone();
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -33,17 +36,21 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
             PackageInfo::size_str(download_size)
         )
     } else if app.core.has_marks() {
-        format!(" APT TUI │ {} marked (press 'u' to compute) ",
-            app.core.list().iter().filter(|p| app.core.is_user_marked(p.id)).count())
+        format!(" APT TUI │ {} marked (press '{}' to compute) ",
+            app.core.list().iter().filter(|p| app.core.is_user_marked(p.id)).count(),
+            app.keymap.hint(AppState::Listing, Action::ShowChanges))
     } else {
         " APT TUI │ No changes pending ".to_string()
     };
     let title = Paragraph::new(title_text)
-        .style(Style::default().fg(Color::White).bg(Color::Blue).bold());
+        .style(Style::default().fg(theme.title_fg).bg(theme.title_bg).bold());
     frame.render_widget(title, main_chunks[0]);
 
-    match app.state {
-        AppState::Listing | AppState::Searching => {
+    // The help overlay is drawn on top of the state it was opened from
+    let screen = if app.state == AppState::ShowingHelp { app.help_context() } else { app.state };
+
+    match screen {
+        AppState::Listing | AppState::Searching | AppState::FilteringFiles => {
             let panes = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([
@@ -80,6 +87,22 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         AppState::ShowingChangelog => {
             render_changelog_view(frame, app, main_chunks[1]);
         }
+        AppState::ShowingVersions => {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(24),
+                    Constraint::Min(40),
+                    Constraint::Length(35),
+                ])
+                .split(main_chunks[1]);
+
+            render_filter_pane(frame, app, panes[0]);
+            render_package_table(frame, app, panes[1]);
+            render_details_pane(frame, app, panes[2]);
+
+            render_version_picker(frame, app, main_chunks[1]);
+        }
         AppState::ShowingSettings => {
             render_settings_view(frame, app, main_chunks[1]);
         }
@@ -106,27 +129,40 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                 .collect();
             let output = Paragraph::new(lines)
                 .block(Block::default().title(" APT Output ").borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Green)))
+                    .border_style(Style::default().fg(theme.success)))
                 .wrap(Wrap { trim: false })
                 .scroll((app.output_scroll, 0));
             frame.render_widget(output, main_chunks[1]);
         }
+        AppState::ShowingHelp => {}
     }
 
-    let status_style = match app.state {
-        AppState::Listing => Style::default().fg(Color::Yellow),
-        AppState::Searching => Style::default().fg(Color::White),
-        AppState::ShowingMarkConfirm => Style::default().fg(Color::Magenta),
-        AppState::ShowingChanges => Style::default().fg(Color::Cyan),
-        AppState::ShowingChangelog => Style::default().fg(Color::Cyan),
-        AppState::ShowingSettings => Style::default().fg(Color::Yellow),
-        AppState::ConfirmExit => Style::default().fg(Color::Red),
-        AppState::Upgrading => Style::default().fg(Color::Cyan),
-        AppState::Done => Style::default().fg(Color::Green),
+    let status_style = match screen {
+        AppState::Listing => Style::default().fg(theme.warning),
+        AppState::Searching if app.core.search_error().is_some() => Style::default().fg(theme.danger),
+        AppState::Searching | AppState::FilteringFiles => Style::default().fg(theme.text),
+        AppState::ShowingMarkConfirm => Style::default().fg(theme.special),
+        AppState::ShowingChanges => Style::default().fg(theme.accent),
+        AppState::ShowingChangelog => Style::default().fg(theme.accent),
+        AppState::ShowingVersions => Style::default().fg(theme.accent),
+        AppState::ShowingSettings => Style::default().fg(theme.warning),
+        AppState::ShowingHelp => Style::default().fg(theme.accent),
+        AppState::ConfirmExit => Style::default().fg(theme.danger),
+        AppState::Upgrading => Style::default().fg(theme.accent),
+        AppState::Done => Style::default().fg(theme.success),
     };
 
-    let status_text = match app.state {
-        AppState::Searching => format!("/{}_", app.core.search_query()),
+    let status_text = match screen {
+        AppState::Searching => match app.core.search_error() {
+            Some(error) => format!("/{}_   ⚠ {error}", app.core.search_query()),
+            None if files::is_path_query(app.core.search_query()) => format!(
+                "/{}_   (file path: {} to look up owners)",
+                app.core.search_query(),
+                app.keymap.hint(AppState::Searching, Action::Confirm)
+            ),
+            None => format!("/{}_", app.core.search_query()),
+        },
+        AppState::FilteringFiles => format!("Filter files: {}_", app.details.file_filter),
         _ => {
             if app.core.search_result_count().is_some() {
                 format!("[Search: {}] {}", app.core.search_query(), app.status_message)
@@ -143,27 +179,93 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     let help_text = match app.state {
         AppState::Listing => {
             if app.ui.visual_mode {
-                "v/Space:Mark selected │ Esc:Cancel │ ↑↓:Extend selection"
+                key_hints(app, app.state, &[
+                    (Action::Toggle, "Mark selected"),
+                    (Action::Cancel, "Cancel"),
+                    (Action::Help, "Help"),
+                ])
             } else if app.core.search_result_count().is_some() {
-                "/:Search │ Esc:Clear │ Space:Mark │ v:Visual │ x:All │ N:None │ u:Apply │ U:Update │ q:Quit"
+                key_hints(app, app.state, &[
+                    (Action::Search, "Search"),
+                    (Action::Cancel, "Clear"),
+                    (Action::Toggle, "Mark"),
+                    (Action::Remove, "Remove"),
+                    (Action::VisualMode, "Visual"),
+                    (Action::UpgradeAll, "All"),
+                    (Action::UnmarkAll, "None"),
+                    (Action::ShowChanges, "Apply"),
+                    (Action::Help, "Help"),
+                    (Action::Quit, "Quit"),
+                ])
             } else {
-                "/:Search │ Space:Mark │ v:Visual │ x:All │ N:None │ d:Deps │ s:Settings │ u:Apply │ U:Update │ q:Quit"
+                key_hints(app, app.state, &[
+                    (Action::Search, "Search"),
+                    (Action::Toggle, "Mark"),
+                    (Action::Remove, "Remove"),
+                    (Action::Purge, "Purge"),
+                    (Action::Hold, "Hold"),
+                    (Action::Versions, "Versions"),
+                    (Action::VisualMode, "Visual"),
+                    (Action::UpgradeAll, "All"),
+                    (Action::UnmarkAll, "None"),
+                    (Action::Settings, "Settings"),
+                    (Action::ShowChanges, "Apply"),
+                    (Action::Update, "Update"),
+                    (Action::Help, "Help"),
+                    (Action::Quit, "Quit"),
+                ])
             }
         }
-        AppState::Searching => "Enter:Confirm │ Esc:Cancel │ Type to search...",
-        AppState::ShowingMarkConfirm => "y/Space/Enter:Confirm │ n/Esc:Cancel",
-        AppState::ShowingChanges => "y/Enter:Apply │ n/Esc:Cancel │ ↑↓:Scroll",
-        AppState::ShowingChangelog => "↑↓/PgUp/PgDn:Scroll │ Esc/q:Close",
-        AppState::ShowingSettings => "↑↓:Navigate │ Space/Enter:Toggle │ Esc/q:Close",
-        AppState::ConfirmExit => "y/Enter:Quit │ n/Esc:Cancel",
-        AppState::Upgrading => "Applying changes...",
-        AppState::Done => "↑↓/PgUp/PgDn:Scroll │ r:Refresh │ q:Quit",
+        AppState::Searching => format!(
+            "{} │ Type to search...",
+            key_hints(app, app.state, &[(Action::Confirm, "Confirm"), (Action::Cancel, "Cancel")]),
+        ),
+        AppState::FilteringFiles => format!(
+            "{} │ Type to filter files...",
+            key_hints(app, app.state, &[(Action::Confirm, "Keep"), (Action::Cancel, "Clear")]),
+        ),
+        AppState::ShowingMarkConfirm => key_hints(app, app.state, &[(Action::Confirm, "Confirm"), (Action::Cancel, "Cancel")]),
+        AppState::ShowingChanges => key_hints(app, app.state, &[
+            (Action::Apply, "Apply"),
+            (Action::Cancel, "Cancel"),
+            (Action::NextTab, "Dry run"),
+            (Action::Changelog, "Upgrade changelog"),
+            (Action::PurgeResidual, "Purge residual config"),
+            (Action::Help, "Help"),
+        ]),
+        AppState::ShowingChangelog => key_hints(app, app.state, &[
+            (Action::ToggleHistory, "Full history/delta"),
+            (Action::Cancel, "Close (cancels loading)"),
+            (Action::Help, "Help"),
+        ]),
+        AppState::ShowingVersions => key_hints(app, app.state, &[
+            (Action::Confirm, "Install this version"),
+            (Action::Cancel, "Close"),
+            (Action::Help, "Help"),
+        ]),
+        AppState::ShowingSettings => key_hints(app, app.state, &[
+            (Action::Toggle, "Toggle"),
+            (Action::Cancel, "Close"),
+            (Action::Help, "Help"),
+        ]),
+        AppState::ShowingHelp => key_hints(app, app.state, &[(Action::Cancel, "Close")]),
+        AppState::ConfirmExit => key_hints(app, app.state, &[(Action::Confirm, "Quit"), (Action::Cancel, "Cancel")]),
+        AppState::Upgrading => "Applying changes...".to_string(),
+        AppState::Done => key_hints(app, app.state, &[
+            (Action::Refresh, "Refresh"),
+            (Action::Help, "Help"),
+            (Action::Quit, "Quit"),
+        ]),
     };
     let help = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::DarkGray))
+        .style(Style::default().fg(theme.muted))
         .alignment(Alignment::Center);
     frame.render_widget(help, main_chunks[3]);
 
+    if app.state == AppState::ShowingHelp {
+        render_help_overlay(frame, app, main_chunks[1]);
+    }
+
     // Show cursor for text input states
     match app.state {
         AppState::Searching => {
@@ -172,36 +274,70 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
             let cursor_y = main_chunks[2].y + 1;
             frame.set_cursor_position((cursor_x, cursor_y));
         }
+        AppState::FilteringFiles => {
+            let cursor_x = main_chunks[2].x + 1 + "Filter files: ".len() as u16 + app.details.file_filter.len() as u16;
+            let cursor_y = main_chunks[2].y + 1;
+            frame.set_cursor_position((cursor_x, cursor_y));
+        }
         _ => {}
     }
 }
 
 fn render_filter_pane(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
     let is_focused = app.ui.focused_pane == FocusedPane::Filters;
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(7), Constraint::Length(9)])
+        .constraints([Constraint::Min(7), Constraint::Length(13)])
         .split(area);
 
-    let items: Vec<ListItem> = FilterCategory::all()
+    let active = Style::default().fg(theme.warning).bold();
+    let items: Vec<ListItem> = app.filter_rows()
         .iter()
-        .map(|cat| {
-            let count = app.core.filter_count(*cat);
-            let label = format!("{} ({})", cat.label(), count);
-            let style = if *cat == app.core.selected_filter() {
-                Style::default().fg(Color::Yellow).bold()
-            } else {
-                Style::default()
-            };
-            ListItem::new(label).style(style)
+        .map(|row| match row {
+            FilterRow::Category(cat) => {
+                let count = app.core.filter_count(*cat);
+                let label = format!("{} ({})", cat.label(), count);
+                let is_active = *cat == app.core.selected_filter() && app.core.custom_filter().is_none();
+                let style = if is_active { active } else { Style::default() };
+                ListItem::new(label).style(style)
+            }
+            FilterRow::Custom(name) => {
+                let count = app.core.custom_filter_counts().iter()
+                    .find(|(n, _)| n == name)
+                    .map_or(0, |(_, count)| *count);
+                let style = if app.core.custom_filter() == Some(name.as_str()) { active } else { Style::default() };
+                ListItem::new(format!("{name} ({count})")).style(style)
+            }
+            FilterRow::Heading(title) => ListItem::new(format!("─ {title}"))
+                .style(Style::default().fg(theme.muted).bold()),
+            FilterRow::AnyRepo => {
+                let style = if app.core.repo_filter().is_none() { active } else { Style::default() };
+                ListItem::new("Any").style(style)
+            }
+            FilterRow::Repo(repo) => {
+                let count = app.core.repo_counts().get(repo).copied().unwrap_or(0);
+                let label = format!("{}{} ({count})", "  ".repeat(repo.depth()), repo.label());
+                let style = if app.core.repo_filter() == Some(repo) { active } else { Style::default() };
+                ListItem::new(label).style(style)
+            }
+            FilterRow::AnySection => {
+                let style = if app.core.section_filter().is_none() { active } else { Style::default() };
+                ListItem::new("Any").style(style)
+            }
+            FilterRow::Section(section) => {
+                let count = app.core.section_counts().get(section).copied().unwrap_or(0);
+                let style = if app.core.section_filter() == Some(section.as_str()) { active } else { Style::default() };
+                ListItem::new(format!("{section} ({count})")).style(style)
+            }
         })
         .collect();
 
     let border_style = if is_focused {
-        Style::default().fg(Color::Cyan)
+        Style::default().fg(theme.accent)
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme.muted)
     };
 
     let list = List::new(items)
@@ -211,38 +347,54 @@ fn render_filter_pane(frame: &mut Frame, app: &mut App, area: Rect) {
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
-        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_style(theme.selection())
         .highlight_symbol("▶ ");
 
     frame.render_stateful_widget(list, chunks[0], &mut app.ui.filter_state);
 
     let legend = vec![
         Line::from(vec![
-            Span::styled("↑", Style::default().fg(Color::Yellow)),
+            Span::styled("↑", Style::default().fg(theme.warning)),
             Span::raw(" Upgradable"),
         ]),
         Line::from(vec![
-            Span::styled("↑", Style::default().fg(Color::Green)),
+            Span::styled("↑", Style::default().fg(theme.success)),
             Span::raw(" Upgrade"),
         ]),
         Line::from(vec![
-            Span::styled("↑", Style::default().fg(Color::Cyan)),
+            Span::styled("↑", Style::default().fg(theme.accent)),
             Span::raw(" Auto-upg"),
         ]),
         Line::from(vec![
-            Span::styled("+", Style::default().fg(Color::Green)),
+            Span::styled("+", Style::default().fg(theme.success)),
             Span::raw(" Install"),
         ]),
         Line::from(vec![
-            Span::styled("+", Style::default().fg(Color::Cyan)),
+            Span::styled("+", Style::default().fg(theme.accent)),
             Span::raw(" Auto-inst"),
         ]),
         Line::from(vec![
-            Span::styled("-", Style::default().fg(Color::Red)),
+            Span::styled("-", Style::default().fg(theme.danger)),
             Span::raw(" Remove"),
         ]),
         Line::from(vec![
-            Span::styled("·", Style::default().fg(Color::DarkGray)),
+            Span::styled("_", Style::default().fg(theme.danger)),
+            Span::raw(" Purge"),
+        ]),
+        Line::from(vec![
+            Span::styled("↓", Style::default().fg(theme.downgrade)),
+            Span::raw(" Downgrade"),
+        ]),
+        Line::from(vec![
+            Span::styled("c", Style::default().fg(theme.special)),
+            Span::raw(" Config left"),
+        ]),
+        Line::from(vec![
+            Span::styled("=", Style::default().fg(theme.held)),
+            Span::raw(" Held"),
+        ]),
+        Line::from(vec![
+            Span::styled("·", Style::default().fg(theme.muted)),
             Span::raw(" Installed"),
         ]),
     ];
@@ -252,19 +404,20 @@ fn render_filter_pane(frame: &mut Frame, app: &mut App, area: Rect) {
             Block::default()
                 .title(" Legend ")
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::DarkGray)),
+                .border_style(Style::default().fg(theme.muted)),
         );
 
     frame.render_widget(legend_widget, chunks[1]);
 }
 
 fn render_package_table(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
     let is_focused = app.ui.focused_pane == FocusedPane::Packages;
     let visible_cols = Column::visible_columns(&app.settings);
 
     let header_cells: Vec<Cell> = visible_cols
         .iter()
-        .map(|col| Cell::from(col.header()).style(Style::default().fg(Color::Cyan).bold()))
+        .map(|col| Cell::from(col.header()).style(Style::default().fg(theme.accent).bold()))
         .collect();
     let header = Row::new(header_cells).height(1);
 
@@ -280,10 +433,10 @@ fn render_package_table(frame: &mut Frame, app: &mut App, area: Rect) {
                 .iter()
                 .map(|col| match col {
                     Column::Status => Cell::from(pkg.status.symbol())
-                        .style(Style::default().fg(pkg.status.color())),
+                        .style(Style::default().fg(theme.status(pkg.status))),
                     Column::Name => {
                         let style = if is_user_marked {
-                            Style::default().fg(Color::White).bold()
+                            Style::default().fg(theme.text).bold()
                         } else {
                             Style::default()
                         };
@@ -300,14 +453,14 @@ fn render_package_table(frame: &mut Frame, app: &mut App, area: Rect) {
                         }
                     }
                     Column::CandidateVersion => Cell::from(pkg.candidate_version.as_str())
-                        .style(Style::default().fg(Color::Green)),
+                        .style(Style::default().fg(theme.success)),
                     Column::DownloadSize => Cell::from(pkg.download_size_str()),
                 })
                 .collect();
 
             let row = Row::new(cells);
             if is_multi_selected {
-                row.style(Style::default().bg(Color::Blue))
+                row.style(theme.multi_select())
             } else {
                 row
             }
@@ -317,20 +470,29 @@ fn render_package_table(frame: &mut Frame, app: &mut App, area: Rect) {
     let widths: Vec<Constraint> = visible_cols.iter().map(|col| col.width(&app.col_widths)).collect();
 
     let border_style = if is_focused {
-        Style::default().fg(Color::Cyan)
+        Style::default().fg(theme.accent)
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme.muted)
     };
 
+    // Name the repository and section the status filter is narrowed to
+    let mut title = format!(" Packages ({}) ", list.len());
+    if let Some(repo) = app.core.repo_filter() {
+        title.push_str(&format!("· {} ", repo.label()));
+    }
+    if let Some(section) = app.core.section_filter() {
+        title.push_str(&format!("· {section} "));
+    }
+
     let table = Table::new(rows, widths)
         .header(header)
         .block(
             Block::default()
-                .title(format!(" Packages ({}) ", list.len()))
+                .title(title)
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
-        .row_highlight_style(Style::default().bg(Color::DarkGray))
+        .row_highlight_style(theme.selection())
         .highlight_symbol("▶ ");
 
     frame.render_stateful_widget(table, area, &mut app.ui.table_state);
@@ -354,28 +516,34 @@ fn render_package_table(frame: &mut Frame, app: &mut App, area: Rect) {
 }
 
 fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let is_focused = app.ui.focused_pane == FocusedPane::Details;
 
     let border_style = if is_focused {
-        Style::default().fg(Color::Cyan)
+        Style::default().fg(theme.accent)
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme.muted)
     };
 
     let info_style = if app.details.tab == DetailsTab::Info {
-        Style::default().fg(Color::Yellow).bold()
+        Style::default().fg(theme.warning).bold()
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme.muted)
     };
     let deps_style = if app.details.tab == DetailsTab::Dependencies {
-        Style::default().fg(Color::Yellow).bold()
+        Style::default().fg(theme.warning).bold()
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme.muted)
     };
     let rdeps_style = if app.details.tab == DetailsTab::ReverseDeps {
-        Style::default().fg(Color::Yellow).bold()
+        Style::default().fg(theme.warning).bold()
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme.muted)
+    };
+    let files_style = if app.details.tab == DetailsTab::Files {
+        Style::default().fg(theme.warning).bold()
+    } else {
+        Style::default().fg(theme.muted)
     };
 
     let mut content = vec![
@@ -385,8 +553,13 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
             Span::styled("[Deps]", deps_style),
             Span::raw(" "),
             Span::styled("[RDeps]", rdeps_style),
+            Span::raw(" "),
+            Span::styled("[Files]", files_style),
         ]),
-        Line::from(Span::styled("  (d to switch)", Style::default().fg(Color::DarkGray))),
+        Line::from(Span::styled(
+            format!("  ({} to switch)", app.keymap.hint(AppState::Listing, Action::NextTab)),
+            Style::default().fg(theme.muted),
+        )),
         Line::from(""),
     ];
 
@@ -394,28 +567,31 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
         let display_name = app.core.cache().display_name(&pkg.name);
         match app.details.tab {
             DetailsTab::Info => {
+                let terms = app.core.search_terms();
+                let hl = Style::default().fg(theme.warning).bold();
+                let details = &app.details.cached_details;
+                let label = Style::default().fg(theme.accent);
+                let field = |name, value| labeled(name, label, value, terms, hl);
+
+                let mut package = vec![Span::styled("Package: ", Style::default().fg(theme.accent).bold())];
+                package.extend(highlight(&display_name, terms, Style::default(), hl));
+
                 content.extend(vec![
-                    Line::from(vec![
-                        Span::styled("Package: ", Style::default().fg(Color::Cyan).bold()),
-                        Span::raw(display_name),
-                    ]),
+                    Line::from(package),
                     Line::from(""),
                     Line::from(vec![
-                        Span::styled("Status: ", Style::default().fg(Color::Cyan)),
-                        Span::styled(pkg.status.symbol(), Style::default().fg(pkg.status.color())),
+                        Span::styled("Status: ", Style::default().fg(theme.accent)),
+                        Span::styled(pkg.status.symbol(), Style::default().fg(theme.status(pkg.status))),
                         Span::raw(format!(" {:?}", pkg.status)),
                     ]),
+                    field("Section: ", &pkg.section),
                     Line::from(vec![
-                        Span::styled("Section: ", Style::default().fg(Color::Cyan)),
-                        Span::raw(&pkg.section),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Arch: ", Style::default().fg(Color::Cyan)),
+                        Span::styled("Arch: ", Style::default().fg(theme.accent)),
                         Span::raw(&pkg.architecture),
                     ]),
                     Line::from(""),
                     Line::from(vec![
-                        Span::styled("Installed: ", Style::default().fg(Color::Cyan)),
+                        Span::styled("Installed: ", Style::default().fg(theme.accent)),
                         Span::raw(if pkg.installed_version.is_empty() {
                             "(none)"
                         } else {
@@ -423,31 +599,65 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
                         }),
                     ]),
                     Line::from(vec![
-                        Span::styled("Candidate: ", Style::default().fg(Color::Green)),
+                        Span::styled("Candidate: ", Style::default().fg(theme.success)),
                         Span::raw(&pkg.candidate_version),
                     ]),
                     Line::from(""),
                     Line::from(vec![
-                        Span::styled("Download: ", Style::default().fg(Color::Cyan)),
+                        Span::styled("Download: ", Style::default().fg(theme.accent)),
                         Span::raw(pkg.download_size_str()),
                     ]),
                     Line::from(vec![
-                        Span::styled("Inst Size: ", Style::default().fg(Color::Cyan)),
+                        Span::styled("Inst Size: ", Style::default().fg(theme.accent)),
                         Span::raw(pkg.installed_size_str()),
                     ]),
                     Line::from(""),
-                    Line::from(Span::styled(
-                        "Description:",
-                        Style::default().fg(Color::Cyan).bold(),
-                    )),
-                    Line::from(pkg.description.as_str()),
                 ]);
+
+                let record = [
+                    ("Maintainer: ", &details.maintainer),
+                    ("Homepage: ", &details.homepage),
+                    ("Source: ", &details.source),
+                ];
+                let mut has_record = false;
+                for (label, value) in record {
+                    if !value.is_empty() {
+                        content.push(field(label, value));
+                        has_record = true;
+                    }
+                }
+                if !details.provides.is_empty() {
+                    let mut spans = vec![Span::styled("Provides: ", label)];
+                    for (i, name) in details.provides.iter().enumerate() {
+                        if i > 0 {
+                            spans.push(Span::raw(", "));
+                        }
+                        spans.extend(highlight(name, terms, Style::default(), hl));
+                    }
+                    content.push(Line::from(spans));
+                    has_record = true;
+                }
+                if has_record {
+                    content.push(Line::from(""));
+                }
+
+                content.push(Line::from(Span::styled(
+                    "Description:",
+                    Style::default().fg(theme.accent).bold(),
+                )));
+                content.push(Line::from(highlight(&pkg.description, terms, Style::default(), hl)));
+                if !details.long_description.is_empty() {
+                    content.push(Line::from(""));
+                    for line in details.long_description.lines() {
+                        content.push(Line::from(highlight(line, terms, Style::default(), hl)));
+                    }
+                }
             }
             DetailsTab::Dependencies => {
                 if app.details.cached_deps.is_empty() {
                     content.push(Line::from(Span::styled(
                         "No dependencies",
-                        Style::default().fg(Color::DarkGray),
+                        Style::default().fg(theme.muted),
                     )));
                 } else {
                     let mut current_type = String::new();
@@ -459,7 +669,7 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
                             }
                             content.push(Line::from(Span::styled(
                                 format!("{dep_type}:"),
-                                Style::default().fg(Color::Cyan).bold(),
+                                Style::default().fg(theme.accent).bold(),
                             )));
                             current_type = dep_type.clone();
                         }
@@ -475,12 +685,12 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
                 if app.details.cached_rdeps.is_empty() {
                     content.push(Line::from(Span::styled(
                         "No reverse dependencies",
-                        Style::default().fg(Color::DarkGray),
+                        Style::default().fg(theme.muted),
                     )));
                 } else {
                     content.push(Line::from(Span::styled(
                         format!("{} packages depend on this:", app.details.cached_rdeps.len()),
-                        Style::default().fg(Color::Cyan).bold(),
+                        Style::default().fg(theme.accent).bold(),
                     )));
                     content.push(Line::from(""));
 
@@ -493,7 +703,7 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
                             }
                             content.push(Line::from(Span::styled(
                                 format!("{dep_type}:"),
-                                Style::default().fg(Color::Cyan).bold(),
+                                Style::default().fg(theme.accent).bold(),
                             )));
                             current_type = dep_type.clone();
                         }
@@ -505,11 +715,54 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
                     }
                 }
             }
+            DetailsTab::Files => {
+                let filter = &app.details.file_filter;
+                if !filter.is_empty() || app.state == AppState::FilteringFiles {
+                    content.push(Line::from(vec![
+                        Span::styled("Filter: ", Style::default().fg(theme.accent)),
+                        Span::raw(filter.as_str()),
+                    ]));
+                }
+
+                if app.details.cached_files.is_empty() {
+                    let message = if app.files_loading() {
+                        "Searching the Contents indexes..."
+                    } else if app.details.files_from_contents {
+                        "Not installed, and no Contents index lists it"
+                    } else {
+                        "No files"
+                    };
+                    content.push(Line::from(Span::styled(message, Style::default().fg(theme.muted))));
+                } else {
+                    let count = app.visible_files().count();
+                    let source = if app.details.files_from_contents { " (from Contents index)" } else { "" };
+                    content.push(Line::from(Span::styled(
+                        format!("{count} files{source}:"),
+                        Style::default().fg(theme.accent).bold(),
+                    )));
+                    content.push(Line::from(""));
+
+                    for file in app.visible_files() {
+                        let mut spans = Vec::new();
+                        if let Some(size) = file.size {
+                            spans.push(Span::styled(
+                                format!("{:>8} ", PackageInfo::size_str(size)),
+                                Style::default().fg(theme.muted),
+                            ));
+                        }
+                        spans.push(Span::raw(file.path.as_str()));
+                        if file.conffile {
+                            spans.push(Span::styled(" [conf]", Style::default().fg(theme.special)));
+                        }
+                        content.push(Line::from(spans));
+                    }
+                }
+            }
         }
     } else {
         content.push(Line::from(Span::styled(
             "No package selected",
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(theme.muted),
         )));
     }
 
@@ -517,6 +770,7 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
         DetailsTab::Info => " Details ",
         DetailsTab::Dependencies => " Dependencies ",
         DetailsTab::ReverseDeps => " Reverse Deps ",
+        DetailsTab::Files => " Files ",
     };
 
     let details = Paragraph::new(content)
@@ -532,8 +786,57 @@ fn render_details_pane(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(details, area);
 }
 
+/// A "Label: value" line with search terms highlighted in the value
+fn labeled<'a>(name: &'a str, label: Style, value: &'a str, terms: &[String], hl: Style) -> Line<'a> {
+    let mut spans = vec![Span::styled(name, label)];
+    spans.extend(highlight(value, terms, Style::default(), hl));
+    Line::from(spans)
+}
+
+/// Split text into spans, styling words that start with a search term. Matching
+/// ignores ASCII case, like the FTS5 tokenizer.
+fn highlight<'a>(text: &'a str, terms: &[String], style: Style, hl_style: Style) -> Vec<Span<'a>> {
+    let lower = text.to_ascii_lowercase();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for term in terms.iter().filter(|t| !t.is_empty()) {
+        let term = term.to_ascii_lowercase();
+        for (start, _) in lower.match_indices(term.as_str()) {
+            let at_word_start = lower[..start].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+            if at_word_start {
+                ranges.push((start, start + term.len()));
+            }
+        }
+    }
+    if ranges.is_empty() {
+        return vec![Span::styled(text, style)];
+    }
+
+    ranges.sort_unstable();
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for (start, end) in ranges {
+        if end <= pos {
+            continue;
+        }
+        let start = start.max(pos);
+        if start > pos {
+            spans.push(Span::styled(&text[pos..start], style));
+        }
+        spans.push(Span::styled(&text[start..end], hl_style));
+        pos = end;
+    }
+    if pos < text.len() {
+        spans.push(Span::styled(&text[pos..], style));
+    }
+    spans
+}
+
 fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
-    let modal_width = 60.min(area.width.saturating_sub(4));
+    let theme = &app.theme;
+    let simulating = app.modals.changes_tab == ChangesTab::Simulation;
+    // Simulated steps carry versions and origins, so give them more room
+    let modal_width = if simulating { 100 } else { 60 }.min(area.width.saturating_sub(4));
     let modal_height = 20.min(area.height.saturating_sub(2));
     let modal_x = area.x + (area.width - modal_width) / 2;
     let modal_y = area.y + (area.height - modal_height) / 2;
@@ -541,6 +844,36 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
 
     frame.render_widget(Clear, modal_area);
 
+    let lines = if simulating { simulation_lines(app) } else { change_lines(app) };
+    let tab_style = |active: bool| if active {
+        Style::default().fg(theme.warning).bold()
+    } else {
+        Style::default().fg(theme.muted)
+    };
+    let title = Line::from(vec![
+        Span::raw(" Confirm Changes "),
+        Span::styled("[Changes]", tab_style(!simulating)),
+        Span::raw(" "),
+        Span::styled("[Dry run]", tab_style(simulating)),
+        Span::raw(" "),
+    ]);
+
+    let modal = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.warning)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((app.modals.changes_scroll, 0));
+
+    frame.render_widget(modal, modal_area);
+}
+
+/// The planned changes grouped by action, with sizes
+fn change_lines(app: &App) -> Vec<Line<'static>> {
+    let theme = &app.theme;
     let mut lines = vec![
         Line::from(Span::styled(
             "The following changes will be made:",
@@ -573,17 +906,26 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
         let dep_installs: Vec<_> = changes.iter()
             .filter(|c| c.action == ChangeAction::Install && c.reason == ChangeReason::Dependency)
             .collect();
+        let downgrades: Vec<_> = changes.iter()
+            .filter(|c| c.action == ChangeAction::Downgrade)
+            .collect();
         let user_removes: Vec<_> = changes.iter()
             .filter(|c| c.action == ChangeAction::Remove && c.reason == ChangeReason::UserRequested)
             .collect();
+        let dep_removes: Vec<_> = changes.iter()
+            .filter(|c| c.action == ChangeAction::Remove && c.reason == ChangeReason::Dependency)
+            .collect();
         let auto_removes: Vec<_> = changes.iter()
             .filter(|c| c.action == ChangeAction::Remove && c.reason == ChangeReason::AutoRemove)
             .collect();
+        let purges: Vec<_> = changes.iter()
+            .filter(|c| c.action == ChangeAction::Purge)
+            .collect();
 
         if !user_upgrades.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("UPGRADE ({}):", user_upgrades.len()),
-                Style::default().fg(Color::Yellow).bold(),
+                Style::default().fg(theme.warning).bold(),
             )));
             for c in &user_upgrades {
                 lines.push(Line::from(format!("  ↑ {}", get_name(c))));
@@ -594,7 +936,7 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
         if !user_installs.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("INSTALL ({}):", user_installs.len()),
-                Style::default().fg(Color::Green).bold(),
+                Style::default().fg(theme.success).bold(),
             )));
             for c in &user_installs {
                 lines.push(Line::from(format!("  + {}", get_name(c))));
@@ -605,7 +947,7 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
         if !dep_upgrades.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("AUTO-UPGRADE (dependencies) ({}):", dep_upgrades.len()),
-                Style::default().fg(Color::Cyan).bold(),
+                Style::default().fg(theme.accent).bold(),
             )));
             for c in &dep_upgrades {
                 lines.push(Line::from(format!("  ↑ {}", get_name(c))));
@@ -616,7 +958,7 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
         if !dep_installs.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("AUTO-INSTALL (dependencies) ({}):", dep_installs.len()),
-                Style::default().fg(Color::Cyan).bold(),
+                Style::default().fg(theme.accent).bold(),
             )));
             for c in &dep_installs {
                 lines.push(Line::from(format!("  + {}", get_name(c))));
@@ -624,10 +966,21 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
             lines.push(Line::from(""));
         }
 
+        if !downgrades.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("DOWNGRADE ({}):", downgrades.len()),
+                Style::default().fg(theme.downgrade).bold(),
+            )));
+            for c in &downgrades {
+                lines.push(Line::from(format!("  ↓ {}", get_name(c))));
+            }
+            lines.push(Line::from(""));
+        }
+
         if !user_removes.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("REMOVE ({}):", user_removes.len()),
-                Style::default().fg(Color::Red).bold(),
+                Style::default().fg(theme.danger).bold(),
             )));
             for c in &user_removes {
                 lines.push(Line::from(format!("  - {}", get_name(c))));
@@ -635,10 +988,21 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
             lines.push(Line::from(""));
         }
 
+        if !dep_removes.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("REMOVE (required by other changes) ({}):", dep_removes.len()),
+                Style::default().fg(theme.danger).bold(),
+            )));
+            for c in &dep_removes {
+                lines.push(Line::from(format!("  - {}", get_name(c))));
+            }
+            lines.push(Line::from(""));
+        }
+
         if !auto_removes.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("AUTO-REMOVE (no longer needed) ({}):", auto_removes.len()),
-                Style::default().fg(Color::Magenta).bold(),
+                Style::default().fg(theme.special).bold(),
             )));
             for c in &auto_removes {
                 lines.push(Line::from(format!("  X {}", get_name(c))));
@@ -646,6 +1010,17 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
             lines.push(Line::from(""));
         }
 
+        if !purges.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("PURGE (remove with configuration) ({}):", purges.len()),
+                Style::default().fg(theme.danger).bold(),
+            )));
+            for c in &purges {
+                lines.push(Line::from(format!("  _ {}", get_name(c))));
+            }
+            lines.push(Line::from(""));
+        }
+
         // Download and size info
         let download_size: u64 = changes.iter().map(|c| c.download_size).sum();
         let size_change: i64 = changes.iter().map(|c| c.size_change).sum();
@@ -666,45 +1041,252 @@ fn render_changes_modal(frame: &mut Frame, app: &mut App, area: Rect) {
         lines.push(Line::from("No changes computed"));
     }
 
-    let modal = Paragraph::new(lines)
+    let residual = app.core.residual_count();
+    if residual > 0 {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{residual} removed packages left configuration files behind - {}",
+                key_hints(app, AppState::ShowingChanges, &[(Action::PurgeResidual, "Purge all")]),
+            ),
+            Style::default().fg(theme.special),
+        )));
+    }
+
+    lines
+}
+
+/// The `apt-get -s` dry run of the plan, in dpkg order, with predicted
+/// broken states and triggers
+fn simulation_lines(app: &App) -> Vec<Line<'static>> {
+    let theme = &app.theme;
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "Dry run (apt-get -s) - nothing is changed:",
+            Style::default().bold(),
+        )),
+        Line::from(""),
+    ];
+
+    let sim = match &app.modals.simulation {
+        Some(Ok(sim)) => sim,
+        Some(Err(e)) => {
+            lines.push(Line::from(Span::styled(format!("Simulation failed: {e}"), Style::default().fg(theme.danger))));
+            return lines;
+        }
+        None => {
+            lines.push(Line::from("Nothing simulated yet"));
+            return lines;
+        }
+    };
+
+    let mut section = |heading: String, color: Color, items: Vec<Line<'static>>| {
+        if items.is_empty() {
+            return;
+        }
+        lines.push(Line::from(Span::styled(heading, Style::default().fg(color).bold())));
+        lines.extend(items);
+        lines.push(Line::from(""));
+    };
+
+    section(
+        format!("ERRORS ({}):", sim.errors.len()),
+        theme.danger,
+        sim.errors.iter().map(|e| Line::from(format!("  {e}"))).collect(),
+    );
+    section(
+        format!("UNMET DEPENDENCIES ({}):", sim.broken.len()),
+        theme.broken,
+        sim.broken.iter().map(|b| Line::from(format!("  {b}"))).collect(),
+    );
+    section(
+        format!("STEPS ({}):", sim.steps.len()),
+        theme.accent,
+        sim.steps.iter()
+            .map(|step| {
+                let mut spans = vec![Span::raw(format!("  {} {} {}", step.kind.tag(), step.package, step.detail))];
+                if !step.breaks.is_empty() {
+                    spans.push(Span::styled(
+                        format!("  breaks {} until configured", step.breaks.join(", ")),
+                        Style::default().fg(theme.warning),
+                    ));
+                }
+                Line::from(spans)
+            })
+            .collect(),
+    );
+    section(
+        format!("TRIGGERS ({}):", sim.triggers.len()),
+        theme.special,
+        sim.triggers.iter().map(|t| Line::from(format!("  {}: {}", t.package, t.name))).collect(),
+    );
+    if sim.triggers.is_empty() {
+        lines.push(Line::from(Span::styled("No triggers expected", Style::default().fg(theme.muted))));
+    }
+    lines
+}
+
+fn render_changelog_view(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
+    let targets = &app.modals.changelog_targets;
+    let full = app.modals.changelog_full;
+    let mut lines: Vec<Line> = Vec::new();
+
+    if app.modals.changelog.is_loading() {
+        let (done, total) = app.modals.changelog.progress();
+        let cancel = app.keymap.hint(AppState::ShowingChangelog, Action::Cancel);
+        let text = if total > 1 {
+            format!("{} Loading changelogs {}/{total}... ({cancel} to cancel)", app.modals.changelog.spinner(), done + 1)
+        } else {
+            format!("{} Loading changelog... ({cancel} to cancel)", app.modals.changelog.spinner())
+        };
+        lines.push(Line::from(Span::styled(text, Style::default().fg(theme.accent))));
+        lines.push(Line::from(""));
+    }
+
+    for target in targets {
+        if targets.len() > 1 {
+            lines.push(Line::from(Span::styled(
+                format!("{}: {} → {}", target.name, target.installed.as_deref().unwrap_or("-"), target.version),
+                Style::default().fg(theme.warning).bold(),
+            )));
+            lines.push(Line::from(""));
+        }
+
+        if !target.loaded {
+            lines.push(Line::from(Span::styled("  (waiting)", Style::default().fg(theme.muted))));
+            lines.push(Line::from(""));
+            continue;
+        }
+
+        if let Some(note) = &target.note {
+            lines.push(Line::from(Span::styled(format!("({note})"), Style::default().fg(theme.warning))));
+            lines.push(Line::from(""));
+        }
+
+        let entries = target.visible_entries(full);
+        if entries.is_empty() && (target.note.is_none() || !target.raw.is_empty()) {
+            if target.raw.is_empty() {
+                lines.push(Line::from(Span::styled(
+                    "No entries between the installed and candidate versions (f: full history)",
+                    Style::default().fg(theme.muted),
+                )));
+            } else {
+                lines.extend(target.raw.iter().map(|s| Line::from(s.as_str())));
+            }
+            lines.push(Line::from(""));
+        }
+
+        for entry in entries {
+            lines.push(Line::from(vec![
+                Span::styled(entry.version.clone(), Style::default().fg(theme.success).bold()),
+                Span::raw(format!(" {}", entry.distribution)),
+                Span::styled(format!("  urgency={}", entry.urgency), Style::default().fg(theme.muted)),
+            ]));
+            lines.extend(entry.changes.iter().map(|c| Line::from(format!("  {c}"))));
+            lines.push(Line::from(Span::styled(
+                format!("  -- {}  {}", entry.author, entry.date),
+                Style::default().fg(theme.muted),
+            )));
+            lines.push(Line::from(""));
+        }
+    }
+
+    let view = if full { "full history" } else { "changes since installed" };
+    let title = match targets.as_slice() {
+        [target] if target.is_upgrade() => format!(
+            " Changelog: {} ({} → {}) [{view}] ",
+            target.name, target.installed.as_deref().unwrap_or("-"), target.version,
+        ),
+        [target] => format!(" Changelog: {} ", target.name),
+        _ => format!(" Upgrade changelog: {} packages [{view}] ", targets.len()),
+    };
+
+    let changelog = Paragraph::new(lines)
         .block(
             Block::default()
-                .title(" Confirm Changes ")
+                .title(title)
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Yellow)),
+                .border_style(Style::default().fg(theme.accent)),
         )
         .wrap(Wrap { trim: false })
-        .scroll((app.modals.changes_scroll, 0));
+        .scroll((app.modals.changelog_scroll, 0));
 
-    frame.render_widget(modal, modal_area);
+    frame.render_widget(changelog, area);
 }
 
-fn render_changelog_view(frame: &mut Frame, app: &mut App, area: Rect) {
-    let pkg_name = app
-        .selected_package()
-        .map(|p| p.name.clone())
-        .unwrap_or_else(|| "Unknown".to_string());
+fn render_version_picker(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let modal_width = 76.min(area.width.saturating_sub(4));
+    let modal_height = (app.modals.versions.len() as u16 + 4).min(area.height.saturating_sub(4));
+    let modal_x = area.x + (area.width - modal_width) / 2;
+    let modal_y = area.y + (area.height - modal_height) / 2;
+    let modal_area = Rect::new(modal_x, modal_y, modal_width, modal_height);
 
-    let lines: Vec<Line> = app
-        .modals.changelog_content
-        .iter()
-        .map(|s| Line::from(s.as_str()))
+    frame.render_widget(Clear, modal_area);
+
+    let cache = app.core.cache();
+    let pkg_name = app.modals.version_pkg
+        .and_then(|id| cache.fullname_of(id))
+        .map(|n| cache.display_name(n).to_string())
+        .unwrap_or_else(|| "Unknown".to_string());
+    let chosen = app.modals.version_pkg.and_then(|id| app.core.chosen_version(id));
+
+    let rows: Vec<Row> = app.modals.versions.iter()
+        .map(|v| {
+            // I = installed, C = policy candidate, * = picked by the user
+            let marker = format!(
+                "{}{}{}",
+                if v.is_installed { "I" } else { " " },
+                if v.is_candidate { "C" } else { " " },
+                if chosen == Some(v.version.as_str()) { "*" } else { " " },
+            );
+            let source = if v.component.is_empty() {
+                format!("{}/{}", v.origin, v.archive)
+            } else {
+                format!("{}/{}/{}", v.origin, v.archive, v.component)
+            };
+            let style = if v.is_installed {
+                Style::default().fg(theme.success)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                Cell::from(marker),
+                Cell::from(v.version.clone()),
+                Cell::from(v.priority.to_string()),
+                Cell::from(source),
+                Cell::from(PackageInfo::size_str(v.download_size)),
+            ]).style(style)
+        })
         .collect();
 
-    let changelog = Paragraph::new(lines)
+    let header = Row::new(vec!["", "Version", "Pin", "Origin", "Download"])
+        .style(Style::default().bold());
+
+    let table = Table::new(rows, [
+            Constraint::Length(3),
+            Constraint::Min(16),
+            Constraint::Length(5),
+            Constraint::Min(20),
+            Constraint::Length(9),
+        ])
+        .header(header)
         .block(
             Block::default()
-                .title(format!(" Changelog: {pkg_name} "))
+                .title(format!(" Versions: {pkg_name} "))
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Cyan)),
+                .border_style(Style::default().fg(theme.accent)),
         )
-        .wrap(Wrap { trim: false })
-        .scroll((app.modals.changelog_scroll, 0));
+        .row_highlight_style(theme.selection());
 
-    frame.render_widget(changelog, area);
+    let mut state = TableState::default();
+    state.select(Some(app.modals.version_selection));
+    frame.render_stateful_widget(table, modal_area, &mut state);
 }
 
 fn render_settings_view(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
     let column_items = [
         ("Status column (S)", app.settings.show_status_column),
         ("Name column", app.settings.show_name_column),
@@ -721,7 +1303,7 @@ fn render_settings_view(frame: &mut Frame, app: &mut App, area: Rect) {
             let checkbox = if *enabled { "[X]" } else { "[ ]" };
             let text = format!("{checkbox} {label}");
             let style = if idx == app.settings_selection {
-                Style::default().bg(Color::DarkGray)
+                theme.selection()
             } else {
                 Style::default()
             };
@@ -732,32 +1314,60 @@ fn render_settings_view(frame: &mut Frame, app: &mut App, area: Rect) {
     // Add sort options
     items.push(ListItem::new(""));
     let sort_style = if app.settings_selection == 6 {
-        Style::default().bg(Color::DarkGray)
+        theme.selection()
     } else {
         Style::default()
     };
-    items.push(ListItem::new(format!("Sort by: {}", app.settings.sort_by.label())).style(sort_style));
+    items.push(ListItem::new(format!(
+        "Sort by: {} ({} while searching)",
+        app.settings.sort_by.label(),
+        SortBy::Relevance.label(),
+    )).style(sort_style));
 
     let order_style = if app.settings_selection == 7 {
-        Style::default().bg(Color::DarkGray)
+        theme.selection()
     } else {
         Style::default()
     };
     let order = if app.settings.sort_ascending { "Ascending" } else { "Descending" };
     items.push(ListItem::new(format!("Sort order: {order}")).style(order_style));
 
+    // Startup options
+    items.push(ListItem::new(""));
+    let filter_style = if app.settings_selection == 8 {
+        theme.selection()
+    } else {
+        Style::default()
+    };
+    items.push(ListItem::new(format!("Startup filter: {}", app.settings.default_filter.label())).style(filter_style));
+
+    let tab_style = if app.settings_selection == 9 {
+        theme.selection()
+    } else {
+        Style::default()
+    };
+    items.push(ListItem::new(format!("Startup details tab: {}", app.settings.details_tab.label())).style(tab_style));
+
+    let theme_style = if app.settings_selection == 10 {
+        theme.selection()
+    } else {
+        Style::default()
+    };
+    items.push(ListItem::new(format!("Theme: {}", app.settings.theme.name.label())).style(theme_style));
+
     let settings_list = List::new(items)
         .block(
             Block::default()
-                .title(" Settings ")
+                .title(" Settings (saved to ~/.config/synh8/config.toml) ")
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Yellow)),
+                .border_style(Style::default().fg(theme.warning)),
         );
 
     frame.render_widget(settings_list, area);
 }
 
 fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let Some(ref preview) = app.mark_preview else {
         return;
     };
@@ -774,7 +1384,13 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
 
     if preview.is_marking {
         // MARK operation
-        let action = if preview.is_upgrade { "upgrade" } else { "install" };
+        let action = match preview.intent {
+            UserIntent::Remove => "removal",
+            UserIntent::Purge => "purge",
+            _ if preview.is_downgrade => "downgrade",
+            _ if preview.is_upgrade => "upgrade",
+            _ => "install",
+        };
         lines.push(Line::from(Span::styled(
             format!("Mark '{}' for {}?", preview.package_name, action),
             Style::default().bold(),
@@ -784,7 +1400,7 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
         if !preview.additional_installs.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("Will install {} additional packages:", preview.additional_installs.len()),
-                Style::default().fg(Color::Green),
+                Style::default().fg(theme.success),
             )));
             for name in &preview.additional_installs {
                 lines.push(Line::from(format!("  + {name}")));
@@ -795,7 +1411,7 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
         if !preview.additional_upgrades.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("Will upgrade {} packages:", preview.additional_upgrades.len()),
-                Style::default().fg(Color::Yellow),
+                Style::default().fg(theme.warning),
             )));
             for name in &preview.additional_upgrades {
                 lines.push(Line::from(format!("  ^ {name}")));
@@ -805,8 +1421,8 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
 
         if !preview.additional_removes.is_empty() {
             lines.push(Line::from(Span::styled(
-                format!("Will remove {} packages:", preview.additional_removes.len()),
-                Style::default().fg(Color::Red),
+                format!("Will also remove {} packages:", preview.additional_removes.len()),
+                Style::default().fg(theme.danger),
             )));
             for name in &preview.additional_removes {
                 lines.push(Line::from(format!("  - {name}")));
@@ -816,7 +1432,7 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
 
         lines.push(Line::from(Span::styled(
             format!("Download size: {}", PackageInfo::size_str(preview.download_size)),
-            Style::default().fg(Color::Cyan),
+            Style::default().fg(theme.accent),
         )));
     } else {
         // UNMARK operation
@@ -830,7 +1446,7 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
         if !preview.additional_upgrades.is_empty() {
             lines.push(Line::from(Span::styled(
                 format!("This will also unmark {} packages:", preview.additional_upgrades.len()),
-                Style::default().fg(Color::Yellow),
+                Style::default().fg(theme.warning),
             )));
             for name in &preview.additional_upgrades {
                 lines.push(Line::from(format!("  {name}")));
@@ -855,7 +1471,7 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
             Block::default()
                 .title(title)
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Magenta)),
+                .border_style(Style::default().fg(theme.special)),
         )
         .wrap(Wrap { trim: false });
 
@@ -869,14 +1485,19 @@ fn render_mark_preview_modal(frame: &mut Frame, app: &App, area: Rect) {
         1,
     );
     let hint = Paragraph::new(Span::styled(
-        " y/Enter: Confirm │ n/Esc: Cancel │ j/k: Scroll ",
-        Style::default().fg(Color::DarkGray),
+        format!(" {} ", key_hints(app, AppState::ShowingMarkConfirm, &[
+            (Action::Confirm, "Confirm"),
+            (Action::Cancel, "Cancel"),
+            (Action::Down, "Scroll"),
+        ])),
+        Style::default().fg(theme.muted),
     ))
     .alignment(Alignment::Center);
     frame.render_widget(hint, hint_area);
 }
 
-fn render_exit_confirm_modal(frame: &mut Frame, _app: &App, area: Rect) {
+fn render_exit_confirm_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let modal_width = 50.min(area.width.saturating_sub(4));
     let modal_height = 7;
     let modal_x = area.x + (area.width - modal_width) / 2;
@@ -889,14 +1510,14 @@ fn render_exit_confirm_modal(frame: &mut Frame, _app: &App, area: Rect) {
         Line::from(""),
         Line::from(Span::styled(
             "You have unsaved changes!",
-            Style::default().fg(Color::Red).bold(),
+            Style::default().fg(theme.danger).bold(),
         )),
         Line::from(""),
         Line::from("Really quit without applying?"),
         Line::from(""),
         Line::from(Span::styled(
-            "y/Enter: Quit │ n/Esc: Cancel",
-            Style::default().fg(Color::DarkGray),
+            key_hints(app, AppState::ConfirmExit, &[(Action::Confirm, "Quit"), (Action::Cancel, "Cancel")]),
+            Style::default().fg(theme.muted),
         )),
     ];
 
@@ -905,10 +1526,69 @@ fn render_exit_confirm_modal(frame: &mut Frame, _app: &App, area: Rect) {
             Block::default()
                 .title(" Confirm Exit ")
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Red)),
+                .border_style(Style::default().fg(theme.danger)),
         )
         .alignment(Alignment::Center);
 
     frame.render_widget(modal, modal_area);
 }
 
+/// "keys:Label" for each action that has keys in `state`, e.g. "y/Enter:Apply │ n/Esc:Cancel"
+fn key_hints(app: &App, state: AppState, hints: &[(Action, &str)]) -> String {
+    hints.iter()
+        .filter_map(|(action, label)| {
+            let binding = app.keymap.bindings(state).iter().find(|b| b.action == *action)?;
+            (!binding.keys.is_empty()).then(|| format!("{}:{label}", binding.keys_label()))
+        })
+        .collect::<Vec<_>>()
+        .join(" │ ")
+}
+
+/// Every key of the state the help was opened from, straight from the keymap
+fn render_help_overlay(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let context = app.help_context();
+    let bindings = app.keymap.bindings(context);
+
+    let key_width = bindings.iter()
+        .map(|b| b.keys_label().chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("(unbound)".len());
+
+    let mut lines: Vec<Line> = bindings.iter()
+        .map(|b| {
+            let keys = if b.keys.is_empty() {
+                Span::styled(format!("{:<key_width$}", "(unbound)"), Style::default().fg(theme.muted))
+            } else {
+                Span::styled(format!("{:<key_width$}", b.keys_label()), Style::default().fg(theme.accent).bold())
+            };
+            Line::from(vec![Span::raw(" "), keys, Span::raw("  "), Span::raw(b.description)])
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        format!(" Rebind in [keys.{}] of config.toml", Keymap::context_name(context)),
+        Style::default().fg(theme.muted),
+    )));
+
+    let content_width = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+    let modal_width = (content_width + 3).min(area.width.saturating_sub(4));
+    let modal_height = (lines.len() as u16 + 2).min(area.height.saturating_sub(2));
+    let modal_x = area.x + (area.width - modal_width) / 2;
+    let modal_y = area.y + (area.height - modal_height) / 2;
+    let modal_area = Rect::new(modal_x, modal_y, modal_width, modal_height);
+
+    frame.render_widget(Clear, modal_area);
+
+    let modal = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!(" Keys: {} ", Keymap::context_name(context)))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.accent)),
+        )
+        .scroll((app.modals.help_scroll, 0));
+
+    frame.render_widget(modal, modal_area);
+}
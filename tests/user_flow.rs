@@ -0,0 +1,210 @@
+//! The scenarios of docs/user-flow.md, driven through `ManagerState` on top
+//! of the in-memory backend
+
+use synh8::backend::Backend;
+use synh8::core::ManagerState;
+use synh8::fake::FakeBackend;
+use synh8::types::*;
+
+use PackageStatus::*;
+
+const USER_FLOW: &str = include_str!("fixtures/user_flow.txt");
+
+fn manager(fixture: &str) -> ManagerState<FakeBackend> {
+    let mut core = ManagerState::with_backend(FakeBackend::from_fixture(fixture).unwrap());
+    core.apply_filter(FilterCategory::All);
+    core
+}
+
+fn id(core: &ManagerState<FakeBackend>, name: &str) -> PackageId {
+    core.cache().get_id(&format!("{name}:amd64")).unwrap()
+}
+
+/// Display names of some packages, sorted
+fn names(core: &ManagerState<FakeBackend>, ids: &[PackageId]) -> Vec<String> {
+    let cache = core.cache();
+    let mut names: Vec<String> = ids.iter()
+        .map(|&id| cache.display_name(cache.fullname_of(id).unwrap()).to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Status of every package in the list, by name
+fn statuses(core: &ManagerState<FakeBackend>) -> Vec<(String, PackageStatus)> {
+    let mut statuses: Vec<_> = core.list().iter()
+        .map(|p| (core.cache().display_name(&p.name).to_string(), p.status))
+        .collect();
+    statuses.sort_by(|a, b| a.0.cmp(&b.0));
+    statuses
+}
+
+fn expect(list: &[(&str, PackageStatus)]) -> Vec<(String, PackageStatus)> {
+    list.iter().map(|&(name, status)| (name.to_string(), status)).collect()
+}
+
+/// Mark `name` and return the other packages the plan pulled in
+fn toggle_marks(core: &mut ManagerState<FakeBackend>, name: &str) -> Vec<String> {
+    match core.toggle(id(core, name)) {
+        ToggleResult::Marked { additional, .. } => names(core, &additional),
+        other => panic!("toggling {name} did not mark it: {other:?}"),
+    }
+}
+
+/// Unmark `name` and return the other packages that went with it
+fn toggle_unmarks(core: &mut ManagerState<FakeBackend>, name: &str) -> Vec<String> {
+    match core.toggle(id(core, name)) {
+        ToggleResult::Unmarked { also_unmarked, .. } => names(core, &also_unmarked),
+        other => panic!("toggling {name} did not unmark it: {other:?}"),
+    }
+}
+
+#[test]
+fn marking_pulls_in_dependencies() {
+    let mut core = manager(USER_FLOW);
+    assert_eq!(statuses(&core), expect(&[
+        ("bar", Upgradable), ("baz", Upgradable), ("foo", Upgradable), ("qux", Upgradable),
+    ]));
+
+    // Step 1: "Also mark bar, baz?"
+    assert_eq!(toggle_marks(&mut core, "foo"), ["bar", "baz"]);
+    assert_eq!(statuses(&core), expect(&[
+        ("bar", MarkedForUpgrade), ("baz", MarkedForUpgrade), ("foo", MarkedForUpgrade), ("qux", Upgradable),
+    ]));
+}
+
+#[test]
+fn marking_with_marked_dependencies_needs_no_confirmation() {
+    let mut core = manager(USER_FLOW);
+    toggle_marks(&mut core, "foo");
+
+    // Step 2: bar is already marked
+    assert!(toggle_marks(&mut core, "qux").is_empty());
+    assert_eq!(statuses(&core), expect(&[
+        ("bar", MarkedForUpgrade), ("baz", MarkedForUpgrade), ("foo", MarkedForUpgrade), ("qux", MarkedForUpgrade),
+    ]));
+}
+
+#[test]
+fn unmarking_a_dependency_cascades() {
+    let mut core = manager(USER_FLOW);
+    toggle_marks(&mut core, "foo");
+    toggle_marks(&mut core, "qux");
+
+    // Step 3(a): "This will also unmark foo, qux"; baz was only needed by foo
+    assert_eq!(toggle_unmarks(&mut core, "bar"), ["baz", "foo", "qux"]);
+    assert_eq!(statuses(&core), expect(&[
+        ("bar", Upgradable), ("baz", Upgradable), ("foo", Upgradable), ("qux", Upgradable),
+    ]));
+    assert!(!core.has_marks());
+}
+
+#[test]
+fn unmarking_a_leaf_leaves_the_rest() {
+    let mut core = manager(USER_FLOW);
+    toggle_marks(&mut core, "foo");
+    toggle_marks(&mut core, "qux");
+
+    // Step 3(b): nothing depends on qux
+    assert!(toggle_unmarks(&mut core, "qux").is_empty());
+    assert_eq!(statuses(&core), expect(&[
+        ("bar", MarkedForUpgrade), ("baz", MarkedForUpgrade), ("foo", MarkedForUpgrade), ("qux", Upgradable),
+    ]));
+}
+
+#[test]
+fn plan_tells_requested_from_dependencies() {
+    let mut core = manager(USER_FLOW);
+    toggle_marks(&mut core, "foo");
+
+    let planned = core.planned().unwrap();
+    let mut changes: Vec<_> = planned.changes.iter()
+        .map(|c| (names(&core, &[c.package]).remove(0), c.action, c.reason))
+        .collect();
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(changes, [
+        ("bar".to_string(), ChangeAction::Upgrade, ChangeReason::Dependency),
+        ("baz".to_string(), ChangeAction::Upgrade, ChangeReason::Dependency),
+        ("foo".to_string(), ChangeAction::Upgrade, ChangeReason::UserRequested),
+    ]);
+    assert_eq!(planned.download_size, 700);
+    assert_eq!(planned.install_size_change, 0);
+    assert!(planned.errors.is_empty());
+}
+
+#[test]
+fn applying_changes_returns_to_clean() {
+    let mut core = manager(USER_FLOW);
+    toggle_marks(&mut core, "foo");
+
+    core.commit().unwrap();
+    core.rebuild_list();
+    assert!(core.is_clean());
+    assert_eq!(statuses(&core), expect(&[
+        ("bar", Installed), ("baz", Installed), ("foo", Installed), ("qux", Upgradable),
+    ]));
+    assert_eq!(core.cache().installed_version(id(&core, "foo")).as_deref(), Some("2.0"));
+}
+
+#[test]
+fn removal_takes_dependents_along() {
+    let mut core = manager("\
+Package: app
+Installed: 1.0
+Depends: libfoo
+
+Package: libfoo
+Installed: 1.0
+Auto-Installed: yes
+
+Package: other
+Installed: 1.0
+");
+
+    match core.toggle_remove(id(&core, "libfoo"), false) {
+        ToggleResult::Marked { additional, .. } => assert_eq!(names(&core, &additional), ["app"]),
+        other => panic!("libfoo was not marked for removal: {other:?}"),
+    }
+    assert_eq!(statuses(&core), expect(&[
+        ("app", MarkedForRemove), ("libfoo", MarkedForRemove), ("other", Installed),
+    ]));
+
+    // Unmarking the removal brings app back too
+    assert_eq!(toggle_unmarks(&mut core, "libfoo"), ["app"]);
+    assert!(!core.has_marks());
+}
+
+#[test]
+fn restoring_marks_brings_back_intents_and_versions() {
+    let mut core = manager(USER_FLOW);
+    toggle_marks(&mut core, "foo");
+    core.mark_version(id(&core, "qux"), "2.0");
+
+    // Cancelling the cascade of unmarking bar
+    let before = core.user_marks();
+    toggle_unmarks(&mut core, "bar");
+    let changed = core.changed_marks(&before, id(&core, "bar"));
+    let packages: Vec<_> = changed.iter().map(|mark| mark.package).collect();
+    assert_eq!(names(&core, &packages), ["foo", "qux"]);
+    core.restore_marks(&changed);
+    assert_eq!(core.user_intent(id(&core, "foo")), UserIntent::Install);
+    assert_eq!(core.chosen_version(id(&core, "qux")), Some("2.0"));
+
+    // Cancelling a purge that replaced the picked version
+    let before = core.user_marks();
+    core.toggle_remove(id(&core, "qux"), true);
+    assert_eq!(core.chosen_version(id(&core, "qux")), None);
+    core.restore_marks(&core.changed_marks(&before, id(&core, "qux")));
+    assert_eq!(core.user_intent(id(&core, "qux")), UserIntent::Install);
+    assert_eq!(core.chosen_version(id(&core, "qux")), Some("2.0"));
+}
+
+#[test]
+fn section_filter_narrows_the_list() {
+    let mut core = manager(USER_FLOW);
+    assert_eq!(core.section_counts().get("libs"), Some(&2));
+
+    core.set_section_filter(Some("libs".to_string()));
+    core.rebuild_list();
+    assert_eq!(statuses(&core), expect(&[("bar", Upgradable), ("baz", Upgradable)]));
+}